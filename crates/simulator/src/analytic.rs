@@ -0,0 +1,350 @@
+//! Erlang reduced-load approximation of the highway.
+//!
+//! Every station is a guard channel cell. Handovers into a station are the calls carried by its
+//! neighbours that cross into it, and the stations are solved together as a fixed point.
+//! Call durations and station dwell times are taken as exponential with the input model's means,
+//! so results approximate the simulator rather than match it exactly. Ramps are not modelled.
+
+use std::{io, path::Path};
+
+use probability::distribution::{Distribution, Mean};
+use simulator_core::{analytic::GuardChannelCell, stats::student_t_quantile};
+
+use crate::{
+    base_station::NUM_STATIONS,
+    event::PerfMeasure,
+    generator::{InputModel, VEHICLE_LOC_DIST},
+    FloatingPoint,
+};
+
+/// Fixed point iterations stop when no handover rate changes more than this.
+const TOLERANCE: f64 = 1e-12;
+
+/// Fixed point iterations give up after this many rounds.
+const MAX_ITERATIONS: usize = 10_000;
+
+/// Rates of the highway derived from an input model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighwayModel {
+    /// Channels of every station
+    pub channels: usize,
+
+    /// Channels reserved for handovers in every station
+    pub reserved: usize,
+
+    /// New calls per second at each station
+    pub new_rates: Vec<f64>,
+
+    /// Probability that a vehicle travels west to east
+    pub west_to_east: f64,
+
+    /// Rate at which a call ends
+    pub completion_rate: f64,
+
+    /// Rate at which a vehicle leaves its station
+    pub crossing_rate: f64,
+}
+
+/// Approximate performance of a single station.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StationApproximation {
+    pub new_rate: f64,
+    pub handover_rate: f64,
+    pub blocking: f64,
+    pub dropping: f64,
+}
+
+/// Approximate performance of the highway.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighwayApproximation {
+    pub stations: Vec<StationApproximation>,
+
+    /// Fixed point iterations until convergence
+    pub iterations: usize,
+}
+
+impl HighwayModel {
+    /// Derive rates from the means of the input model, with any unset distribution defaulted.
+    pub fn new(model: &InputModel, channels: usize, reserved: usize) -> Self {
+        let model = model.clone().or_default();
+        let arrival_rate = 1.0 / model.inter_arrival.as_ref().unwrap().mean();
+
+        // stations are the floored cell tower variate, wrapped around the highway
+        let cell_tower = model.cell_tower.as_ref().unwrap();
+        let shares = (0..NUM_STATIONS)
+            .map(|s| cell_tower.distribution((s + 1) as f64) - cell_tower.distribution(s as f64))
+            .collect::<Vec<_>>();
+        let total_share = shares.iter().sum::<f64>();
+
+        let station_length = VEHICLE_LOC_DIST.1 - VEHICLE_LOC_DIST.0;
+        let velocity = model.vehicle_velocity.as_ref().unwrap().mean() / 3.6;
+
+        Self {
+            channels,
+            reserved,
+            new_rates: shares
+                .iter()
+                .map(|share| arrival_rate * share / total_share)
+                .collect(),
+            west_to_east: 1.0 - model.vehicle_direction.as_ref().unwrap().distribution(0.5),
+            completion_rate: 1.0 / model.call_duration.as_ref().unwrap().mean(),
+            crossing_rate: velocity / station_length,
+        }
+    }
+
+    /// Solve the reduced-load fixed point for the handover rates into every station.
+    pub fn solve(&self) -> HighwayApproximation {
+        let release_rate = self.completion_rate + self.crossing_rate;
+        let direction_shares = [self.west_to_east, 1.0 - self.west_to_east];
+
+        // handover rates into each station, west to east and east to west
+        let mut handovers = vec![[0.0; 2]; NUM_STATIONS];
+        let mut stations = Vec::new();
+        let mut iterations = 0;
+
+        while iterations < MAX_ITERATIONS {
+            iterations += 1;
+
+            stations = (0..NUM_STATIONS)
+                .map(|s| {
+                    let handover_rate = handovers[s][0] + handovers[s][1];
+                    let solution = GuardChannelCell {
+                        channels: self.channels,
+                        guard: self.reserved,
+                        new_rate: self.new_rates[s],
+                        handover_rate,
+                        service_rate: release_rate,
+                    }
+                    .solve();
+
+                    StationApproximation {
+                        new_rate: self.new_rates[s],
+                        handover_rate,
+                        blocking: solution.blocking,
+                        dropping: solution.dropping,
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let mut next = vec![[0.0; 2]; NUM_STATIONS];
+            for (s, station) in stations.iter().enumerate() {
+                for (dir, share) in direction_shares.iter().enumerate() {
+                    let admitted = station.new_rate * share * (1.0 - station.blocking)
+                        + handovers[s][dir] * (1.0 - station.dropping);
+                    let crossing = admitted / release_rate * self.crossing_rate;
+
+                    // calls leaving either end of the highway are released
+                    let neighbour = match dir {
+                        0 => s.checked_add(1).filter(|n| *n < NUM_STATIONS),
+                        _ => s.checked_sub(1),
+                    };
+                    if let Some(n) = neighbour {
+                        next[n][dir] = crossing;
+                    }
+                }
+            }
+
+            let change = handovers
+                .iter()
+                .zip(&next)
+                .flat_map(|(a, b)| [(a[0] - b[0]).abs(), (a[1] - b[1]).abs()])
+                .fold(0.0, f64::max);
+            handovers = next;
+
+            if change < TOLERANCE {
+                break;
+            }
+        }
+
+        HighwayApproximation {
+            stations,
+            iterations,
+        }
+    }
+}
+
+impl HighwayApproximation {
+    /// Blocked and dropped calls as fractions of initiated calls, as in the simulator.
+    pub fn perf_measure(&self) -> PerfMeasure {
+        let new_rate = self.stations.iter().map(|s| s.new_rate).sum::<f64>();
+
+        PerfMeasure {
+            blocked_calls: (self
+                .stations
+                .iter()
+                .map(|s| s.new_rate * s.blocking)
+                .sum::<f64>()
+                / new_rate) as FloatingPoint,
+            dropped_calls: (self
+                .stations
+                .iter()
+                .map(|s| s.handover_rate * s.dropping)
+                .sum::<f64>()
+                / new_rate) as FloatingPoint,
+        }
+    }
+
+    /// Print the approximation per station, and compared to simulated performance measures.
+    pub fn print(&self, simulated: Option<&[PerfMeasure]>) {
+        println!(
+            "{:>7} {:>10} {:>10} {:>10} {:>10}",
+            "station", "new/s", "handover/s", "blocking", "dropping"
+        );
+        for (s, station) in self.stations.iter().enumerate() {
+            println!(
+                "{:>7} {:>10.5} {:>10.5} {:>10.6} {:>10.6}",
+                s + 1,
+                station.new_rate,
+                station.handover_rate,
+                station.blocking,
+                station.dropping
+            );
+        }
+
+        let perf = self.perf_measure();
+        println!("\nconverged after {} iterations", self.iterations);
+        println!(
+            "analytic:  {:.4}% blocked, {:.4}% dropped",
+            perf.blocked_calls * 100.0,
+            perf.dropped_calls * 100.0
+        );
+
+        let simulated = match simulated {
+            Some(simulated) if !simulated.is_empty() => simulated,
+            _ => return,
+        };
+
+        let (blocked, blocked_hw) = mean_ci(simulated.iter().map(|p| p.blocked_calls));
+        let (dropped, dropped_hw) = mean_ci(simulated.iter().map(|p| p.dropped_calls));
+        println!(
+            "simulated: {:.4}% ± {:.4}% blocked, {:.4}% ± {:.4}% dropped ({} runs, 95% CI)",
+            blocked * 100.0,
+            blocked_hw * 100.0,
+            dropped * 100.0,
+            dropped_hw * 100.0,
+            simulated.len()
+        );
+    }
+}
+
+/// Read simulated performance measures from a csv file.
+pub fn read_perf_measures<P: AsRef<Path>>(path: P) -> io::Result<Vec<PerfMeasure>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let perf = reader
+        .deserialize()
+        .collect::<Result<Vec<PerfMeasure>, _>>()?;
+
+    Ok(perf)
+}
+
+/// Sample mean and Student-t 95% confidence half-width.
+fn mean_ci(samples: impl Iterator<Item = FloatingPoint>) -> (f64, f64) {
+    let samples = samples.map(|x| x as f64).collect::<Vec<_>>();
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let half_width = match samples.len() > 1 {
+        true => {
+            let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            student_t_quantile(0.975, n - 1.0) * (var / n).sqrt()
+        }
+        false => 0.0,
+    };
+
+    (mean, half_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::source;
+    use simulator_core::{analytic::erlang_b, EventRunner};
+
+    use super::*;
+    use crate::{
+        base_station::NUM_CHANNELS,
+        generator::CallEventGenerator,
+        logic::{EventProcessor, Shared},
+    };
+
+    #[test]
+    fn test_stationary_vehicles() {
+        // without handovers, each station is an Erlang loss system
+        let model = HighwayModel {
+            crossing_rate: 0.0,
+            ..HighwayModel::new(&InputModel::default(), 10, 0)
+        };
+        let approx = model.solve();
+
+        assert_eq!(approx.iterations, 1);
+        let expected = erlang_b(model.new_rates[0] / model.completion_rate, 10);
+        assert!((approx.perf_measure().blocked_calls - expected).abs() < 1e-12);
+        assert_eq!(approx.perf_measure().dropped_calls, 0.0);
+    }
+
+    #[test]
+    fn test_handover_flow() {
+        let model = HighwayModel::new(&InputModel::default(), 10, 1);
+        let approx = model.solve();
+
+        // the end stations only receive handovers from one side
+        let first = approx.stations[0].handover_rate;
+        let middle = approx.stations[NUM_STATIONS / 2].handover_rate;
+        assert!(first > 0.0 && first < middle);
+        assert!(approx.stations.iter().all(|s| s.blocking > s.dropping));
+
+        let open = HighwayModel {
+            reserved: 0,
+            ..model
+        }
+        .solve()
+        .perf_measure();
+        let perf = approx.perf_measure();
+        assert!(perf.blocked_calls > open.blocked_calls);
+        assert!(perf.dropped_calls < open.dropped_calls);
+    }
+
+    #[test]
+    fn test_matches_simulation() {
+        let input = InputModel {
+            inter_arrival: Some("exponential(0.5)".parse().unwrap()),
+            ..Default::default()
+        };
+        let perf = HighwayModel::new(&input, 10, 0).solve().perf_measure();
+
+        let runs = 4;
+        let simulated = (0..runs)
+            .map(|run| {
+                let events = CallEventGenerator::new(run + 1, source::default(run as u64), &input)
+                    .take(20_000)
+                    .collect();
+                let mut sim = EventRunner::init(
+                    EventProcessor::new(run + 1, events),
+                    Some(Shared::with_channels(NUM_CHANNELS, 0)),
+                );
+                sim.run();
+                sim.performance_measure(0)
+            })
+            .fold(
+                PerfMeasure {
+                    blocked_calls: 0.0,
+                    dropped_calls: 0.0,
+                },
+                |a, b| a + b,
+            )
+            / runs as f64;
+
+        // the approximation is within a quarter of simulated blocking and dropping
+        let close = |analytic: f64, simulated: f64| (analytic - simulated).abs() < 0.25 * simulated;
+        assert!(
+            close(perf.blocked_calls, simulated.blocked_calls),
+            "{:?} vs {:?}",
+            perf,
+            simulated
+        );
+        assert!(
+            close(perf.dropped_calls, simulated.dropped_calls),
+            "{:?} vs {:?}",
+            perf,
+            simulated
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Effectiveness of antithetic runs, from both halves of every pair.
+//!
+//! Each half of a pair is distributed as an independent run, so the halves also give the
+//! variance of naive runs, without simulating them. Antithetic pairs pay for their variance
+//! reduction with slower event generation, which is weighed against a naive run's generation
+//! time, measured once per invocation on the streams of the first run.
+
+use serde::Serialize;
+use simulator_core::stats::correlation;
+
+use crate::{event::PerfMeasure, FloatingPoint};
+
+/// Both halves of an antithetic pair, with the time spent on them.
+#[derive(Clone, Debug, Serialize)]
+pub struct AntitheticPair {
+    pub run: usize,
+    pub blocked_calls_a: FloatingPoint,
+    pub dropped_calls_a: FloatingPoint,
+    pub blocked_calls_b: FloatingPoint,
+    pub dropped_calls_b: FloatingPoint,
+
+    /// Seconds spent generating the events of both halves
+    pub generation_seconds: f64,
+
+    /// Seconds spent simulating both halves
+    pub simulation_seconds: f64,
+}
+
+/// Effectiveness of antithetic pairs for one performance measure.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Effectiveness {
+    /// Correlation between the halves of a pair
+    pub correlation: f64,
+
+    /// Variance of the mean over pairs
+    pub paired_variance: f64,
+
+    /// Variance of the mean over as many independent runs as there are halves
+    pub naive_variance: f64,
+
+    /// Variance reduction per CPU second over naive runs, above one if pairs are worth it
+    pub speed_up: f64,
+}
+
+impl AntitheticPair {
+    pub fn new(run: usize, a: PerfMeasure, b: PerfMeasure) -> Self {
+        Self {
+            run,
+            blocked_calls_a: a.blocked_calls,
+            dropped_calls_a: a.dropped_calls,
+            blocked_calls_b: b.blocked_calls,
+            dropped_calls_b: b.dropped_calls,
+            generation_seconds: 0.0,
+            simulation_seconds: 0.0,
+        }
+    }
+}
+
+/// Effectiveness for blocked and dropped calls, given the seconds spent generating the events
+/// of one naive run.
+pub fn effectiveness(
+    pairs: &[AntitheticPair],
+    naive_generation_seconds: f64,
+) -> Result<(Effectiveness, Effectiveness), String> {
+    if pairs.len() < 2 {
+        return Err("effectiveness of antithetic runs needs at least two pairs".to_string());
+    }
+
+    let paired_seconds = pairs
+        .iter()
+        .map(|p| p.generation_seconds + p.simulation_seconds)
+        .sum::<f64>();
+    let naive_seconds = pairs
+        .iter()
+        .map(|p| 2.0 * naive_generation_seconds + p.simulation_seconds)
+        .sum::<f64>();
+
+    let measure = |a: Vec<f64>, b: Vec<f64>| {
+        let n = a.len() as f64;
+        let variance = |x: &[f64]| {
+            let mean = x.iter().sum::<f64>() / n;
+            x.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        };
+
+        let averages = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| (a + b) / 2.0)
+            .collect::<Vec<_>>();
+        let paired_variance = variance(&averages) / n;
+        let naive_variance = (variance(&a) + variance(&b)) / 2.0 / (2.0 * n);
+
+        Effectiveness {
+            correlation: correlation(&a, &b),
+            paired_variance,
+            naive_variance,
+            speed_up: (naive_variance * naive_seconds) / (paired_variance * paired_seconds),
+        }
+    };
+    let column = |value: fn(&AntitheticPair) -> FloatingPoint| {
+        pairs.iter().map(|p| value(p) as f64).collect::<Vec<_>>()
+    };
+
+    Ok((
+        measure(column(|p| p.blocked_calls_a), column(|p| p.blocked_calls_b)),
+        measure(column(|p| p.dropped_calls_a), column(|p| p.dropped_calls_b)),
+    ))
+}
+
+/// Print the effectiveness of antithetic pairs, along with the time spent on them.
+pub fn print_effectiveness(
+    pairs: &[AntitheticPair],
+    naive_generation_seconds: f64,
+    blocked: &Effectiveness,
+    dropped: &Effectiveness,
+) {
+    let seconds = |value: fn(&AntitheticPair) -> f64| pairs.iter().map(value).sum::<f64>();
+    println!(
+        "\nantithetic pairs: {:.2}s generating ({:.2}s for naive runs), {:.2}s simulating",
+        seconds(|p| p.generation_seconds),
+        2.0 * naive_generation_seconds * pairs.len() as f64,
+        seconds(|p| p.simulation_seconds)
+    );
+
+    println!(
+        "{:>8} {:>12} {:>12} {:>12} {:>10} {:>10}",
+        "measure", "correlation", "paired var", "naive var", "reduction", "speed-up"
+    );
+    for (name, e) in [("blocked", blocked), ("dropped", dropped)] {
+        println!(
+            "{:>8} {:>12.3} {:>12.4e} {:>12.4e} {:>10.2} {:>10.2}",
+            name,
+            e.correlation,
+            e.paired_variance,
+            e.naive_variance,
+            e.naive_variance / e.paired_variance,
+            e.speed_up
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(run: usize, a: FloatingPoint, b: FloatingPoint) -> AntitheticPair {
+        let perf = |x| PerfMeasure {
+            blocked_calls: x,
+            dropped_calls: x / 2.0,
+        };
+        AntitheticPair {
+            generation_seconds: 2.0,
+            simulation_seconds: 1.0,
+            ..AntitheticPair::new(run, perf(a), perf(b))
+        }
+    }
+
+    #[test]
+    fn test_effectiveness() {
+        // perfectly negatively correlated halves leave no variance in the pairs
+        let pairs = [pair(1, 1.0, 3.0), pair(2, 3.0, 1.0), pair(3, 2.0, 2.0)];
+        let (blocked, dropped) = effectiveness(&pairs, 0.5).unwrap();
+        assert!((blocked.correlation + 1.0).abs() < 1e-12);
+        assert_eq!(blocked.paired_variance, 0.0);
+        assert!((blocked.naive_variance - 1.0 / 6.0).abs() < 1e-12);
+        assert_eq!(dropped.naive_variance, blocked.naive_variance / 4.0);
+
+        // uncorrelated halves reduce nothing, and cost more per run
+        let pairs = [
+            pair(1, 1.0, 1.0),
+            pair(2, 1.0, 3.0),
+            pair(3, 3.0, 1.0),
+            pair(4, 3.0, 3.0),
+        ];
+        let (blocked, _) = effectiveness(&pairs, 0.5).unwrap();
+        assert_eq!(blocked.correlation, 0.0);
+        assert!((blocked.naive_variance - blocked.paired_variance).abs() < 1e-12);
+        assert!((blocked.speed_up - 2.0 / 3.0).abs() < 1e-12);
+
+        assert!(effectiveness(&pairs[..1], 0.5).is_err());
+    }
+}
@@ -1,39 +1,226 @@
+use std::io;
+
+use probability::distribution::Mean;
+use simulator_core::{output::Compression, stats::DEFAULT_CONFIDENCE, Variate};
+
+use crate::{
+    columnar::Format,
+    config::ScenarioConfig,
+    experiment::Scenario,
+    generator::{Input, InputModel},
+    optimize::StationReserves,
+    ramp::Ramp,
+    replay::{DirectionDefault, PositionDefault},
+    sweep::{self, DesignPoint, Levels},
+};
+
 /// Main CLI arguments
 #[derive(Debug, clap::Parser)]
 pub struct CliArgs {
-    /// Number of simulation runs to perform
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+/// Modes of the simulator, each with the options it uses.
+///
+/// Settings not given as flags are taken from `--scenario`, or their defaults.
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Simulate `--num-runs` runs and write their performance measures and event log.
+    Run(RunArgs),
+
+    /// Generate call initiation events, without running the simulation.
+    ///
+    /// Used for verifying input modelling correctness.
+    Generate(GenerateArgs),
+
+    /// Replay call initiations from a csv file instead of generating them.
+    ///
+    /// Accepts the course's test data or a file written by `generate`.
+    /// Each run in the file is simulated once.
+    Replay(ReplayArgs),
+
+    /// Simulate every combination of factor levels with the same seeds, and summarise the
+    /// performance measures of each combination in one csv row.
+    ///
+    /// Levels are comma separated values and inclusive ranges `start:end[:step]`.
+    /// Factors that are not given keep the level of the scenario.
+    Sweep(SweepArgs),
+
+    /// Compare configurations against the first one with paired differences over the same
+    /// seeds, e.g. `compare --reserved 0,1,2`.
+    ///
+    /// Run `r` of every configuration draws the same random numbers for every input variate,
+    /// so the differences have less variance than between independent runs.
+    Compare(CompareArgs),
+
+    /// Find the fewest reserved handover channels that keep blocked and dropped calls under
+    /// their limits, with a stated probability of correct selection.
+    ///
+    /// Candidates are every uniform reservation up to `--max-reserved` and any per-station
+    /// `--profile`. Runs are added until every decision is statistically settled.
+    Optimize(OptimizeArgs),
+
+    /// Compare the variance of the mean performance measure under naive, antithetic,
+    /// stratified, Latin hypercube and randomized quasi-Monte Carlo sampling, over independent
+    /// designs with the same number of runs each.
+    SamplingReport(SamplingReportArgs),
+
+    /// Estimate blocked and dropped calls with importance sampling, from `--num-runs` runs with
+    /// tilted call arrivals and durations weighted by their likelihood ratios. Both must be
+    /// exponential.
+    Importance(ImportanceArgs),
+
+    /// Estimate blocked and dropped calls over the regeneration cycles of one long run of
+    /// `--num-events` call initiations, starting whenever every station is idle. No warmup is
+    /// needed, but cycles only complete at loads where the highway is sometimes empty.
+    Regenerative(RegenerativeArgs),
+
+    /// Analyse inputs and outputs without simulating.
+    Analyze(AnalyzeArgs),
+}
+
+/// Analysis arguments
+#[derive(Debug, clap::Args)]
+pub struct AnalyzeArgs {
+    #[clap(subcommand)]
+    pub command: AnalyzeCommand,
+}
+
+/// Analyses
+#[derive(Debug, clap::Subcommand)]
+pub enum AnalyzeCommand {
+    /// Fit input distributions to the test data and test their goodness of fit, along with
+    /// generated call initiations against the input model.
+    Input(AnalyzeInputArgs),
+
+    /// Approximate blocking and dropping with an Erlang reduced-load model of the highway,
+    /// using the input model and reserved handover channels.
+    Analytic(AnalyticArgs),
+
+    /// Summarise performance measure csv files with Student-t, percentile and BCa bootstrap
+    /// intervals, and test two files for a difference with paired and Welch t tests.
+    PerfStats(PerfStatsArgs),
+}
+
+/// Flags overriding part of the settings of a scenario
+pub trait Overrides {
+    fn apply(&self, config: &mut ScenarioConfig);
+}
+
+/// Scenario file and input model
+#[derive(Debug, clap::Args)]
+pub struct ModelArgs {
+    /// Toml scenario file with every setting of the simulation, in the tables `[topology]`,
+    /// `[inputs]`, `[admission]`, `[run]` and `[output]`. Flags take precedence over the file.
     #[clap(long)]
-    #[clap(default_value_t = 100)]
-    pub num_runs: u32,
+    pub scenario: Option<String>,
 
-    /// Number of call inititation events to generate per simulation run
+    /// Toml file with input distributions, e.g. `call_duration = "lognormal(4.2, 0.8)"`.
+    ///
+    /// Keys are call_duration, inter_arrival, cell_tower, vehicle_velocity, vehicle_position and
+    /// vehicle_direction. Distributions given as flags take precedence over the file.
     #[clap(long)]
-    #[clap(default_value_t = 10_000)]
-    pub num_events: u32,
+    pub input_model: Option<String>,
 
-    /// The number of channels reserved for handover requests
+    /// Call duration distribution in seconds, e.g. `lognormal(4.2, 0.8)`.
+    ///
+    /// Available distributions: exponential(mean[, loc]), uniform(min, max), normal(mean, stddev),
+    /// lognormal(mu, sigma), weibull(shape, scale), gamma(shape, scale), erlang(k, mean),
+    /// hyperexponential(p1, mean1, p2, mean2, ...), triangular(min, mode, max),
+    /// empirical(x1, x2, ...), empirical("file.csv", "column"),
+    /// fit("file.csv", "column"[, "family"]),
+    /// truncated(<dist>, low, high), clamped(<dist>, low, high) and scaled(<dist>, factor).
+    #[clap(long)]
+    pub call_duration: Option<Variate>,
+
+    /// Call inter-arrival time distribution in seconds
+    #[clap(long)]
+    pub inter_arrival: Option<Variate>,
+
+    /// Base station distribution of call initiations, floored to a station index (0-19)
+    #[clap(long)]
+    pub cell_tower: Option<Variate>,
+
+    /// Vehicle velocity distribution in km/h
+    #[clap(long)]
+    pub vehicle_velocity: Option<Variate>,
+
+    /// Vehicle position distribution inside a station's coverage, in meters from west to east
+    #[clap(long)]
+    pub vehicle_position: Option<Variate>,
+
+    /// Vehicle direction distribution, west to east if above 0.5
+    #[clap(long)]
+    pub vehicle_direction: Option<Variate>,
+
+    /// Ramp between two stations, as `<after>:<exit_prob>[:<entry_rate>[:<release|sink>]]`.
+    ///
+    /// `after` is the station on the western side of the ramp (1-19).
+    /// Vehicles passing the ramp leave the highway with `exit_prob`, and new vehicles join it
+    /// at `entry_rate` calls per second, their calls handed over from off the highway.
+    /// Calls of leaving vehicles are either released or handed over to an off-highway sink.
+    /// May be given multiple times, once per station.
+    #[clap(long = "ramp")]
+    pub ramps: Vec<Ramp>,
+}
+
+/// Channels of the stations
+#[derive(Debug, clap::Args)]
+pub struct StationArgs {
+    /// Number of channels of every base station [default: 10]
+    #[clap(long)]
+    pub channels: Option<usize>,
+
+    /// The number of channels reserved for handover requests [default: 0]
     #[clap(short, long)]
-    #[clap(default_value_t = 0)]
-    pub reserved_handover_channels: u8,
+    pub reserved_handover_channels: Option<u8>,
+}
 
-    /// Run the simulation in antithetic pairs
+/// Number of runs
+#[derive(Debug, clap::Args)]
+pub struct RunsArgs {
+    /// Number of simulation runs to perform [default: 100]
     #[clap(long)]
-    pub antithetic: bool,
+    pub num_runs: Option<u32>,
+}
+
+/// Length of every run
+#[derive(Debug, clap::Args)]
+pub struct LengthArgs {
+    /// Number of call inititation events to generate per simulation run [default: 10000]
+    #[clap(long)]
+    pub num_events: Option<u32>,
+
+    /// Simulated time in seconds after which no more calls are initiated in a run
+    #[clap(long)]
+    pub horizon: Option<f64>,
 
     /// Skip the first N events in the simulation when calculating performance measures
+    /// [default: 0]
+    #[clap(long)]
+    pub warmup: Option<usize>,
+}
+
+/// Seeding of the random number streams
+#[derive(Debug, clap::Args)]
+pub struct SeedArgs {
+    /// Base seed of the simulation runs. Run `r` draws from a stream derived from the seed and `r`,
+    /// so experiments with the same seed share random numbers. Random if not set.
     #[clap(long)]
-    #[clap(default_value_t = 0)]
-    pub warmup: usize,
+    pub seed: Option<u64>,
+}
 
-    /// Output file for completed events in the simulation
+/// Event log and performance measure files
+#[derive(Debug, clap::Args)]
+pub struct OutputArgs {
+    /// Output file for completed events in the simulation [default: simulator_events.csv]
     #[clap(long)]
-    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_events", ".csv"))]
-    pub event_log_output: String,
+    pub event_log_output: Option<String>,
 
-    /// Output file for performance measures in the simulation
+    /// Output file for performance measures in the simulation [default: simulator_perf.csv]
     #[clap(long)]
-    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_perf", ".csv"))]
-    pub perf_measure_output: String,
+    pub perf_measure_output: Option<String>,
 
     /// Common postfix for event log and performance measure output files.
     ///
@@ -41,18 +228,681 @@ pub struct CliArgs {
     #[clap(long)]
     pub common_postfix: Option<String>,
 
+    /// Format of the event log and performance measure files: `csv`, `jsonl`, `parquet` or
+    /// `arrow`, replacing their extensions. Detected from the extension of each file if not set.
+    #[clap(long)]
+    pub format: Option<Format>,
+
+    /// Compression of csv and jsonl files: `gzip`, `zstd` or `none`, appending `.gz` or `.zst`
+    /// to their names. Detected from the extension of each file if not set.
+    #[clap(long)]
+    pub compression: Option<Compression>,
+
     /// Skip writing the event log to file. For large simulations, this can save a lot of time and data.
     #[clap(long)]
     pub skip_event_log: bool,
 
-    /// Generate a given number of call initiation events, without running the simulation.
-    ///
-    /// Used for verifying input modelling correctness.
+    /// SQLite database to also write the settings, seed and results of every run to. Invocations
+    /// are appended, so many experiments can be queried together. Only `run` and `replay` write
+    /// to the database.
+    #[clap(long)]
+    pub database: Option<String>,
+
+    /// Also write every event to the database
+    #[clap(long, requires = "database")]
+    pub database_events: bool,
+}
+
+/// Variance reduction of the simulation runs
+#[derive(Debug, clap::Args)]
+pub struct VarianceArgs {
+    /// Run the simulation in antithetic pairs
+    #[clap(long)]
+    pub antithetic: bool,
+
+    /// Output file for both halves of every antithetic pair, and the time spent on them
+    /// [default: simulator_antithetic.csv]
     #[clap(long)]
-    pub generate: Option<u32>,
+    pub antithetic_output: Option<String>,
+
+    /// Also estimate blocked and dropped calls with control variates: the sample means of
+    /// inter-arrival times, call durations and velocities of every run, whose true means are
+    /// known from the input model. Not available with antithetic runs.
+    #[clap(long)]
+    pub control_variates: bool,
+}
+
+/// Simulation arguments
+#[derive(Debug, clap::Args)]
+pub struct RunArgs {
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub stations: StationArgs,
+
+    #[clap(flatten)]
+    pub runs: RunsArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+
+    #[clap(flatten)]
+    pub variance: VarianceArgs,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+/// Call initiation generation arguments
+#[derive(Debug, clap::Args)]
+pub struct GenerateArgs {
+    /// Number of call initiation events to generate
+    #[clap(value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
 
     /// Generated call initiation events will be written to this path as csv.
     #[clap(long)]
     #[clap(default_value = "call_init.csv")]
-    pub generate_to: String,
+    pub output: String,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Replay arguments
+#[derive(Debug, clap::Args)]
+pub struct ReplayArgs {
+    /// Csv file of call initiations
+    pub trace: String,
+
+    /// Vehicle position for replayed calls without a position column:
+    /// meters from the western end of the station, or `random`.
+    #[clap(long)]
+    #[clap(default_value = "random")]
+    pub position: PositionDefault,
+
+    /// Vehicle direction for replayed calls without a direction column:
+    /// `west-to-east`, `east-to-west`, `alternate` or `random`.
+    #[clap(long)]
+    #[clap(default_value = "random")]
+    pub direction: DirectionDefault,
+
+    /// Skip the first N events of every run when calculating performance measures
+    /// [default: 0]
+    #[clap(long)]
+    pub warmup: Option<usize>,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub stations: StationArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+}
+
+/// Input analysis arguments
+#[derive(Debug, clap::Args)]
+pub struct AnalyzeInputArgs {
+    /// Csv file with the course's test data
+    #[clap(long)]
+    #[clap(default_value = "analysis/PCS_TEST_DETERMINSTIC.csv")]
+    pub data: String,
+
+    /// Output file for test statistics and p-values
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_input_analysis", ".csv"))]
+    pub report: String,
+
+    /// Number of equiprobable bins for chi-square tests of continuous variables
+    #[clap(long)]
+    #[clap(default_value_t = 20)]
+    pub bins: usize,
+
+    /// Number of call initiations to generate and test against the input model, 0 to skip
+    #[clap(long)]
+    #[clap(default_value_t = 10_000)]
+    pub num_generated: u32,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Analytical model arguments
+#[derive(Debug, clap::Args)]
+pub struct AnalyticArgs {
+    /// Performance measure csv of a simulation to compare against
+    #[clap(long)]
+    pub compare: Option<String>,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub stations: StationArgs,
+}
+
+/// Levels of the factors of a design, as comma separated values and inclusive ranges
+/// `start:end[:step]`
+#[derive(Debug, clap::Args)]
+pub struct FactorArgs {
+    /// Channels reserved for handover requests, e.g. `0:3`
+    #[clap(long)]
+    pub reserved: Option<Levels>,
+
+    /// Factor applied to the call arrival rate, e.g. `0.8,1,1.2`
+    #[clap(long)]
+    pub arrival_scale: Option<Levels>,
+
+    /// Channels of every station, e.g. `8:12:2`
+    #[clap(long)]
+    pub channels: Option<Levels>,
+
+    /// Mean vehicle velocity in km/h, scaling the velocity distribution
+    #[clap(long)]
+    pub velocity_mean: Option<Levels>,
+}
+
+/// Parameter sweep arguments
+#[derive(Debug, clap::Args)]
+pub struct SweepArgs {
+    #[clap(flatten)]
+    pub factors: FactorArgs,
+
+    /// Confidence level of the intervals
+    #[clap(long)]
+    #[clap(default_value_t = DEFAULT_CONFIDENCE)]
+    pub confidence: f64,
+
+    /// Output file for the summary of every combination
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_sweep", ".csv"))]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub runs: RunsArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Configuration comparison arguments
+#[derive(Debug, clap::Args)]
+pub struct CompareArgs {
+    /// Configurations to compare, in the order of the full factorial design.
+    /// The first configuration is the baseline.
+    #[clap(flatten)]
+    pub factors: FactorArgs,
+
+    /// Draw independent random numbers for every configuration instead of common ones
+    #[clap(long)]
+    pub independent: bool,
+
+    /// Confidence level of the intervals
+    #[clap(long)]
+    #[clap(default_value_t = DEFAULT_CONFIDENCE)]
+    pub confidence: f64,
+
+    /// Output file for the differences to the baseline
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_compare", ".csv"))]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub runs: RunsArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Reservation optimisation arguments
+#[derive(Debug, clap::Args)]
+pub struct OptimizeArgs {
+    /// Largest number of channels reserved in every station
+    #[clap(long)]
+    #[clap(default_value_t = 3)]
+    pub max_reserved: usize,
+
+    /// Per-station reserved channels, west to east, as 20 comma separated counts.
+    /// May be given multiple times.
+    #[clap(long = "profile")]
+    pub profiles: Vec<StationReserves>,
+
+    /// Limit on the fraction of blocked calls
+    #[clap(long)]
+    #[clap(default_value_t = 0.02)]
+    pub blocked_limit: f64,
+
+    /// Limit on the fraction of dropped calls
+    #[clap(long)]
+    #[clap(default_value_t = 0.01)]
+    pub dropped_limit: f64,
+
+    /// Candidates within this of a limit may be declared either feasible or infeasible
+    #[clap(long)]
+    #[clap(default_value_t = 0.001)]
+    pub tolerance: f64,
+
+    /// Differences in dropped calls between equally costly candidates that do not matter
+    #[clap(long)]
+    #[clap(default_value_t = 0.0005)]
+    pub indifference: f64,
+
+    /// Probability of correct selection
+    #[clap(long)]
+    #[clap(default_value_t = DEFAULT_CONFIDENCE)]
+    pub pcs: f64,
+
+    /// Runs of every candidate before any decision
+    #[clap(long)]
+    #[clap(default_value_t = 10)]
+    pub initial_runs: usize,
+
+    /// Runs after which undecided candidates are given up on
+    #[clap(long)]
+    #[clap(default_value_t = 1_000)]
+    pub max_runs: usize,
+
+    /// Output file for the outcome of every candidate
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_optimize", ".csv"))]
+    pub output: String,
+
+    /// Number of channels of every base station [default: 10]
+    #[clap(long)]
+    pub channels: Option<usize>,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Sampling report arguments
+#[derive(Debug, clap::Args)]
+pub struct SamplingReportArgs {
+    /// Independent designs per sampling scheme, over which variances are taken
+    #[clap(long)]
+    #[clap(default_value_t = 20)]
+    pub designs: usize,
+
+    /// Runs per design, which are the strata of stratified sampling. Must be even.
+    #[clap(long)]
+    #[clap(default_value_t = 10)]
+    pub runs_per_design: usize,
+
+    /// Inputs stratified by the stratified scheme, comma separated: call_duration,
+    /// inter_arrival, cell_tower, vehicle_velocity, vehicle_position or vehicle_direction
+    #[clap(long)]
+    #[clap(value_delimiter = ',')]
+    #[clap(default_value = "inter_arrival,call_duration")]
+    pub stratify: Vec<Input>,
+
+    /// Output file for the variance of every scheme
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_sampling", ".csv"))]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub stations: StationArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Importance sampling arguments
+#[derive(Debug, clap::Args)]
+pub struct ImportanceArgs {
+    /// Factor of the call arrival rate in tilted runs. Defaults to 1 + 1/sqrt(num-events),
+    /// which keeps the variance of the log likelihood ratio of a run around two.
+    #[clap(long)]
+    pub arrival_tilt: Option<f64>,
+
+    /// Factor of the mean call duration, above its location, in tilted runs. Defaults as
+    /// `--arrival-tilt`.
+    #[clap(long)]
+    pub duration_tilt: Option<f64>,
+
+    /// Also simulate untilted runs on the same streams, to compare variances
+    #[clap(long)]
+    pub naive: bool,
+
+    /// Confidence level of the intervals
+    #[clap(long)]
+    #[clap(default_value_t = DEFAULT_CONFIDENCE)]
+    pub confidence: f64,
+
+    /// Output file for the performance measure and likelihood ratio of every run
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_importance", ".csv"))]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub stations: StationArgs,
+
+    #[clap(flatten)]
+    pub runs: RunsArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Performance measure statistics arguments
+#[derive(Debug, clap::Args)]
+pub struct PerfStatsArgs {
+    /// One performance measure csv to summarise, or two to compare
+    #[clap(required = true, num_args = 1..=2)]
+    pub files: Vec<String>,
+
+    /// Bootstrap resamples of every measure
+    #[clap(long)]
+    #[clap(default_value_t = 10_000)]
+    pub resamples: usize,
+
+    /// Confidence level of the intervals
+    #[clap(long)]
+    #[clap(default_value_t = DEFAULT_CONFIDENCE)]
+    pub confidence: f64,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+/// Regenerative estimate arguments
+#[derive(Debug, clap::Args)]
+pub struct RegenerativeArgs {
+    /// Confidence level of the intervals
+    #[clap(long)]
+    #[clap(default_value_t = DEFAULT_CONFIDENCE)]
+    pub confidence: f64,
+
+    /// Output file for the calls of every cycle
+    #[clap(long)]
+    #[clap(default_value = concat!(env!("CARGO_BIN_NAME"), "_regenerative", ".csv"))]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub model: ModelArgs,
+
+    #[clap(flatten)]
+    pub stations: StationArgs,
+
+    #[clap(flatten)]
+    pub length: LengthArgs,
+
+    #[clap(flatten)]
+    pub seed: SeedArgs,
+}
+
+impl FactorArgs {
+    /// Full factorial design over the given levels, with factors not given kept at the base
+    /// scenario.
+    pub fn design(&self, base: &Scenario) -> Result<Vec<DesignPoint>, String> {
+        let counts = |levels: &Option<Levels>, factor: &str, default: usize| match levels {
+            Some(levels) => levels.counts(factor),
+            None => Ok(vec![default]),
+        };
+        let velocity = base
+            .input_model
+            .clone()
+            .or_default()
+            .vehicle_velocity
+            .unwrap();
+
+        sweep::design(
+            &counts(&self.reserved, "reserved channels", base.reserved)?,
+            &self
+                .arrival_scale
+                .clone()
+                .map_or(vec![1.0], |levels| levels.0),
+            &counts(&self.channels, "channels", base.channels)?,
+            &self
+                .velocity_mean
+                .clone()
+                .map_or(vec![velocity.mean()], |levels| levels.0),
+        )
+    }
+}
+
+impl ModelArgs {
+    /// Resolve the settings of a mode from the scenario file, if any, the input model file and
+    /// flags, in increasing precedence, and check them.
+    pub fn resolve(&self, overrides: &[&dyn Overrides]) -> io::Result<ScenarioConfig> {
+        let mut config = match &self.scenario {
+            Some(path) => ScenarioConfig::from_file(path)?,
+            None => ScenarioConfig::default(),
+        };
+
+        if let Some(path) = &self.input_model {
+            config.inputs = config.inputs.overridden_by(InputModel::from_file(path)?);
+        }
+        config.inputs = config.inputs.overridden_by(InputModel {
+            call_duration: self.call_duration.clone(),
+            inter_arrival: self.inter_arrival.clone(),
+            cell_tower: self.cell_tower.clone(),
+            vehicle_velocity: self.vehicle_velocity.clone(),
+            vehicle_position: self.vehicle_position.clone(),
+            vehicle_direction: self.vehicle_direction.clone(),
+        });
+
+        if !self.ramps.is_empty() {
+            config.topology.ramps = self.ramps.clone();
+        }
+
+        for overrides in overrides {
+            overrides.apply(&mut config);
+        }
+
+        config
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(config)
+    }
+}
+
+impl Overrides for StationArgs {
+    fn apply(&self, config: &mut ScenarioConfig) {
+        if let Some(channels) = self.channels {
+            config.topology.channels = channels;
+        }
+        if let Some(reserved) = self.reserved_handover_channels {
+            config.admission.reserved_handover_channels = reserved as usize;
+            config.admission.station_reserved = None;
+        }
+    }
+}
+
+impl Overrides for RunsArgs {
+    fn apply(&self, config: &mut ScenarioConfig) {
+        config.run.runs = self.num_runs.map_or(config.run.runs, |n| n as usize);
+    }
+}
+
+impl Overrides for LengthArgs {
+    fn apply(&self, config: &mut ScenarioConfig) {
+        let run = &mut config.run;
+        run.events = self.num_events.map_or(run.events, |n| n as usize);
+        run.horizon = self.horizon.or(run.horizon);
+        run.warmup = self.warmup.unwrap_or(run.warmup);
+    }
+}
+
+impl Overrides for SeedArgs {
+    fn apply(&self, config: &mut ScenarioConfig) {
+        config.run.seed = self.seed.or(config.run.seed);
+    }
+}
+
+impl Overrides for OutputArgs {
+    fn apply(&self, config: &mut ScenarioConfig) {
+        let output = &mut config.output;
+        if let Some(path) = &self.event_log_output {
+            output.event_log = path.clone();
+        }
+        if let Some(path) = &self.perf_measure_output {
+            output.perf_measure = path.clone();
+        }
+        output.common_postfix = self.common_postfix.clone().or(output.common_postfix.take());
+        output.format = self.format.or(output.format);
+        output.compression = self.compression.or(output.compression);
+        output.skip_event_log |= self.skip_event_log;
+        output.database = self.database.clone().or(output.database.take());
+        output.database_events |= self.database_events;
+    }
+}
+
+impl Overrides for VarianceArgs {
+    fn apply(&self, config: &mut ScenarioConfig) {
+        config.run.antithetic |= self.antithetic;
+        config.run.control_variates |= self.control_variates;
+        if let Some(path) = &self.antithetic_output {
+            config.output.antithetic = path.clone();
+        }
+    }
+}
+
+impl RunArgs {
+    /// Settings of the simulation.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model.resolve(&[
+            &self.stations,
+            &self.runs,
+            &self.length,
+            &self.seed,
+            &self.variance,
+            &self.output,
+        ])
+    }
+}
+
+impl GenerateArgs {
+    /// Settings of the generated call initiations.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model.resolve(&[&self.seed])
+    }
+}
+
+impl ReplayArgs {
+    /// Settings of the replayed runs.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        let mut config = self
+            .model
+            .resolve(&[&self.stations, &self.seed, &self.output])?;
+        config.run.warmup = self.warmup.unwrap_or(config.run.warmup);
+        Ok(config)
+    }
+}
+
+impl AnalyzeInputArgs {
+    /// Settings of the analysed input model.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model.resolve(&[&self.seed])
+    }
+}
+
+impl AnalyticArgs {
+    /// Settings of the analytical model.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model.resolve(&[&self.stations])
+    }
+}
+
+impl PerfStatsArgs {
+    /// Settings of the summary, which only uses the seed of its resamples.
+    pub fn config(&self) -> ScenarioConfig {
+        let mut config = ScenarioConfig::default();
+        self.seed.apply(&mut config);
+        config
+    }
+}
+
+impl SweepArgs {
+    /// Settings of the base scenario of the design.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model.resolve(&[&self.runs, &self.length, &self.seed])
+    }
+}
+
+impl CompareArgs {
+    /// Settings of the base scenario of the design.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model.resolve(&[&self.runs, &self.length, &self.seed])
+    }
+}
+
+impl OptimizeArgs {
+    /// Settings of the scenario the candidates reserve channels in.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        let stations = StationArgs {
+            channels: self.channels,
+            reserved_handover_channels: None,
+        };
+        self.model.resolve(&[&stations, &self.length, &self.seed])
+    }
+}
+
+impl SamplingReportArgs {
+    /// Settings of the runs of every design.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model
+            .resolve(&[&self.stations, &self.length, &self.seed])
+    }
+}
+
+impl ImportanceArgs {
+    /// Settings of the tilted runs.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model
+            .resolve(&[&self.stations, &self.runs, &self.length, &self.seed])
+    }
+}
+
+impl RegenerativeArgs {
+    /// Settings of the regenerative run.
+    pub fn config(&self) -> io::Result<ScenarioConfig> {
+        self.model
+            .resolve(&[&self.stations, &self.length, &self.seed])
+    }
 }
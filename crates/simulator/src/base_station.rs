@@ -5,6 +5,12 @@ use serde::Serialize;
 
 use crate::debug_println;
 
+/// Number of channels of every base station on the highway.
+pub const NUM_CHANNELS: usize = 10;
+
+/// Number of base stations along the highway.
+pub const NUM_STATIONS: usize = 20;
+
 /// The base station that handles calls.
 ///
 /// Each base station has a fixed number of available channels.
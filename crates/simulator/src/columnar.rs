@@ -0,0 +1,401 @@
+//! Columnar output of event logs and performance measures, as Parquet or Arrow IPC files.
+//! Text formats are written by [`simulator_core::output`].
+//!
+//! Event logs of large simulations take tens of gigabytes as csv. Columnar files store every
+//! field once per column with its own type, enums dictionary encoded, so they are a fraction of
+//! the size and load directly into dataframes. Parquet files are additionally compressed with
+//! zstd.
+
+use std::{fmt::Display, fs::File, io, path::Path, str::FromStr, sync::Arc};
+
+use arrow_array::{
+    types::Int8Type, ArrayRef, DictionaryArray, Float64Array, Int8Array, RecordBatch, StringArray,
+    UInt32Array, UInt64Array,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde::{Deserialize, Serialize};
+use simulator_core::output::{Encoding, RecordWriter};
+
+use crate::event::{CellEventResult, PerfRecord};
+
+/// File format of the event log and performance measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+
+    /// One json object per line
+    #[serde(rename = "jsonl")]
+    JsonLines,
+
+    /// Parquet with zstd compressed columns
+    Parquet,
+
+    /// Arrow IPC file, also known as Feather v2
+    Arrow,
+}
+
+/// Records with a fixed columnar schema.
+pub trait Columnar: Sized {
+    fn schema() -> Schema;
+
+    /// Columns of `rows`, in the order of the schema.
+    fn columns(rows: &[Self]) -> Vec<ArrayRef>;
+
+    /// Record batch of `rows`.
+    fn batch(rows: &[Self]) -> io::Result<RecordBatch> {
+        RecordBatch::try_new(Arc::new(Self::schema()), Self::columns(rows))
+            .map_err(io::Error::other)
+    }
+}
+
+/// Writer of record batches to a columnar file.
+pub enum ColumnarWriter {
+    Parquet(ArrowWriter<File>),
+    Arrow(FileWriter<File>),
+}
+
+impl Format {
+    /// Format of a file from its extension: `.parquet`, `.arrow`, `.ipc` or `.feather` for
+    /// columnar files, and otherwise the encoding of a text file, which may be compressed.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("parquet") => Format::Parquet,
+            Some("arrow" | "ipc" | "feather") => Format::Arrow,
+            _ => match Encoding::from_path(path) {
+                Encoding::Csv => Format::Csv,
+                Encoding::JsonLines => Format::JsonLines,
+            },
+        }
+    }
+
+    /// Whether files are written by a [`ColumnarWriter`], rather than as compressible text.
+    pub fn is_columnar(self) -> bool {
+        matches!(self, Format::Parquet | Format::Arrow)
+    }
+
+    /// Extension of files written in the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::JsonLines => "jsonl",
+            Format::Parquet => "parquet",
+            Format::Arrow => "arrow",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "csv" => Ok(Format::Csv),
+            "jsonl" | "ndjson" => Ok(Format::JsonLines),
+            "parquet" => Ok(Format::Parquet),
+            "arrow" | "ipc" | "feather" => Ok(Format::Arrow),
+            other => Err(format!(
+                "expected csv, jsonl, parquet or arrow, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl ColumnarWriter {
+    /// Create a file of records of type `T` in a columnar format.
+    pub fn create<T: Columnar, P: AsRef<Path>>(path: P, format: Format) -> io::Result<Self> {
+        let file = File::create(path)?;
+        let schema = Arc::new(T::schema());
+
+        match format {
+            Format::Csv | Format::JsonLines => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a columnar format", format),
+            )),
+            Format::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .build();
+                ArrowWriter::try_new(file, schema, Some(properties))
+                    .map(ColumnarWriter::Parquet)
+                    .map_err(io::Error::other)
+            }
+            Format::Arrow => FileWriter::try_new(file, &schema)
+                .map(ColumnarWriter::Arrow)
+                .map_err(io::Error::other),
+        }
+    }
+
+    /// Write `rows` as one batch, which is one row group of a parquet file.
+    pub fn write<T: Columnar>(&mut self, rows: &[T]) -> io::Result<()> {
+        let batch = T::batch(rows)?;
+
+        match self {
+            ColumnarWriter::Parquet(writer) => {
+                writer.write(&batch).map_err(io::Error::other)?;
+                // keep every run in its own row group
+                writer.flush().map_err(io::Error::other)
+            }
+            ColumnarWriter::Arrow(writer) => writer.write(&batch).map_err(io::Error::other),
+        }
+    }
+
+    /// Write the footer of the file. The file is unreadable without it.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            ColumnarWriter::Parquet(writer) => writer.close().map(drop).map_err(io::Error::other),
+            ColumnarWriter::Arrow(mut writer) => writer.finish().map_err(io::Error::other),
+        }
+    }
+}
+
+/// Write `rows` to a file in the format of its extension.
+pub fn write_records<T, P>(path: P, rows: &[T]) -> io::Result<()>
+where
+    T: Columnar + Serialize,
+    P: AsRef<Path>,
+{
+    match Format::from_path(path.as_ref()) {
+        Format::Csv | Format::JsonLines => {
+            let mut writer = RecordWriter::create(path, false)?;
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.finish()
+        }
+        format => {
+            let mut writer = ColumnarWriter::create::<T, _>(path, format)?;
+            writer.write(rows)?;
+            writer.finish()
+        }
+    }
+}
+
+/// Dictionary encoded column of enum variants, given as indices into `names`.
+///
+/// Every batch has the dictionary of all variants, since Arrow IPC files cannot replace it.
+fn variants<T>(rows: &[T], names: &[&str], variant: impl Fn(&T) -> i8) -> ArrayRef {
+    let keys = Int8Array::from_iter_values(rows.iter().map(variant));
+    let values = Arc::new(StringArray::from(names.to_vec()));
+    Arc::new(DictionaryArray::<Int8Type>::try_new(keys, values).expect("every variant is named"))
+}
+
+fn dictionary(name: &str) -> Field {
+    Field::new_dictionary(name, DataType::Int8, DataType::Utf8, false)
+}
+
+impl Columnar for CellEventResult {
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("idx", DataType::UInt64, false),
+            Field::new("run", DataType::UInt32, false),
+            Field::new("time", DataType::Float64, false),
+            dictionary("ty"),
+            dictionary("outcome"),
+            dictionary("direction"),
+            Field::new("speed", DataType::Float64, false),
+            Field::new("station", DataType::UInt32, false),
+            Field::new("station_free_channels", DataType::UInt32, false),
+        ])
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.idx as u64),
+            )),
+            Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.run))),
+            Arc::new(Float64Array::from_iter_values(
+                rows.iter().map(|r| r.time as f64),
+            )),
+            // variants are named in declaration order
+            variants(
+                rows,
+                &["Initiate", "Terminate", "Handover", "Exit", "Enter"],
+                |r| r.ty as i8,
+            ),
+            variants(rows, &["Success", "Blocked", "Terminated"], |r| {
+                r.outcome as i8
+            }),
+            variants(rows, &["WestToEast", "EastToWest"], |r| r.direction as i8),
+            Arc::new(Float64Array::from_iter_values(
+                rows.iter().map(|r| r.speed as f64),
+            )),
+            // stations are numbered from 1, as in the csv
+            Arc::new(UInt32Array::from_iter_values(
+                rows.iter().map(|r| r.station as u32 + 1),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                rows.iter().map(|r| r.station_free_channels as u32),
+            )),
+        ]
+    }
+}
+
+impl Columnar for PerfRecord {
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("run", DataType::UInt64, false),
+            Field::new("seed", DataType::UInt64, false),
+            Field::new("blocked_calls", DataType::Float64, false),
+            Field::new("dropped_calls", DataType::Float64, false),
+        ])
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.run as u64),
+            )),
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.seed))),
+            Arc::new(Float64Array::from_iter_values(
+                rows.iter().map(|r| r.blocked_calls as f64),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                rows.iter().map(|r| r.dropped_calls as f64),
+            )),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_ipc::reader::FileReader;
+    use parquet::{
+        arrow::arrow_reader::ParquetRecordBatchReaderBuilder, file::reader::FileReader as _,
+    };
+
+    use super::*;
+    use crate::{
+        base_station::StationResponse,
+        event::{CellEventType, PerfMeasure},
+    };
+
+    fn records(run: usize) -> Vec<PerfRecord> {
+        (0..3)
+            .map(|i| {
+                PerfRecord::new(
+                    run,
+                    7,
+                    PerfMeasure {
+                        blocked_calls: i as f64 / 10.0,
+                        dropped_calls: 0.0,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path(Path::new("a.parquet")), Format::Parquet);
+        assert_eq!(Format::from_path(Path::new("a.feather")), Format::Arrow);
+        assert_eq!(Format::from_path(Path::new("a.csv")), Format::Csv);
+        assert_eq!(Format::from_path(Path::new("events")), Format::Csv);
+        assert_eq!(
+            Format::from_path(Path::new("a.ndjson.gz")),
+            Format::JsonLines
+        );
+        assert_eq!("ipc".parse::<Format>(), Ok(Format::Arrow));
+        assert!("xlsx".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_columnar_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "simulator_test_columnar_{}.parquet",
+            std::process::id()
+        ));
+        let mut writer = ColumnarWriter::create::<PerfRecord, _>(&path, Format::Parquet).unwrap();
+        writer.write(&records(1)).unwrap();
+        writer.write(&records(2)).unwrap();
+        writer.finish().unwrap();
+
+        // one row group per batch
+        let file = File::open(&path).unwrap();
+        let reader = parquet::file::reader::SerializedFileReader::new(file).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        let file = File::open(&path).unwrap();
+        let batches = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 6);
+        assert_eq!(batches[0].schema().field(2).name(), "blocked_calls");
+        std::fs::remove_file(&path).unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "simulator_test_columnar_{}.arrow",
+            std::process::id()
+        ));
+        write_records(&path, &records(1)).unwrap();
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        let blocked = batches[0]
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(blocked.value(2), 0.2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_event_batches() {
+        // batches with different variants share one dictionary
+        let path = std::env::temp_dir().join(format!(
+            "simulator_test_event_batches_{}.arrow",
+            std::process::id()
+        ));
+        let mut writer =
+            ColumnarWriter::create::<CellEventResult, _>(&path, Format::Arrow).unwrap();
+        writer
+            .write(&[CellEventResult::fixture(
+                0,
+                CellEventType::Initiate,
+                StationResponse::Blocked,
+            )])
+            .unwrap();
+        writer
+            .write(&[CellEventResult::fixture(
+                1,
+                CellEventType::Handover,
+                StationResponse::Terminated,
+            )])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        let ty = batches[1]
+            .column(3)
+            .as_any()
+            .downcast_ref::<DictionaryArray<Int8Type>>()
+            .unwrap();
+        let names = ty.values().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(names.value(ty.keys().value(0) as usize), "Handover");
+        let station = batches[0]
+            .column(7)
+            .as_any()
+            .downcast_ref::<UInt32Array>()
+            .unwrap();
+        assert_eq!(station.value(0), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+//! Paired comparisons of configurations against a baseline.
+//!
+//! Under common random numbers, run `r` of every configuration sees the same call initiations
+//! up to the configuration's own parameters, so the per-run differences are positively
+//! correlated and their mean is estimated with less variance than from independent runs.
+
+use serde::Serialize;
+use simulator_core::stats::{welch_half_width, Summary};
+
+use crate::{event::PerfMeasure, experiment::Scenario, sweep::DesignPoint};
+
+/// Difference of a performance measure between a configuration and the baseline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difference {
+    /// Summary of the per-run differences
+    pub paired: Summary,
+
+    /// Half-width of a Welch interval treating the runs as independent
+    pub unpaired_half_width: f64,
+}
+
+/// Difference of a configuration to the baseline.
+#[derive(Clone, Debug, Serialize)]
+pub struct ComparisonRow {
+    pub reserved_channels: usize,
+    pub arrival_scale: f64,
+    pub channels: usize,
+    pub velocity_mean: f64,
+
+    /// Base seed the configuration was simulated under
+    pub seed: u64,
+
+    pub runs: usize,
+    pub common_random_numbers: bool,
+    pub blocked_diff_mean: f64,
+    pub blocked_diff_ci_low: f64,
+    pub blocked_diff_ci_high: f64,
+    pub blocked_unpaired_half_width: f64,
+    pub dropped_diff_mean: f64,
+    pub dropped_diff_ci_low: f64,
+    pub dropped_diff_ci_high: f64,
+    pub dropped_unpaired_half_width: f64,
+}
+
+impl Difference {
+    /// Difference of paired samples, `config[r] - baseline[r]`.
+    pub fn new(config: &[f64], baseline: &[f64], confidence: f64) -> Self {
+        let diff = config
+            .iter()
+            .zip(baseline)
+            .map(|(c, b)| c - b)
+            .collect::<Vec<_>>();
+
+        Self {
+            paired: Summary::new(&diff, confidence),
+            unpaired_half_width: welch_half_width(
+                &Summary::new(config, confidence),
+                &Summary::new(baseline, confidence),
+                confidence,
+            ),
+        }
+    }
+}
+
+/// Simulate every configuration and compare the rest to the first.
+///
+/// Configurations share the base seed under common random numbers, otherwise each is offset
+/// to independent streams.
+pub fn run_compare(
+    points: &[DesignPoint],
+    base: &Scenario,
+    num_runs: usize,
+    seed: u64,
+    common_random_numbers: bool,
+    confidence: f64,
+) -> Result<Vec<ComparisonRow>, String> {
+    if points.len() < 2 {
+        return Err("comparisons need at least two configurations".to_string());
+    }
+    if num_runs < 2 {
+        return Err("comparisons need at least two runs".to_string());
+    }
+
+    let seeds = (0..points.len())
+        .map(|i| match common_random_numbers {
+            true => seed,
+            false => seed.wrapping_add(i as u64),
+        })
+        .collect::<Vec<_>>();
+    let perf = points
+        .iter()
+        .zip(&seeds)
+        .map(|(point, seed)| point.scenario(base).simulate_runs(num_runs, *seed))
+        .collect::<Vec<_>>();
+
+    let blocked = |perf: &[PerfMeasure]| {
+        perf.iter()
+            .map(|p| p.blocked_calls as f64)
+            .collect::<Vec<_>>()
+    };
+    let dropped = |perf: &[PerfMeasure]| {
+        perf.iter()
+            .map(|p| p.dropped_calls as f64)
+            .collect::<Vec<_>>()
+    };
+
+    let rows = points
+        .iter()
+        .zip(&perf)
+        .zip(&seeds)
+        .skip(1)
+        .map(|((point, config), seed)| {
+            let blocked = Difference::new(&blocked(config), &blocked(&perf[0]), confidence);
+            let dropped = Difference::new(&dropped(config), &dropped(&perf[0]), confidence);
+
+            ComparisonRow {
+                reserved_channels: point.reserved,
+                arrival_scale: point.arrival_scale,
+                channels: point.channels,
+                velocity_mean: point.velocity_mean,
+                seed: *seed,
+                runs: num_runs,
+                common_random_numbers,
+                blocked_diff_mean: blocked.paired.mean,
+                blocked_diff_ci_low: blocked.paired.ci().0,
+                blocked_diff_ci_high: blocked.paired.ci().1,
+                blocked_unpaired_half_width: blocked.unpaired_half_width,
+                dropped_diff_mean: dropped.paired.mean,
+                dropped_diff_ci_low: dropped.paired.ci().0,
+                dropped_diff_ci_high: dropped.paired.ci().1,
+                dropped_unpaired_half_width: dropped.unpaired_half_width,
+            }
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+/// Print differences to the baseline, in percentage points.
+pub fn print_comparison(baseline: &DesignPoint, rows: &[ComparisonRow]) {
+    println!(
+        "baseline: reserved {} scale {} channels {} velocity {:.3}",
+        baseline.reserved, baseline.arrival_scale, baseline.channels, baseline.velocity_mean
+    );
+
+    for row in rows {
+        println!(
+            "reserved {} scale {} channels {} velocity {:.3}: \
+             blocked {:+.4} ± {:.4} (unpaired ± {:.4}), dropped {:+.4} ± {:.4} (unpaired ± {:.4})",
+            row.reserved_channels,
+            row.arrival_scale,
+            row.channels,
+            row.velocity_mean,
+            row.blocked_diff_mean * 100.0,
+            (row.blocked_diff_ci_high - row.blocked_diff_mean) * 100.0,
+            row.blocked_unpaired_half_width * 100.0,
+            row.dropped_diff_mean * 100.0,
+            (row.dropped_diff_ci_high - row.dropped_diff_mean) * 100.0,
+            row.dropped_unpaired_half_width * 100.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::InputModel;
+
+    #[test]
+    fn test_difference() {
+        let baseline = [1.0, 2.0, 3.0, 4.0];
+        let shifted = [1.5, 2.5, 3.5, 4.5];
+
+        let diff = Difference::new(&shifted, &baseline, 0.95);
+        assert!((diff.paired.mean - 0.5).abs() < 1e-12);
+        assert!(diff.paired.half_width.abs() < 1e-12);
+        assert!(diff.unpaired_half_width > 1.0);
+    }
+
+    #[test]
+    fn test_common_random_numbers_reduce_variance() {
+        let base = Scenario::new(InputModel::default(), 0, 2_000, 0);
+        let points = [0, 1].map(|reserved| DesignPoint {
+            reserved,
+            arrival_scale: 2.0,
+            channels: 10,
+            velocity_mean: 120.0,
+        });
+
+        let crn = run_compare(&points, &base, 8, 11, true, 0.95).unwrap();
+        let independent = run_compare(&points, &base, 8, 11, false, 0.95).unwrap();
+        let (crn, independent) = (&crn[0], &independent[0]);
+        assert_eq!(crn.seed, 11);
+        assert_eq!(independent.seed, 12);
+
+        // reserving a channel trades blocked calls for dropped ones
+        assert!(crn.blocked_diff_ci_low > 0.0);
+        assert!(crn.dropped_diff_ci_high < 0.0);
+
+        let half_width = |row: &ComparisonRow| row.blocked_diff_ci_high - row.blocked_diff_mean;
+        assert!(half_width(crn) < crn.blocked_unpaired_half_width);
+        assert!(half_width(crn) < half_width(independent));
+
+        assert!(run_compare(&points[..1], &base, 8, 11, true, 0.95).is_err());
+    }
+}
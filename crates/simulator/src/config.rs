@@ -0,0 +1,371 @@
+//! Scenario files, which hold every model parameter, run setting and output of a simulation in
+//! one toml file, so experiments can be versioned with their results:
+//!
+//! ```toml
+//! [topology]
+//! channels = 10
+//! ramps = ["5:0.1:0.2:sink"]
+//!
+//! [inputs]
+//! call_duration = "lognormal(4.2, 0.8)"
+//!
+//! [admission]
+//! reserved_handover_channels = 1
+//!
+//! [run]
+//! runs = 100
+//! events = 10000
+//! seed = 42
+//!
+//! [output]
+//! perf_measure = "reserve_1_perf.csv"
+//! ```
+//!
+//! Every key is optional and defaults to the simulator's usual value. Flags given on the
+//! command line take precedence over the file.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use simulator_core::output::Compression;
+
+use crate::{
+    base_station::{NUM_CHANNELS, NUM_STATIONS},
+    columnar::Format,
+    experiment::Scenario,
+    generator::InputModel,
+    ramp::{self, Ramp},
+    FloatingPoint,
+};
+
+/// Every setting of a simulation.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioConfig {
+    pub topology: Topology,
+
+    /// Input distributions, with the keys of an input model file
+    pub inputs: InputModel,
+
+    pub admission: Admission,
+    pub run: RunControl,
+    pub output: Outputs,
+}
+
+/// Stations and ramps along the highway.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Topology {
+    /// Number of base stations, which is fixed
+    pub stations: usize,
+
+    /// Channels of every station
+    pub channels: usize,
+
+    /// Ramps as `<after>:<exit_prob>[:<entry_rate>[:<release|sink>]]`
+    pub ramps: Vec<Ramp>,
+}
+
+/// Admission policy of the stations.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Admission {
+    /// Channels reserved for handovers in every station
+    pub reserved_handover_channels: usize,
+
+    /// Channels reserved for handovers per station, west to east, instead of
+    /// `reserved_handover_channels`
+    pub station_reserved: Option<Vec<usize>>,
+}
+
+/// Number and length of the simulation runs.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunControl {
+    pub runs: usize,
+
+    /// Call initiations generated per run
+    pub events: usize,
+
+    /// Simulated time in seconds after which no more calls are initiated
+    pub horizon: Option<FloatingPoint>,
+
+    /// Events skipped when calculating performance measures
+    pub warmup: usize,
+
+    /// Base seed of the runs, random if not set
+    pub seed: Option<u64>,
+
+    /// Run the simulation in antithetic pairs
+    pub antithetic: bool,
+
+    /// Also estimate blocked and dropped calls with control variates
+    pub control_variates: bool,
+}
+
+/// Output files of the simulation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Outputs {
+    pub event_log: String,
+    pub perf_measure: String,
+
+    /// Both halves of every antithetic pair
+    pub antithetic: String,
+
+    /// Postfix of the event log and performance measure files, before the extension
+    pub common_postfix: Option<String>,
+
+    /// Format of the event log and performance measure files, replacing their extensions.
+    /// Detected from the extension of each file if not set.
+    pub format: Option<Format>,
+
+    /// Compression of csv and JSON Lines files, appending `.gz` or `.zst` to their names.
+    /// Detected from the extension of each file if not set.
+    pub compression: Option<Compression>,
+
+    pub skip_event_log: bool,
+
+    /// SQLite database the runs of `run` and `replay` invocations are also written to
+    pub database: Option<String>,
+
+    /// Also write every event to the database
+    pub database_events: bool,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Self {
+            stations: NUM_STATIONS,
+            channels: NUM_CHANNELS,
+            ramps: Vec::new(),
+        }
+    }
+}
+
+impl Default for RunControl {
+    fn default() -> Self {
+        Self {
+            runs: 100,
+            events: 10_000,
+            horizon: None,
+            warmup: 0,
+            seed: None,
+            antithetic: false,
+            control_variates: false,
+        }
+    }
+}
+
+impl Default for Outputs {
+    fn default() -> Self {
+        Self {
+            event_log: concat!(env!("CARGO_BIN_NAME"), "_events", ".csv").to_string(),
+            perf_measure: concat!(env!("CARGO_BIN_NAME"), "_perf", ".csv").to_string(),
+            antithetic: concat!(env!("CARGO_BIN_NAME"), "_antithetic", ".csv").to_string(),
+            common_postfix: None,
+            format: None,
+            compression: None,
+            skip_event_log: false,
+            database: None,
+            database_events: false,
+        }
+    }
+}
+
+impl ScenarioConfig {
+    /// Read and validate a scenario file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(&path)?;
+
+        let config = toml::from_str::<Self>(&contents).map_err(|e| e.to_string());
+        config
+            .and_then(|config| config.validate().map(|_| config))
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.as_ref().display(), e),
+                )
+            })
+    }
+
+    /// Check that the settings describe a simulation that can run.
+    pub fn validate(&self) -> Result<(), String> {
+        let channels = self.topology.channels;
+
+        if self.topology.stations != NUM_STATIONS {
+            return Err(format!(
+                "topology.stations must be {}, the highway has a fixed number of stations",
+                NUM_STATIONS
+            ));
+        }
+        if channels == 0 {
+            return Err("topology.channels must be at least 1".to_string());
+        }
+        if self.admission.reserved_handover_channels >= channels {
+            return Err(format!(
+                "admission.reserved_handover_channels must be less than the {} channels of a station",
+                channels
+            ));
+        }
+        if let Some(reserved) = &self.admission.station_reserved {
+            if reserved.len() != NUM_STATIONS {
+                return Err(format!(
+                    "admission.station_reserved must have one count per station, {} instead of {}",
+                    reserved.len(),
+                    NUM_STATIONS
+                ));
+            }
+            if let Some(station) = reserved.iter().position(|r| *r >= channels) {
+                return Err(format!(
+                    "admission.station_reserved of station {} must be less than the {} channels of a station",
+                    station + 1,
+                    channels
+                ));
+            }
+        }
+        ramp::check_unique(&self.topology.ramps).map_err(|e| format!("topology.ramps: {}", e))?;
+        self.inputs
+            .check_support()
+            .map_err(|e| format!("inputs.{}", e))?;
+        if self.run.runs == 0 {
+            return Err("run.runs must be at least 1".to_string());
+        }
+        if self.run.events == 0 {
+            return Err("run.events must be at least 1".to_string());
+        }
+        if let Some(horizon) = self.run.horizon {
+            if horizon.is_nan() || horizon <= 0.0 {
+                return Err(format!("run.horizon must be positive, got {}", horizon));
+            }
+        }
+        if let (Some(format), Some(Compression::Gzip | Compression::Zstd)) =
+            (self.output.format, self.output.compression)
+        {
+            if format.is_columnar() {
+                return Err(format!(
+                    "output.compression applies to csv and jsonl files, {} files are compressed internally",
+                    format
+                ));
+            }
+        }
+        if self.output.format.is_none() {
+            for path in [&self.output.event_log, &self.output.perf_measure] {
+                let path = Path::new(path);
+                let compressed = Compression::from_path(path) != Compression::None;
+                if compressed && Format::from_path(&Compression::strip(path)).is_columnar() {
+                    return Err(format!(
+                        "{} is compressed, but parquet and arrow files are compressed internally",
+                        path.display()
+                    ));
+                }
+            }
+        }
+        if self.output.database_events && self.output.database.is_none() {
+            return Err("output.database_events needs output.database to be set".to_string());
+        }
+        if self.run.antithetic && self.run.control_variates {
+            return Err(
+                "run.control_variates are not available with run.antithetic runs".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Highway configuration of the settings.
+    pub fn scenario(&self) -> Scenario {
+        let mut scenario = Scenario::new(
+            self.inputs.clone(),
+            self.admission.reserved_handover_channels,
+            self.run.events,
+            self.run.warmup,
+        );
+        scenario.channels = self.topology.channels;
+        scenario.station_reserved = self.admission.station_reserved.as_ref().map(|reserved| {
+            let mut stations = [0; NUM_STATIONS];
+            stations.copy_from_slice(reserved);
+            stations
+        });
+        scenario.ramps = self.topology.ramps.clone();
+        scenario.horizon = self.run.horizon;
+        scenario
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scenario() {
+        let config = toml::from_str::<ScenarioConfig>(
+            r#"
+            [topology]
+            channels = 12
+            ramps = ["5:0.1:0.2:sink"]
+
+            [inputs]
+            inter_arrival = "exponential(2)"
+
+            [admission]
+            station_reserved = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2]
+
+            [run]
+            runs = 5
+            horizon = 3600.0
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        // keys that are not given keep their defaults
+        assert_eq!(config.run.events, 10_000);
+        assert_eq!(config.output.perf_measure, Outputs::default().perf_measure);
+
+        let scenario = config.scenario();
+        assert_eq!(scenario.channels, 12);
+        assert_eq!(scenario.station_reserved.unwrap()[19], 2);
+        assert_eq!(scenario.ramps.len(), 1);
+        assert_eq!(scenario.horizon, Some(3600.0));
+        assert!(scenario.input_model.inter_arrival.is_some());
+        assert!(scenario.input_model.call_duration.is_none());
+
+        // the resolved settings round trip
+        let written = toml::to_string(&config).unwrap();
+        let read = toml::from_str::<ScenarioConfig>(&written).unwrap();
+        assert_eq!(read.scenario().ramps, scenario.ramps);
+    }
+
+    #[test]
+    fn test_invalid_scenario() {
+        let invalid = |toml: &str| match toml::from_str::<ScenarioConfig>(toml) {
+            Ok(config) => config.validate().unwrap_err(),
+            Err(e) => e.to_string(),
+        };
+
+        assert!(invalid("[topology]\nstations = 10").contains("topology.stations"));
+        assert!(invalid("[topology]\nchanels = 10").contains("chanels"));
+        assert!(invalid("[admission]\nreserved_handover_channels = 10")
+            .contains("reserved_handover_channels"));
+        assert!(invalid("[admission]\nstation_reserved = [1, 2]").contains("one count"));
+        assert!(invalid("[run]\nruns = 0").contains("run.runs"));
+        assert!(invalid("[run]\nhorizon = -1.0").contains("run.horizon"));
+        assert!(invalid("[inputs]\ncall_duration = \"exponential(\"").contains("exponential"));
+        assert!(invalid("[inputs]\ncall_duration = \"normal(50, 100)\"")
+            .contains("inputs.call_duration must be non-negative"));
+        assert!(invalid("[inputs]\nvehicle_position = \"uniform(0, 3000)\"")
+            .contains("inputs.vehicle_position must be within"));
+        assert!(invalid("[topology]\nramps = [\"25:0.1\"]").contains("ramp"));
+        assert!(invalid("[topology]\nramps = [\"5:0.1\", \"5:0.2\"]")
+            .contains("topology.ramps: more than one ramp after station 5"));
+        assert!(
+            invalid("[output]\nformat = \"parquet\"\ncompression = \"gzip\"")
+                .contains("output.compression")
+        );
+        assert!(
+            invalid("[output]\nevent_log = \"events.parquet.gz\"").contains("events.parquet.gz")
+        );
+        assert!(invalid("[output]\ndatabase_events = true").contains("output.database"));
+    }
+}
@@ -0,0 +1,334 @@
+//! SQLite store of simulation results, so many experiments can be queried together.
+//!
+//! Every `run` and `replay` invocation appends a row to `invocations` with its settings and seed,
+//! and its runs to `perf_measures`, `station_summaries` and, if asked for, `events`, keyed by the
+//! invocation. The experiment subcommands (`sweep`, `compare`, `optimize`, `sampling-report`,
+//! `importance` and `regenerative`) write their own csv outputs and do not use the database.
+//!
+//! Station summaries skip the warmup, like the performance measures, while `events` holds every
+//! event of a run:
+//!
+//! ```sql
+//! SELECT i.id, json_extract(i.config, '$.admission.reserved_handover_channels') AS reserved,
+//!        avg(p.blocked_calls), avg(p.dropped_calls)
+//! FROM invocations i JOIN perf_measures p ON p.invocation = i.id
+//! GROUP BY i.id;
+//! ```
+
+use std::{io, path::Path};
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    base_station::{StationResponse, NUM_STATIONS},
+    event::{CellEventResult, CellEventType, PerfMeasure},
+    manifest::Manifest,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS invocations (
+    id INTEGER PRIMARY KEY,
+    started_at INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    version TEXT NOT NULL,
+    git_hash TEXT,
+    seed TEXT NOT NULL,
+    config TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS perf_measures (
+    invocation INTEGER NOT NULL REFERENCES invocations (id),
+    run INTEGER NOT NULL,
+    blocked_calls REAL NOT NULL,
+    dropped_calls REAL NOT NULL,
+    PRIMARY KEY (invocation, run)
+);
+CREATE TABLE IF NOT EXISTS station_summaries (
+    invocation INTEGER NOT NULL REFERENCES invocations (id),
+    run INTEGER NOT NULL,
+    station INTEGER NOT NULL,
+    initiated_calls INTEGER NOT NULL,
+    blocked_calls INTEGER NOT NULL,
+    handovers INTEGER NOT NULL,
+    dropped_calls INTEGER NOT NULL,
+    completed_calls INTEGER NOT NULL,
+    PRIMARY KEY (invocation, run, station)
+);
+CREATE TABLE IF NOT EXISTS events (
+    invocation INTEGER NOT NULL REFERENCES invocations (id),
+    run INTEGER NOT NULL,
+    idx INTEGER NOT NULL,
+    time REAL NOT NULL,
+    ty TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    speed REAL NOT NULL,
+    station INTEGER NOT NULL,
+    station_free_channels INTEGER NOT NULL
+);
+";
+
+/// Database the runs of one invocation are written to.
+pub struct Database {
+    conn: Connection,
+    invocation: i64,
+
+    /// Also store every event
+    events: bool,
+}
+
+/// Calls at a station over a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StationSummary {
+    pub initiated_calls: usize,
+    pub blocked_calls: usize,
+
+    /// Calls handed over to the station
+    pub handovers: usize,
+
+    /// Calls dropped on handover to the station
+    pub dropped_calls: usize,
+
+    /// Calls ended by the customer at the station
+    pub completed_calls: usize,
+}
+
+impl StationSummary {
+    /// Summaries of every station, west to east.
+    pub fn from_results(results: &[CellEventResult]) -> [Self; NUM_STATIONS] {
+        let mut stations = [Self::default(); NUM_STATIONS];
+
+        for res in results {
+            let station = &mut stations[res.station as usize];
+            match (res.ty, res.outcome) {
+                (CellEventType::Initiate, StationResponse::Blocked) => {
+                    station.initiated_calls += 1;
+                    station.blocked_calls += 1;
+                }
+                (CellEventType::Initiate, _) => station.initiated_calls += 1,
+                (CellEventType::Handover | CellEventType::Enter, StationResponse::Terminated) => {
+                    station.dropped_calls += 1
+                }
+                (CellEventType::Handover | CellEventType::Enter, _) => station.handovers += 1,
+                (CellEventType::Terminate, _) => station.completed_calls += 1,
+                (CellEventType::Exit, _) => {}
+            }
+        }
+
+        stations
+    }
+}
+
+impl Database {
+    /// Open or create the database at `path` and record the invocation of `manifest`.
+    pub fn open<P: AsRef<Path>>(path: P, manifest: &Manifest, events: bool) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(io::Error::other)?;
+        conn.execute_batch(SCHEMA).map_err(io::Error::other)?;
+
+        conn.execute(
+            "INSERT INTO invocations (started_at, command, version, git_hash, seed, config)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                manifest.started_at as i64,
+                serde_json::to_string(&manifest.command).map_err(io::Error::other)?,
+                manifest.version,
+                manifest.git_hash,
+                // seeds may not fit in a signed integer
+                manifest.seed.to_string(),
+                serde_json::to_string(&manifest.config).map_err(io::Error::other)?,
+            ],
+        )
+        .map_err(io::Error::other)?;
+        let invocation = conn.last_insert_rowid();
+
+        Ok(Self {
+            conn,
+            invocation,
+            events,
+        })
+    }
+
+    /// Write the performance measure of run `run`, and the summaries and events of its results.
+    /// Antithetic runs give the results of both halves. Summaries skip the first `warmup` results
+    /// of every half.
+    pub fn write_run(
+        &mut self,
+        run: usize,
+        perf: PerfMeasure,
+        results: &[&[CellEventResult]],
+        warmup: usize,
+    ) -> io::Result<()> {
+        let tx = self.conn.transaction().map_err(io::Error::other)?;
+
+        tx.execute(
+            "INSERT INTO perf_measures (invocation, run, blocked_calls, dropped_calls)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.invocation,
+                run as i64,
+                perf.blocked_calls as f64,
+                perf.dropped_calls as f64
+            ],
+        )
+        .map_err(io::Error::other)?;
+
+        let mut stations = [StationSummary::default(); NUM_STATIONS];
+        for half in results {
+            let counted = &half[warmup.min(half.len())..];
+            for (total, summary) in stations
+                .iter_mut()
+                .zip(StationSummary::from_results(counted))
+            {
+                total.initiated_calls += summary.initiated_calls;
+                total.blocked_calls += summary.blocked_calls;
+                total.handovers += summary.handovers;
+                total.dropped_calls += summary.dropped_calls;
+                total.completed_calls += summary.completed_calls;
+            }
+        }
+
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO station_summaries (invocation, run, station, initiated_calls,
+                     blocked_calls, handovers, dropped_calls, completed_calls)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )
+                .map_err(io::Error::other)?;
+            for (station, summary) in stations.iter().enumerate() {
+                insert
+                    .execute(params![
+                        self.invocation,
+                        run as i64,
+                        // stations are numbered from 1, as in the event log
+                        station as i64 + 1,
+                        summary.initiated_calls as i64,
+                        summary.blocked_calls as i64,
+                        summary.handovers as i64,
+                        summary.dropped_calls as i64,
+                        summary.completed_calls as i64,
+                    ])
+                    .map_err(io::Error::other)?;
+            }
+        }
+
+        if self.events {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT INTO events (invocation, run, idx, time, ty, outcome, direction, speed,
+                     station, station_free_channels)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )
+                .map_err(io::Error::other)?;
+            for res in results.iter().flat_map(|half| half.iter()) {
+                insert
+                    .execute(params![
+                        self.invocation,
+                        res.run,
+                        res.idx as i64,
+                        res.time as f64,
+                        format!("{:?}", res.ty),
+                        format!("{:?}", res.outcome),
+                        format!("{:?}", res.direction),
+                        res.speed as f64,
+                        res.station as i64 + 1,
+                        res.station_free_channels as i64,
+                    ])
+                    .map_err(io::Error::other)?;
+            }
+        }
+
+        tx.commit().map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScenarioConfig;
+
+    #[test]
+    fn test_database() {
+        let path = std::env::temp_dir().join(format!(
+            "simulator_test_database_{}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let manifest = Manifest::start(&ScenarioConfig::default(), u64::MAX);
+        let results = [
+            CellEventResult::fixture(0, CellEventType::Initiate, StationResponse::Success),
+            CellEventResult::fixture(1, CellEventType::Initiate, StationResponse::Blocked),
+            CellEventResult::fixture(2, CellEventType::Handover, StationResponse::Terminated),
+            CellEventResult::fixture(3, CellEventType::Terminate, StationResponse::Success),
+        ];
+        let perf = PerfMeasure {
+            blocked_calls: 0.5,
+            dropped_calls: 0.5,
+        };
+
+        // every invocation is appended to the same database
+        for events in [false, true] {
+            let mut database = Database::open(&path, &manifest, events).unwrap();
+            database.write_run(1, perf, &[&results], 0).unwrap();
+            database
+                .write_run(2, perf, &[&results, &results], 0)
+                .unwrap();
+            database.write_run(3, perf, &[&results], 2).unwrap();
+        }
+
+        let conn = Connection::open(&path).unwrap();
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT count(*) FROM invocations"), 2);
+        assert_eq!(count("SELECT count(*) FROM perf_measures"), 6);
+        assert_eq!(count("SELECT count(*) FROM station_summaries"), 6 * 20);
+        // only the second invocation stored its events, including the warmup
+        assert_eq!(
+            count("SELECT count(*) FROM events WHERE invocation = 2"),
+            16
+        );
+        assert_eq!(count("SELECT count(*) FROM events"), 16);
+        assert_eq!(
+            count(
+                "SELECT blocked_calls FROM station_summaries
+                 WHERE invocation = 1 AND run = 2 AND station = 2"
+            ),
+            2
+        );
+        // the warmup is left out of the summaries
+        assert_eq!(
+            count(
+                "SELECT sum(initiated_calls) + sum(dropped_calls) + sum(completed_calls)
+                 FROM station_summaries WHERE invocation = 1 AND run = 3"
+            ),
+            2
+        );
+        let seed: String = conn
+            .query_row("SELECT seed FROM invocations WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(seed.parse::<u64>().unwrap(), u64::MAX);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_station_summary() {
+        let stations = StationSummary::from_results(&[
+            CellEventResult::fixture(0, CellEventType::Initiate, StationResponse::Blocked),
+            CellEventResult::fixture(1, CellEventType::Handover, StationResponse::Success),
+            CellEventResult::fixture(2, CellEventType::Handover, StationResponse::Terminated),
+        ]);
+
+        assert_eq!(
+            stations[1],
+            StationSummary {
+                initiated_calls: 1,
+                blocked_calls: 1,
+                handovers: 1,
+                dropped_calls: 1,
+                completed_calls: 0,
+            }
+        );
+        assert_eq!(stations[0], StationSummary::default());
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::ops::{Add, Div};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{base_station::StationResponse, generator::VEHICLE_LOC_DIST, FloatingPoint};
 
@@ -41,6 +41,12 @@ pub struct CellEvent {
 
     /// Position of vehicle relative to station
     pub position: RelativeVehiclePosition,
+
+    /// Off-ramp the vehicle leaves the highway at, identified by the station on its western side.
+    ///
+    /// If this is None, the vehicle stays on the highway for the rest of the call.
+    #[serde(serialize_with = "serialize_exit_ramp")]
+    pub exit: Option<BaseStationIdx>,
 }
 
 /// Result of an event
@@ -80,13 +86,47 @@ pub struct CellEventResult {
     pub station_free_channels: usize,
 }
 
+#[cfg(test)]
+impl CellEventResult {
+    /// Result of event `idx` of run 1 at the second station, for tests.
+    pub fn fixture(idx: usize, ty: CellEventType, outcome: StationResponse) -> Self {
+        Self {
+            idx,
+            run: 1,
+            time: idx as FloatingPoint,
+            ty,
+            outcome,
+            direction: VehicleDirection::WestToEast,
+            speed: 110.0,
+            station: BaseStationIdx::Two,
+            station_free_channels: 9,
+        }
+    }
+}
+
 /// Performance measure for sim
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct PerfMeasure {
-    /// Percentage of blocked calls
+    /// Percentage of calls initiated on the highway that are blocked
     pub blocked_calls: FloatingPoint,
 
-    /// Percentage of dropped calls
+    /// Percentage of calls on the highway, including ramp entries, that are dropped
+    pub dropped_calls: FloatingPoint,
+}
+
+/// Performance measure of a run, as written to the performance measure csv.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PerfRecord {
+    /// Run number, from 1
+    pub run: usize,
+
+    /// Base seed of the runs, from which the streams of every run are derived
+    pub seed: u64,
+
+    /// Fraction of blocked calls
+    pub blocked_calls: FloatingPoint,
+
+    /// Fraction of dropped calls
     pub dropped_calls: FloatingPoint,
 }
 
@@ -101,6 +141,13 @@ pub enum CellEventType {
 
     /// A customer's call is passed from one base station to another.
     Handover,
+
+    /// A customer leaves the highway at an off-ramp, taking the call off-network.
+    Exit,
+
+    /// A customer joins the highway at an on-ramp with a call in progress, handed over from
+    /// off the highway.
+    Enter,
 }
 
 /// Vehicle movement direction
@@ -154,6 +201,16 @@ where
     serializer.serialize_u32(station as u32 + 1)
 }
 
+fn serialize_exit_ramp<S>(exit: &Option<BaseStationIdx>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match exit {
+        Some(station) => serializer.serialize_some(&(*station as u32 + 1)),
+        None => serializer.serialize_none(),
+    }
+}
+
 /// Position of vehicle relative to the base station
 #[derive(Clone, Copy, Debug, Serialize)]
 pub enum RelativeVehiclePosition {
@@ -177,7 +234,7 @@ impl PartialEq for CellEvent {
 
 impl PartialOrd for CellEvent {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.time.total_cmp(&other.time))
+        Some(self.cmp(other))
     }
 }
 impl Eq for CellEvent {}
@@ -188,6 +245,17 @@ impl Ord for CellEvent {
     }
 }
 
+impl PerfRecord {
+    pub fn new(run: usize, seed: u64, perf: PerfMeasure) -> Self {
+        Self {
+            run,
+            seed,
+            blocked_calls: perf.blocked_calls,
+            dropped_calls: perf.dropped_calls,
+        }
+    }
+}
+
 impl Add<PerfMeasure> for PerfMeasure {
     type Output = PerfMeasure;
 
@@ -213,17 +281,17 @@ impl Div<f64> for PerfMeasure {
 impl RelativeVehiclePosition {
     /// Convert this value to the relative distance from the western end of the
     /// base station.
-    pub fn to_float(&self) -> FloatingPoint {
+    pub fn to_float(self) -> FloatingPoint {
         match self {
             RelativeVehiclePosition::WestEnd => VEHICLE_LOC_DIST.0,
             RelativeVehiclePosition::EastEnd => VEHICLE_LOC_DIST.1,
-            RelativeVehiclePosition::Other(pos) => *pos,
+            RelativeVehiclePosition::Other(pos) => pos,
         }
     }
 }
 
 impl CellEvent {
-    pub fn to_result(&self, outcome: StationResponse, free_channels: usize) -> CellEventResult {
+    pub fn to_result(self, outcome: StationResponse, free_channels: usize) -> CellEventResult {
         CellEventResult {
             idx: self.idx,
             run: self.run,
@@ -238,6 +306,25 @@ impl CellEvent {
     }
 }
 
+/// Station at a 0-based index, west to east.
+impl TryFrom<usize> for BaseStationIdx {
+    type Error = String;
+
+    fn try_from(idx: usize) -> Result<Self, Self::Error> {
+        use BaseStationIdx::*;
+
+        const STATIONS: [BaseStationIdx; 20] = [
+            One, Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Eleven, Twelve, Thirteen,
+            Fourteen, Fifteen, Sixteen, Seventeen, Eighteen, Nineteen, Twenty,
+        ];
+
+        STATIONS
+            .get(idx)
+            .copied()
+            .ok_or(format!("station index must be below 20, got {}", idx))
+    }
+}
+
 impl BaseStationIdx {
     pub fn next_station(&self, dir: VehicleDirection) -> Option<Self> {
         let station_idx = *self as usize;
@@ -245,14 +332,14 @@ impl BaseStationIdx {
         match dir {
             VehicleDirection::WestToEast => {
                 if station_idx < 19 {
-                    Some(unsafe { std::mem::transmute(station_idx + 1) })
+                    Some(unsafe { std::mem::transmute::<usize, Self>(station_idx + 1) })
                 } else {
                     None
                 }
             }
             VehicleDirection::EastToWest => {
                 if station_idx > 0 {
-                    Some(unsafe { std::mem::transmute(station_idx - 1) })
+                    Some(unsafe { std::mem::transmute::<usize, Self>(station_idx - 1) })
                 } else {
                     None
                 }
@@ -266,14 +353,14 @@ impl BaseStationIdx {
         match dir {
             VehicleDirection::WestToEast => {
                 if station_idx > 0 {
-                    Some(unsafe { std::mem::transmute(station_idx - 1) })
+                    Some(unsafe { std::mem::transmute::<usize, Self>(station_idx - 1) })
                 } else {
                     None
                 }
             }
             VehicleDirection::EastToWest => {
                 if station_idx < 19 {
-                    Some(unsafe { std::mem::transmute(station_idx + 1) })
+                    Some(unsafe { std::mem::transmute::<usize, Self>(station_idx + 1) })
                 } else {
                     None
                 }
@@ -338,14 +425,14 @@ mod tests {
         assert_eq!(prev_station, Some(BaseStationIdx::Eleven));
 
         // generate tests to check the prevoius station for stations 2-20
-        for station in BaseStationIdx::Two as usize..=BaseStationIdx::Twenty as usize {
+        for station in BaseStationIdx::Two as usize..BaseStationIdx::Twenty as usize {
             let station: BaseStationIdx = unsafe { std::mem::transmute(station) };
             let prev_station = station.previous_station(VehicleDirection::EastToWest);
 
             println!("curr: {:?}, prev: {:?}", station, prev_station);
             assert_eq!(
                 prev_station,
-                Some(unsafe { std::mem::transmute(station as usize + 1) })
+                Some(unsafe { std::mem::transmute::<usize, BaseStationIdx>(station as usize + 1) })
             );
         }
     }
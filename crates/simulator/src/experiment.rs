@@ -0,0 +1,405 @@
+//! Seeded simulation runs of a highway configuration, shared by experiments over many runs.
+//!
+//! Run `r` of every experiment with the same base seed draws from the same streams, so
+//! configurations are compared under common random numbers. Every variate has its own stream,
+//! so the streams stay aligned even when configurations draw different numbers of events.
+
+use probability::distribution::Mean;
+use rand::SeedableRng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use simulator_core::{
+    control::{controlled_mean, ControlledEstimate},
+    splitmix64, EventRunner, SplitSource,
+};
+
+use crate::{
+    add_ramp_entries,
+    base_station::{StationResponse, NUM_CHANNELS, NUM_STATIONS},
+    event::{CellEvent, CellEventType, PerfMeasure},
+    generator::{CallEventGenerator, InputModel, Tilt},
+    logic::{EventProcessor, Shared},
+    ramp::Ramp,
+    regenerative::Cycle,
+    FloatingPoint, RngSource,
+};
+
+/// Source of randomness for seeded runs.
+pub type RunSource = RngSource<rand::rngs::StdRng>;
+
+/// Seed of run `run` (zero-based) under a base seed.
+pub fn run_seed(seed: u64, run: usize) -> u64 {
+    splitmix64(seed ^ splitmix64(run as u64))
+}
+
+/// Source of run `run` (zero-based) under a base seed.
+pub fn run_source(seed: u64, run: usize) -> RunSource {
+    RngSource(rand::rngs::StdRng::seed_from_u64(run_seed(seed, run)))
+}
+
+/// A random base seed, for runs that were not given one.
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// Sample means of the input variates over the call initiations of a run, used as control
+/// variates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputMeans {
+    pub inter_arrival: f64,
+    pub call_duration: f64,
+    pub velocity: f64,
+}
+
+/// Performance measure of a run with tilted inputs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TiltedRun {
+    /// Performance measure as simulated
+    pub perf: PerfMeasure,
+
+    /// Performance measure with every blocked and dropped call weighted by its likelihood
+    /// ratio, an unbiased estimate under the input model
+    pub weighted: PerfMeasure,
+
+    /// Log likelihood ratio of the run as a whole
+    pub log_likelihood_ratio: f64,
+}
+
+/// Highway configuration simulated by an experiment.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub input_model: InputModel,
+
+    /// Channels of every station
+    pub channels: usize,
+
+    /// Channels reserved for handovers in every station
+    pub reserved: usize,
+
+    /// Channels reserved for handovers per station, west to east, instead of `reserved`
+    pub station_reserved: Option<[usize; NUM_STATIONS]>,
+
+    pub ramps: Vec<Ramp>,
+
+    /// Call initiations per run
+    pub num_events: usize,
+
+    /// Simulated time in seconds after which no more calls are initiated, if any
+    pub horizon: Option<FloatingPoint>,
+
+    /// Events skipped when calculating performance measures
+    pub warmup: usize,
+}
+
+impl Scenario {
+    /// A scenario with the default number of channels and no ramps.
+    pub fn new(input_model: InputModel, reserved: usize, num_events: usize, warmup: usize) -> Self {
+        Self {
+            input_model,
+            channels: NUM_CHANNELS,
+            reserved,
+            station_reserved: None,
+            ramps: Vec::new(),
+            num_events,
+            horizon: None,
+            warmup,
+        }
+    }
+
+    /// Whether a call initiation is within the horizon.
+    pub fn within_horizon(&self, event: &CellEvent) -> bool {
+        self.horizon.is_none_or(|horizon| event.time <= horizon)
+    }
+
+    /// Shared resources of the stations and ramps, before any call.
+    pub fn shared(&self) -> Shared {
+        let shared = match &self.station_reserved {
+            Some(reserved) => Shared::with_reserves(self.channels, reserved),
+            None => Shared::with_channels(self.channels, self.reserved),
+        };
+        shared.with_ramps(self.ramps.clone())
+    }
+
+    /// Simulate run `run` (zero-based), drawing every variate from streams split off `source`.
+    pub fn simulate<S: SplitSource>(&self, run: usize, source: S) -> PerfMeasure {
+        self.simulate_with_inputs(run, source).0
+    }
+
+    /// Simulate run `run`, along with the sample means of its generated call initiations.
+    pub fn simulate_with_inputs<S: SplitSource>(
+        &self,
+        run: usize,
+        source: S,
+    ) -> (PerfMeasure, InputMeans) {
+        let mut generator = CallEventGenerator::new(run + 1, source, &self.input_model)
+            .with_ramps(self.ramps.clone());
+
+        let events = generator
+            .by_ref()
+            .take(self.num_events)
+            .take_while(|ev| self.within_horizon(ev))
+            .collect::<Vec<_>>();
+        let inputs = InputMeans::from_events(&events);
+        let events = add_ramp_entries(&mut generator, events);
+
+        (self.run_events(run, events), inputs)
+    }
+
+    /// Simulate an antithetic pair of runs, averaging their performance measures.
+    pub fn simulate_antithetic<S: SplitSource>(&self, run: usize, source: S) -> PerfMeasure {
+        let mut generator = CallEventGenerator::new(run + 1, source, &self.input_model)
+            .with_ramps(self.ramps.clone());
+
+        let (mut events_a, mut events_b): (Vec<_>, Vec<_>) =
+            generator.antithetic().take(self.num_events).unzip();
+        events_a.retain(|ev| self.within_horizon(ev));
+        events_b.retain(|ev| self.within_horizon(ev));
+
+        // entering vehicles are not paired, each half gets its own stream
+        let events_a = add_ramp_entries(&mut generator, events_a);
+        let events_b = add_ramp_entries(&mut generator, events_b);
+
+        (self.run_events(run, events_a) + self.run_events(run, events_b)) / 2.0
+    }
+
+    /// Simulate run `run` with tilted call durations and inter-arrival times.
+    ///
+    /// Every blocked or dropped call is weighted by the likelihood ratio of the call
+    /// initiations up to the first one at or after it, whose inputs are all it can depend on.
+    /// With ramps, the number of entering calls depends on every initiation, so all of them
+    /// are weighted by the likelihood ratio of the whole run.
+    pub fn simulate_tilted<S: SplitSource>(
+        &self,
+        run: usize,
+        source: S,
+        tilt: Tilt,
+    ) -> Result<TiltedRun, String> {
+        let mut generator = CallEventGenerator::new(run + 1, source, &self.input_model)
+            .with_ramps(self.ramps.clone())
+            .with_tilt(tilt)?;
+
+        let (events, log_ratios): (Vec<_>, Vec<_>) = (0..self.num_events)
+            .map_while(|_| {
+                let ev = generator.next().filter(|ev| self.within_horizon(ev))?;
+                Some((ev, generator.log_likelihood_ratio()))
+            })
+            .unzip();
+        let times = events.iter().map(|ev| ev.time).collect::<Vec<_>>();
+        let log_ratio = log_ratios.last().copied().unwrap_or_default();
+        let events = add_ramp_entries(&mut generator, events);
+
+        let mut runner = self.runner(run, events);
+        let perf = runner.performance_measure(self.warmup);
+
+        let weight = |time: FloatingPoint| match self.ramps.is_empty() {
+            true => log_ratios
+                .get(times.partition_point(|t| *t < time))
+                .map_or(log_ratio, |l| *l)
+                .exp(),
+            false => log_ratio.exp(),
+        };
+        let results = &runner.into_results()[self.warmup..];
+        let (blocked, dropped) =
+            results
+                .iter()
+                .fold((0.0, 0.0), |(blocked, dropped), res| match res.outcome {
+                    StationResponse::Blocked => (blocked + weight(res.time), dropped),
+                    StationResponse::Terminated => (blocked, dropped + weight(res.time)),
+                    _ => (blocked, dropped),
+                });
+        let calls = results.iter().map(|res| res.idx).max().unwrap_or_default() as FloatingPoint;
+        let initiated = results
+            .iter()
+            .filter(|res| matches!(res.ty, CellEventType::Initiate))
+            .map(|res| res.idx)
+            .max()
+            .unwrap_or_default() as FloatingPoint;
+
+        Ok(TiltedRun {
+            perf,
+            weighted: PerfMeasure {
+                blocked_calls: blocked / initiated,
+                dropped_calls: dropped / calls,
+            },
+            log_likelihood_ratio: log_ratio,
+        })
+    }
+
+    /// Simulate run `run` and split it into regeneration cycles, each starting with every
+    /// station idle. The warmup does not apply.
+    pub fn simulate_cycles<S: SplitSource>(&self, run: usize, source: S) -> Vec<Cycle> {
+        let mut generator = CallEventGenerator::new(run + 1, source, &self.input_model)
+            .with_ramps(self.ramps.clone());
+
+        let events = generator
+            .by_ref()
+            .take(self.num_events)
+            .take_while(|ev| self.within_horizon(ev))
+            .collect::<Vec<_>>();
+        let events = add_ramp_entries(&mut generator, events);
+
+        self.runner(run, events)
+            .regeneration_cycles()
+            .map(Cycle::from_results)
+            .collect()
+    }
+
+    fn run_events(&self, run: usize, events: Vec<CellEvent>) -> PerfMeasure {
+        self.runner(run, events).performance_measure(self.warmup)
+    }
+
+    fn runner(&self, run: usize, events: Vec<CellEvent>) -> EventRunner<EventProcessor> {
+        let mut runner =
+            EventRunner::init(EventProcessor::new(run + 1, events), Some(self.shared()));
+        runner.run();
+
+        runner
+    }
+
+    /// Simulate runs `0..num_runs` under a base seed, in parallel.
+    pub fn simulate_runs(&self, num_runs: usize, seed: u64) -> Vec<PerfMeasure> {
+        (0..num_runs)
+            .into_par_iter()
+            .map(|run| self.simulate(run, run_source(seed, run)))
+            .collect()
+    }
+}
+
+impl InputMeans {
+    /// Sample means of generated call initiations, without entries at ramps.
+    pub fn from_events(events: &[CellEvent]) -> Self {
+        let n = events.len().max(1) as f64;
+
+        Self {
+            // calls are generated from time zero
+            inter_arrival: events.last().map_or(0.0, |ev| ev.time as f64) / n,
+            call_duration: events
+                .iter()
+                .map(|ev| ev.remaining_time as f64)
+                .sum::<f64>()
+                / n,
+            velocity: events.iter().map(|ev| ev.velocity as f64).sum::<f64>() / n,
+        }
+    }
+
+    /// Means of the input model's distributions, with unset distributions defaulted.
+    pub fn expected(model: &InputModel) -> Self {
+        let model = model.clone().or_default();
+
+        Self {
+            inter_arrival: model.inter_arrival.unwrap().mean(),
+            call_duration: model.call_duration.unwrap().mean(),
+            velocity: model.vehicle_velocity.unwrap().mean(),
+        }
+    }
+
+    pub fn to_vec(self) -> Vec<f64> {
+        vec![self.inter_arrival, self.call_duration, self.velocity]
+    }
+}
+
+/// Blocked and dropped calls estimated with the input means of every run as control variates.
+pub fn controlled_perf_measure(
+    runs: &[(PerfMeasure, InputMeans)],
+    model: &InputModel,
+    confidence: f64,
+) -> Result<(ControlledEstimate, ControlledEstimate), String> {
+    let controls = runs
+        .iter()
+        .map(|(_, inputs)| inputs.to_vec())
+        .collect::<Vec<_>>();
+    let expected = InputMeans::expected(model).to_vec();
+    let estimate = |output: fn(&PerfMeasure) -> FloatingPoint| {
+        let outputs = runs
+            .iter()
+            .map(|(perf, _)| output(perf) as f64)
+            .collect::<Vec<_>>();
+        controlled_mean(&outputs, &controls, &expected, confidence)
+    };
+
+    Ok((
+        estimate(|p| p.blocked_calls)?,
+        estimate(|p| p.dropped_calls)?,
+    ))
+}
+
+/// Print estimates with and without control variates, in percent.
+pub fn print_controlled(blocked: &ControlledEstimate, dropped: &ControlledEstimate) {
+    println!("\ncontrol variates: inter-arrival time, call duration and velocity means");
+    for (name, estimate) in [("blocked", blocked), ("dropped", dropped)] {
+        println!(
+            "{}: {:.4}% ± {:.4}% controlled, {:.4}% ± {:.4}% crude, {:.1}% variance reduction",
+            name,
+            estimate.mean * 100.0,
+            estimate.half_width * 100.0,
+            estimate.crude.mean * 100.0,
+            estimate.crude.half_width * 100.0,
+            estimate.variance_reduction * 100.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_runs() {
+        let scenario = Scenario::new(InputModel::default(), 0, 2_000, 0);
+
+        let perf = |seed, run| scenario.simulate(run, run_source(seed, run));
+        assert_eq!(perf(3, 0), perf(3, 0));
+        assert_ne!(perf(3, 0), perf(3, 1));
+        assert_ne!(run_seed(3, 0), run_seed(4, 0));
+    }
+
+    #[test]
+    fn test_streams_aligned_per_variate() {
+        let slow = InputModel {
+            inter_arrival: Some("exponential(2.72)".parse().unwrap()),
+            ..Default::default()
+        };
+        let fast = InputModel {
+            inter_arrival: Some("exponential(0.5)".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let events = |model: &InputModel| {
+            CallEventGenerator::new(1, run_source(5, 2), model)
+                .take(500)
+                .collect::<Vec<_>>()
+        };
+        let (slow, fast) = (events(&slow), events(&fast));
+
+        // only the arrival times differ between the configurations
+        assert!(slow.iter().zip(&fast).all(|(a, b)| a.time > b.time
+            && a.station == b.station
+            && a.remaining_time == b.remaining_time
+            && a.velocity == b.velocity
+            && a.position.to_float() == b.position.to_float()));
+    }
+
+    #[test]
+    fn test_control_variates() {
+        let expected = InputMeans::expected(&InputModel::default());
+        assert!((expected.inter_arrival - 1.36982).abs() < 1e-9);
+        assert!((expected.velocity - 120.072).abs() < 1e-3);
+
+        let model = InputModel {
+            inter_arrival: Some("exponential(0.5)".parse().unwrap()),
+            ..Default::default()
+        };
+        let scenario = Scenario::new(model.clone(), 0, 2_000, 0);
+        let runs = (0..30)
+            .map(|run| scenario.simulate_with_inputs(run, run_source(9, run)))
+            .collect::<Vec<_>>();
+
+        // under heavy load, runs with faster arrivals drop more calls
+        let (blocked, dropped) = controlled_perf_measure(&runs, &model, 0.95).unwrap();
+        assert!(dropped.coefficients[0] < 0.0, "{:?}", dropped);
+        assert!(dropped.variance_reduction > 0.1, "{:?}", dropped);
+        assert!(dropped.half_width < dropped.crude.half_width);
+        assert!((blocked.mean - blocked.crude.mean).abs() < blocked.crude.half_width);
+
+        assert!(controlled_perf_measure(&runs[..3], &model, 0.95).is_err());
+    }
+}
@@ -1,10 +1,17 @@
 //! Random variable generators, their parameters and other sampling stuff are defined here.
 
+use std::{fmt::Display, fs, io, path::Path, str::FromStr};
+
 use probability::{distribution, source::Source};
-use simulator_core::{AntitheticIterator, ExponentialLoc, SingleVariateIterator};
+use serde::{Deserialize, Serialize};
+use simulator_core::{
+    importance::ExponentialTilt, AntitheticIterator, ExponentialLoc, SingleVariateIterator,
+    SplitSource, Truncated, Variate,
+};
 
 use crate::{
     event::{BaseStationIdx, CellEvent, CellEventType, RelativeVehiclePosition, VehicleDirection},
+    ramp::{choose_exit, Ramp, RampEntryGenerator},
     FloatingPoint,
 };
 
@@ -18,6 +25,10 @@ pub const VEHICLE_VELOCITY_MEAN: FloatingPoint = 120.072;
 /// Standard deviation of velocity in km/h.
 pub const VEHICLE_VELOCITY_STDDEV: FloatingPoint = 9.0186;
 
+/// Bounds of the velocity distribution in km/h.
+/// Vehicles on the highway must be moving, and no faster than physically plausible.
+pub const VEHICLE_VELOCITY_BOUNDS: (FloatingPoint, FloatingPoint) = (1.0, 250.0);
+
 /// Cell tower distributions.
 pub const CELL_TOWER_DIST: (FloatingPoint, FloatingPoint) = (0.0, 20.0);
 
@@ -36,6 +47,42 @@ pub const CALL_DURATION_LOC: FloatingPoint = 10.004;
 /// Average call inter-arrival time in seconds.
 pub const CALL_INTER_ARR_LAMBDA: FloatingPoint = 1.36982;
 
+/// Input distributions of the call event generator.
+///
+/// Distributions that are not set fall back to the fitted defaults above.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputModel {
+    /// Call duration in seconds
+    pub call_duration: Option<Variate>,
+
+    /// Call inter-arrival time in seconds
+    pub inter_arrival: Option<Variate>,
+
+    /// Base station of a call initiation, floored to a station index
+    pub cell_tower: Option<Variate>,
+
+    /// Vehicle velocity in km/h
+    pub vehicle_velocity: Option<Variate>,
+
+    /// Vehicle position inside a station's coverage, in meters from west to east
+    pub vehicle_position: Option<Variate>,
+
+    /// Vehicle direction, west to east if above 0.5
+    pub vehicle_direction: Option<Variate>,
+}
+
+/// Input variates of the call event generator, in the order their streams are split off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    CallDuration,
+    InterArrival,
+    CellTower,
+    VehicleVelocity,
+    VehiclePosition,
+    VehicleDirection,
+}
+
 /// Generator iterator for call events.
 #[derive(Debug)]
 pub struct CallEventGenerator<S>
@@ -52,20 +99,41 @@ where
     /// Simulation run number
     run: usize,
 
-    // expon dist
-    call_duration: SingleVariateIterator<ExponentialLoc, S>,
+    // expon dist by default
+    call_duration: SingleVariateIterator<Variate, S>,
 
-    // expon dist
-    call_inter_arrival: SingleVariateIterator<distribution::Exponential, S>,
+    // expon dist by default
+    call_inter_arrival: SingleVariateIterator<Variate, S>,
 
+    // uniform dist by default
+    cell_tower: SingleVariateIterator<Variate, S>,
+    // truncated norm dist by default
+    vehicle_velocity: SingleVariateIterator<Variate, S>,
+    // uniform dist by default
+    vehicle_position: SingleVariateIterator<Variate, S>,
+    // uniform dist by default
+    vehicle_direction: SingleVariateIterator<Variate, S>,
+
+    /// Ramps along the highway
+    ramps: Vec<Ramp>,
     // uniform dist
-    cell_tower: SingleVariateIterator<distribution::Uniform, S>,
-    // norm dist
-    vehicle_velocity: SingleVariateIterator<distribution::Gaussian, S>,
-    // uniform dist
-    vehicle_position: SingleVariateIterator<distribution::Uniform, S>,
-    // uniform dist
-    vehicle_direction: SingleVariateIterator<distribution::Uniform, S>,
+    exit_ramp: SingleVariateIterator<distribution::Uniform, S>,
+
+    /// Tilts of the call duration and inter-arrival time, for importance sampling
+    tilts: Option<(ExponentialTilt, ExponentialTilt)>,
+
+    /// Log likelihood ratio of the calls generated so far
+    log_likelihood_ratio: f64,
+}
+
+/// Tilt of the input model for importance sampling.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tilt {
+    /// Factor of the call arrival rate
+    pub arrival_rate: f64,
+
+    /// Factor of the mean call duration, above its location
+    pub call_duration: f64,
 }
 
 /// Generator iterator for call events with antithetic pair sampling.
@@ -83,12 +151,15 @@ where
 
     run: usize,
 
-    call_duration: AntitheticIterator<ExponentialLoc, S>,
-    call_inter_arrival: AntitheticIterator<distribution::Exponential, S>,
-    cell_tower: AntitheticIterator<distribution::Uniform, S>,
-    vehicle_velocity: AntitheticIterator<distribution::Gaussian, S>,
-    vehicle_position: AntitheticIterator<distribution::Uniform, S>,
-    vehicle_direction: AntitheticIterator<distribution::Uniform, S>,
+    call_duration: AntitheticIterator<Variate, S>,
+    call_inter_arrival: AntitheticIterator<Variate, S>,
+    cell_tower: AntitheticIterator<Variate, S>,
+    vehicle_velocity: AntitheticIterator<Variate, S>,
+    vehicle_position: AntitheticIterator<Variate, S>,
+    vehicle_direction: AntitheticIterator<Variate, S>,
+
+    ramps: Vec<Ramp>,
+    exit_ramp: AntitheticIterator<distribution::Uniform, S>,
 }
 
 /// Calculate the time to next station.
@@ -123,7 +194,19 @@ pub fn calculate_ttn(
     }
 }
 
+/// The variate as an exponential distribution, if it is one.
+pub(crate) fn as_exponential(variate: &Variate) -> Option<ExponentialLoc> {
+    match variate {
+        Variate::Exponential(exp) => Some(exp.clone()),
+        Variate::Scaled(inner, factor) => as_exponential(inner)
+            .map(|exp| ExponentialLoc::new(exp.lambda() / factor, exp.loc() * factor)),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn cell_event_from_random_variables(
+    ramps: &[Ramp],
     idx: usize,
     run: u32,
     call_dur: FloatingPoint,
@@ -133,6 +216,7 @@ fn cell_event_from_random_variables(
     vehicle_velocity: FloatingPoint,
     vehicle_position: FloatingPoint,
     vehicle_direction: FloatingPoint,
+    exit_ramp: FloatingPoint,
 ) -> CellEvent {
     let dir = match vehicle_direction > 0.5 {
         true => VehicleDirection::WestToEast,
@@ -141,6 +225,13 @@ fn cell_event_from_random_variables(
 
     let ttn = calculate_ttn(call_dur, vehicle_position, vehicle_velocity, dir);
 
+    let station = {
+        let station_idx = (cell_tower % 20.0).floor() as usize;
+        let station: BaseStationIdx = unsafe { std::mem::transmute(station_idx) };
+
+        station
+    };
+
     CellEvent {
         idx,
         run,
@@ -150,34 +241,37 @@ fn cell_event_from_random_variables(
         ttn,
         velocity: vehicle_velocity as FloatingPoint,
         direction: dir,
-        station: {
-            let station_idx = (cell_tower % 20.0).floor() as usize;
-            let station: BaseStationIdx = unsafe { std::mem::transmute(station_idx) };
-
-            station
-        },
+        station,
         position: RelativeVehiclePosition::Other(vehicle_position as FloatingPoint),
+        exit: choose_exit(ramps, station, dir, exit_ramp),
     }
 }
 
 impl<S> Iterator for CallEventGenerator<S>
 where
-    S: Source + Clone,
+    S: Source,
 {
     type Item = CellEvent;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let call_dur = self.call_duration.clone().take(1).last()?;
-        let inter_arr = self.call_inter_arrival.clone().take(1).last()?;
-        let cell_tower = self.cell_tower.clone().take(1).last()?;
-        let vehicle_velocity = self.vehicle_velocity.clone().take(1).last()?;
-        let vehicle_position = self.vehicle_position.clone().take(1).last()?;
-        let vehicle_direction = self.vehicle_direction.clone().take(1).last()?;
+        let call_dur = self.call_duration.next()?;
+        let inter_arr = self.call_inter_arrival.next()?;
+        let cell_tower = self.cell_tower.next()?;
+        let vehicle_velocity = self.vehicle_velocity.next()?;
+        let vehicle_position = self.vehicle_position.next()?;
+        let vehicle_direction = self.vehicle_direction.next()?;
+        let exit_ramp = self.exit_ramp.next()?;
+
+        if let Some((duration, arrival)) = &self.tilts {
+            self.log_likelihood_ratio +=
+                duration.log_likelihood_ratio(call_dur) + arrival.log_likelihood_ratio(inter_arr);
+        }
 
         self.count += 1;
         self.time += inter_arr as FloatingPoint;
 
         let ev = cell_event_from_random_variables(
+            &self.ramps,
             self.count,
             self.run as u32,
             call_dur as FloatingPoint,
@@ -186,6 +280,7 @@ where
             vehicle_velocity as FloatingPoint,
             vehicle_position as FloatingPoint,
             vehicle_direction as FloatingPoint,
+            exit_ramp as FloatingPoint,
         );
 
         Some(ev)
@@ -194,32 +289,31 @@ where
 
 impl<S> Iterator for AntitheticCallEventGenerator<S>
 where
-    S: Source + Clone,
+    S: Source,
 {
     type Item = (CellEvent, CellEvent);
 
     fn next(&mut self) -> Option<Self::Item> {
         // debug_println!("generating call duration");
-        let (call_dur_a, call_dur_b) = self.call_duration.clone().take(1).last()?;
+        let (call_dur_a, call_dur_b) = self.call_duration.next()?;
         // debug_println!("generating inter arrival");
-        let (inter_arr_a, inter_arr_b) = self.call_inter_arrival.clone().take(1).last()?;
+        let (inter_arr_a, inter_arr_b) = self.call_inter_arrival.next()?;
         // debug_println!("generating cell tower");
-        let (cell_tower_a, cell_tower_b) = self.cell_tower.clone().take(1).last()?;
+        let (cell_tower_a, cell_tower_b) = self.cell_tower.next()?;
         // debug_println!("generating vehicle velocity");
-        let (vehicle_velocity_a, vehicle_velocity_b) =
-            self.vehicle_velocity.clone().take(1).last()?;
+        let (vehicle_velocity_a, vehicle_velocity_b) = self.vehicle_velocity.next()?;
         // debug_println!("generating vehicle position");
-        let (vehicle_position_a, vehicle_position_b) =
-            self.vehicle_position.clone().take(1).last()?;
+        let (vehicle_position_a, vehicle_position_b) = self.vehicle_position.next()?;
         // debug_println!("generating vehicle direction");
-        let (vehicle_direction_a, vehicle_direction_b) =
-            self.vehicle_direction.clone().take(1).last()?;
+        let (vehicle_direction_a, vehicle_direction_b) = self.vehicle_direction.next()?;
+        let (exit_ramp_a, exit_ramp_b) = self.exit_ramp.next()?;
 
         self.count += 1;
         self.time_a += inter_arr_a as FloatingPoint;
         self.time_b += inter_arr_b as FloatingPoint;
 
         let ev_a = cell_event_from_random_variables(
+            &self.ramps,
             self.count,
             self.run as u32,
             call_dur_a as FloatingPoint,
@@ -228,9 +322,11 @@ where
             vehicle_velocity_a as FloatingPoint,
             vehicle_position_a as FloatingPoint,
             vehicle_direction_a as FloatingPoint,
+            exit_ramp_a as FloatingPoint,
         );
 
         let ev_b = cell_event_from_random_variables(
+            &self.ramps,
             self.count,
             self.run as u32,
             call_dur_b as FloatingPoint,
@@ -239,74 +335,297 @@ where
             vehicle_velocity_b as FloatingPoint,
             vehicle_position_b as FloatingPoint,
             vehicle_direction_b as FloatingPoint,
+            exit_ramp_b as FloatingPoint,
         );
 
         Some((ev_a, ev_b))
     }
 }
 
+impl InputModel {
+    /// Read an input model from a toml file, e.g. `call_duration = "lognormal(4.2, 0.8)"`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(&path)?;
+
+        toml::from_str(&contents).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.as_ref().display(), e),
+            )
+        })
+    }
+
+    /// Check that every distribution that is set only takes values the highway can simulate:
+    /// non-negative call durations and inter-arrival times, positive velocities and positions
+    /// within a station's coverage.
+    pub fn check_support(&self) -> Result<(), String> {
+        let check = |input: Input,
+                     variate: &Option<Variate>,
+                     range: &str,
+                     valid: fn(f64, f64) -> bool| {
+            let Some(variate) = variate else {
+                return Ok(());
+            };
+            let (low, high) = variate.support();
+            match valid(low, high) {
+                true => Ok(()),
+                false => Err(format!(
+                    "{} must be {}, but {} takes values in [{}, {}]; \
+                     bound it with truncated(<variate>, low, high) or clamped(<variate>, low, high)",
+                    input, range, variate, low, high
+                )),
+            }
+        };
+
+        // exponentials without a location start at 0, but never take it
+        check(
+            Input::CallDuration,
+            &self.call_duration,
+            "non-negative",
+            |low, _| low >= 0.0,
+        )?;
+        check(
+            Input::InterArrival,
+            &self.inter_arrival,
+            "non-negative",
+            |low, _| low >= 0.0,
+        )?;
+        check(
+            Input::VehicleVelocity,
+            &self.vehicle_velocity,
+            "positive",
+            |low, _| low > 0.0,
+        )?;
+        check(
+            Input::VehiclePosition,
+            &self.vehicle_position,
+            &format!("within [{}, {}]", VEHICLE_LOC_DIST.0, VEHICLE_LOC_DIST.1),
+            |low, high| low >= VEHICLE_LOC_DIST.0 as f64 && high <= VEHICLE_LOC_DIST.1 as f64,
+        )
+    }
+
+    /// Use distributions set in `other` in place of the ones in this model.
+    pub fn overridden_by(self, other: InputModel) -> Self {
+        Self {
+            call_duration: other.call_duration.or(self.call_duration),
+            inter_arrival: other.inter_arrival.or(self.inter_arrival),
+            cell_tower: other.cell_tower.or(self.cell_tower),
+            vehicle_velocity: other.vehicle_velocity.or(self.vehicle_velocity),
+            vehicle_position: other.vehicle_position.or(self.vehicle_position),
+            vehicle_direction: other.vehicle_direction.or(self.vehicle_direction),
+        }
+    }
+
+    /// Fill in every distribution that is not set with its fitted default.
+    pub fn or_default(self) -> Self {
+        Self {
+            call_duration: self.call_duration.or_else(|| {
+                Some(
+                    ExponentialLoc::new(
+                        1.0 / CALL_DURATION_LAMBDA as f64,
+                        CALL_DURATION_LOC as f64,
+                    )
+                    .into(),
+                )
+            }),
+            inter_arrival: self.inter_arrival.or_else(|| {
+                Some(ExponentialLoc::new(1.0 / CALL_INTER_ARR_LAMBDA as f64, 0.0).into())
+            }),
+            cell_tower: self.cell_tower.or_else(|| {
+                Some(
+                    distribution::Uniform::new(CELL_TOWER_DIST.0 as f64, CELL_TOWER_DIST.1 as f64)
+                        .into(),
+                )
+            }),
+            vehicle_velocity: self.vehicle_velocity.or_else(|| {
+                Some(
+                    Truncated::new(
+                        Variate::from(distribution::Gaussian::new(
+                            VEHICLE_VELOCITY_MEAN as f64,
+                            VEHICLE_VELOCITY_STDDEV as f64,
+                        )),
+                        VEHICLE_VELOCITY_BOUNDS.0 as f64,
+                        VEHICLE_VELOCITY_BOUNDS.1 as f64,
+                    )
+                    .into(),
+                )
+            }),
+            vehicle_position: self.vehicle_position.or_else(|| {
+                Some(
+                    distribution::Uniform::new(
+                        VEHICLE_LOC_DIST.0 as f64,
+                        VEHICLE_LOC_DIST.1 as f64,
+                    )
+                    .into(),
+                )
+            }),
+            vehicle_direction: self.vehicle_direction.or_else(|| {
+                Some(
+                    distribution::Uniform::new(
+                        VEHICLE_DIR_DIST.0 as f64,
+                        VEHICLE_DIR_DIST.1 as f64,
+                    )
+                    .into(),
+                )
+            }),
+        }
+    }
+}
+
+impl Input {
+    pub const ALL: [Input; 6] = [
+        Input::CallDuration,
+        Input::InterArrival,
+        Input::CellTower,
+        Input::VehicleVelocity,
+        Input::VehiclePosition,
+        Input::VehicleDirection,
+    ];
+
+    /// Index of the input's stream among those split off by `CallEventGenerator::new`.
+    pub fn stream_index(self) -> usize {
+        self as usize
+    }
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().replace('-', "_").as_str() {
+            "call_duration" => Ok(Input::CallDuration),
+            "inter_arrival" => Ok(Input::InterArrival),
+            "cell_tower" => Ok(Input::CellTower),
+            "vehicle_velocity" => Ok(Input::VehicleVelocity),
+            "vehicle_position" => Ok(Input::VehiclePosition),
+            "vehicle_direction" => Ok(Input::VehicleDirection),
+            other => Err(format!("unknown input variate '{}'", other)),
+        }
+    }
+}
+
+impl Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Input::CallDuration => "call_duration",
+            Input::InterArrival => "inter_arrival",
+            Input::CellTower => "cell_tower",
+            Input::VehicleVelocity => "vehicle_velocity",
+            Input::VehiclePosition => "vehicle_position",
+            Input::VehicleDirection => "vehicle_direction",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl<S> CallEventGenerator<S>
 where
-    S: Source + Clone,
+    S: SplitSource,
 {
-    /// Initialize the event generator, along with any distribution overrides.
-    pub fn new(
-        run: usize,
-        source: S,
-        call_dur: Option<ExponentialLoc>,
-        inter_arrival: Option<distribution::Exponential>,
-        cell_tower: Option<distribution::Uniform>,
-        vehicle_velocity: Option<distribution::Gaussian>,
-        vehicle_position: Option<distribution::Uniform>,
-        vehicle_direction: Option<distribution::Uniform>,
-    ) -> Self {
+    /// Initialize the event generator, with any distributions set in the input model
+    /// overriding the defaults.
+    ///
+    /// Every variate draws from its own stream split off `source`, in the order of `Input`.
+    pub fn new(run: usize, mut source: S, model: &InputModel) -> Self {
+        let model = model.clone().or_default();
+
         Self {
             count: 0,
-            source: source.clone(),
             time: 0.0,
             run,
-            call_duration: SingleVariateIterator::new(
-                call_dur.unwrap_or(ExponentialLoc::new(
-                    1.0 / CALL_DURATION_LAMBDA as f64,
-                    CALL_DURATION_LOC as f64,
-                )),
-                source.clone(),
-            ),
+            call_duration: SingleVariateIterator::new(model.call_duration.unwrap(), source.split()),
             call_inter_arrival: SingleVariateIterator::new(
-                inter_arrival.unwrap_or(distribution::Exponential::new(
-                    1.0 / CALL_INTER_ARR_LAMBDA as f64,
-                )),
-                source.clone(),
-            ),
-            cell_tower: SingleVariateIterator::new(
-                cell_tower.unwrap_or(distribution::Uniform::new(
-                    CELL_TOWER_DIST.0 as f64,
-                    CELL_TOWER_DIST.1 as f64,
-                )),
-                source.clone(),
+                model.inter_arrival.unwrap(),
+                source.split(),
             ),
+            cell_tower: SingleVariateIterator::new(model.cell_tower.unwrap(), source.split()),
             vehicle_velocity: SingleVariateIterator::new(
-                vehicle_velocity.unwrap_or(distribution::Gaussian::new(
-                    VEHICLE_VELOCITY_MEAN as f64,
-                    VEHICLE_VELOCITY_STDDEV as f64,
-                )),
-                source.clone(),
+                model.vehicle_velocity.unwrap(),
+                source.split(),
             ),
             vehicle_position: SingleVariateIterator::new(
-                vehicle_position.unwrap_or(distribution::Uniform::new(
-                    VEHICLE_LOC_DIST.0 as f64,
-                    VEHICLE_LOC_DIST.1 as f64,
-                )),
-                source.clone(),
+                model.vehicle_position.unwrap(),
+                source.split(),
             ),
             vehicle_direction: SingleVariateIterator::new(
-                vehicle_direction.unwrap_or(distribution::Uniform::new(
-                    VEHICLE_DIR_DIST.0 as f64,
-                    VEHICLE_DIR_DIST.1 as f64,
-                )),
-                source.clone(),
+                model.vehicle_direction.unwrap(),
+                source.split(),
             ),
+            ramps: Vec::new(),
+            exit_ramp: SingleVariateIterator::new(
+                distribution::Uniform::new(0.0, 1.0),
+                source.split(),
+            ),
+            source,
+            tilts: None,
+            log_likelihood_ratio: 0.0,
+        }
+    }
+
+    /// Draw call durations and inter-arrival times from tilted distributions, tracking the
+    /// likelihood ratio of the generated calls under the untilted ones.
+    ///
+    /// Both distributions must be exponential. Vehicles entering at ramps are drawn without the
+    /// tilt, and antithetic pairs carry no likelihood ratio.
+    pub fn with_tilt(mut self, tilt: Tilt) -> Result<Self, String> {
+        if tilt.arrival_rate <= 0.0 || tilt.call_duration <= 0.0 {
+            return Err("tilting factors must be positive".to_string());
         }
+
+        let exponential = |variate: &Variate, input: Input| {
+            as_exponential(variate).ok_or(format!(
+                "importance sampling needs an exponential {}, got {}",
+                input, variate
+            ))
+        };
+        let duration = ExponentialTilt::new(
+            exponential(self.call_duration.distribution(), Input::CallDuration)?,
+            1.0 / tilt.call_duration,
+        );
+        let arrival = ExponentialTilt::new(
+            exponential(self.call_inter_arrival.distribution(), Input::InterArrival)?,
+            tilt.arrival_rate,
+        );
+
+        self.call_duration = self
+            .call_duration
+            .with_distribution(duration.tilted().clone().into());
+        self.call_inter_arrival = self
+            .call_inter_arrival
+            .with_distribution(arrival.tilted().clone().into());
+        self.tilts = Some((duration, arrival));
+        Ok(self)
+    }
+
+    /// Log likelihood ratio of the calls generated so far, zero without a tilt.
+    pub fn log_likelihood_ratio(&self) -> f64 {
+        self.log_likelihood_ratio
+    }
+
+    /// Add ramps along the highway, where vehicles may leave mid-call.
+    pub fn with_ramps(mut self, ramps: Vec<Ramp>) -> Self {
+        self.ramps = ramps;
+        self
+    }
+
+    /// Create a generator for vehicles entering the highway at ramps.
+    ///
+    /// Entering vehicles draw their calls from the same distributions as this generator,
+    /// on streams of their own.
+    pub fn ramp_entries(&mut self) -> RampEntryGenerator<S> {
+        let call_duration = match &self.tilts {
+            Some((duration, _)) => duration.nominal().clone().into(),
+            None => self.call_duration.distribution().clone(),
+        };
+
+        RampEntryGenerator::new(
+            self.run,
+            self.source.split(),
+            self.ramps.clone(),
+            call_duration,
+            self.vehicle_velocity.distribution().clone(),
+            self.vehicle_direction.distribution().clone(),
+        )
     }
 
     /// Create a new generator that generates antithetic pairs of samples.
@@ -323,6 +642,8 @@ where
             vehicle_velocity: self.vehicle_velocity.antithetic_iter(ANTITHETIC_PREPARE),
             vehicle_position: self.vehicle_position.antithetic_iter(ANTITHETIC_PREPARE),
             vehicle_direction: self.vehicle_direction.antithetic_iter(ANTITHETIC_PREPARE),
+            ramps: self.ramps.clone(),
+            exit_ramp: self.exit_ramp.antithetic_iter(ANTITHETIC_PREPARE),
         }
     }
 }
@@ -336,49 +657,99 @@ mod tests {
         source,
     };
 
-    use crate::{debug_println, RngSource};
+    use simulator_core::gof;
+
+    use crate::debug_println;
 
     use super::*;
 
+    /// Samples per statistical test
+    const STAT_SAMPLES: usize = 20_000;
+
+    /// Significance level of statistical tests. Sources are seeded, so results are fixed.
+    const ALPHA: f64 = 0.001;
+
     struct MockSource(u64);
 
-    impl Source for MockSource {
-        fn read_u64(&mut self) -> u64 {
-            self.0
-        }
+    /// Samples of each continuous variate in generated call initiations.
+    fn continuous_samples(events: &[CellEvent]) -> [(&'static str, Vec<f64>); 4] {
+        let inter_arrival = events
+            .iter()
+            .scan(0.0, |prev, ev| {
+                let inter_arr = ev.time - *prev;
+                *prev = ev.time;
+                Some(inter_arr)
+            })
+            .collect();
+
+        [
+            ("inter_arrival", inter_arrival),
+            (
+                "call_duration",
+                events.iter().map(|ev| ev.remaining_time).collect(),
+            ),
+            ("velocity", events.iter().map(|ev| ev.velocity).collect()),
+            (
+                "position",
+                events.iter().map(|ev| ev.position.to_float()).collect(),
+            ),
+        ]
     }
 
-    #[test]
-    fn test_antithetic_iter() {
-        let gen = SingleVariateIterator::new(Uniform::new(0.0, 10.0), source::default(0));
+    /// Distribution of a continuous variate in the input model.
+    fn configured<'m>(model: &'m InputModel, variable: &str) -> &'m Variate {
+        match variable {
+            "inter_arrival" => &model.inter_arrival,
+            "call_duration" => &model.call_duration,
+            "velocity" => &model.vehicle_velocity,
+            _ => &model.vehicle_position,
+        }
+        .as_ref()
+        .unwrap()
+    }
 
-        for sample in gen.clone().take(10) {
-            debug_println!("{:?}", sample);
+    /// Mean of a continuous variate under the default input model.
+    fn expected_mean(variable: &str) -> f64 {
+        match variable {
+            "inter_arrival" => CALL_INTER_ARR_LAMBDA,
+            "call_duration" => CALL_DURATION_LAMBDA + CALL_DURATION_LOC,
+            "velocity" => VEHICLE_VELOCITY_MEAN,
+            _ => (VEHICLE_LOC_DIST.0 + VEHICLE_LOC_DIST.1) / 2.0,
         }
+    }
 
-        let sum = gen.clone().take(1000).sum::<f64>();
-        let avg = sum / 1000.0;
-        debug_println!("average: {}", avg);
+    fn correlation(pairs: &[(f64, f64)]) -> f64 {
+        let n = pairs.len() as f64;
+        let mean_a = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_b = pairs.iter().map(|p| p.1).sum::<f64>() / n;
 
-        let antithetic = gen.antithetic_iter(ANTITHETIC_PREPARE);
+        let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+        for (a, b) in pairs {
+            cov += (a - mean_a) * (b - mean_b);
+            var_a += (a - mean_a).powi(2);
+            var_b += (b - mean_b).powi(2);
+        }
 
-        // for uniform anthithetic pairs, the average between pairs should be around 5
-        let sum = antithetic
-            .clone()
-            .take(1000)
-            .map(|(a, b)| (a + b) / 2.0)
-            .sum::<f64>();
+        cov / (var_a * var_b).sqrt()
+    }
 
-        let avg = sum / 1000.0;
-        debug_println!("average: {}", avg);
+    impl Source for MockSource {
+        fn read_u64(&mut self) -> u64 {
+            self.0
+        }
+    }
 
-        let v = antithetic
-            .clone()
-            .take(10)
-            //     .map(|(a, b)| (a + b) / 2.0)
+    #[test]
+    fn test_antithetic_iter() {
+        let gen = SingleVariateIterator::new(Uniform::new(0.0, 10.0), source::default(0));
+        let pairs = gen
+            .antithetic_iter(ANTITHETIC_PREPARE)
+            .take(STAT_SAMPLES)
             .collect::<Vec<_>>();
 
-        debug_println!("{:#?}", v);
+        // uniform antithetic pairs mirror each other around the middle of the range
+        assert!(pairs.iter().all(|(a, b)| (a + b - 10.0).abs() < 1e-6));
+        assert!(correlation(&pairs) < -0.99);
     }
 
     /// Gaussian generation is giving problems when generating antithetic pairs
@@ -442,21 +813,209 @@ mod tests {
 
     #[test]
     fn test_call_event_gen() {
-        let generator = CallEventGenerator::new(
-            1,
-            RngSource(rand::rngs::OsRng),
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
+        let input = InputModel {
+            call_duration: Some("lognormal(4.2, 0.8)".parse().unwrap()),
+            inter_arrival: Some("gamma(2, 0.7)".parse().unwrap()),
+            ..Default::default()
+        };
+        let model = input.clone().or_default();
+
+        let events = CallEventGenerator::new(1, source::default(42), &input)
+            .take(STAT_SAMPLES)
+            .collect::<Vec<_>>();
+
+        for (variable, samples) in continuous_samples(&events) {
+            let ks = gof::kolmogorov_smirnov(&samples, configured(&model, variable));
+            assert!(ks.p_value > ALPHA, "{}: {:?}", variable, ks);
+        }
+
+        // one bin per station
+        let stations = events
+            .iter()
+            .map(|ev| ev.station as usize as f64)
+            .collect::<Vec<_>>();
+        let edges = (0..=20).map(|s| s as f64).collect::<Vec<_>>();
+        let chi = gof::chi_square(&stations, model.cell_tower.as_ref().unwrap(), &edges, 0);
+        assert!(chi.p_value > ALPHA, "station: {:?}", chi);
+
+        let west_to_east = events
+            .iter()
+            .filter(|ev| matches!(ev.direction, VehicleDirection::WestToEast))
+            .count() as f64
+            / STAT_SAMPLES as f64;
+        assert!((west_to_east - 0.5).abs() < 4.0 * (0.25 / STAT_SAMPLES as f64).sqrt());
+
+        // variates are drawn from independent streams
+        let samples = continuous_samples(&events);
+        let pairs = samples[1]
+            .1
+            .iter()
+            .copied()
+            .zip(samples[2].1.iter().copied())
+            .collect::<Vec<_>>();
+        assert!(correlation(&pairs).abs() < 4.0 / (STAT_SAMPLES as f64).sqrt());
+    }
+
+    #[test]
+    fn test_call_event_gen_seeded() {
+        let generate = |seed| {
+            CallEventGenerator::new(1, source::default(seed), &InputModel::default())
+                .take(100)
+                .map(|ev| (ev.time, ev.remaining_time, ev.velocity))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(generate(3), generate(3));
+        assert_ne!(generate(3), generate(4));
+    }
+
+    #[test]
+    fn test_tilted_call_event_gen() {
+        let tilt = Tilt {
+            arrival_rate: 1.2,
+            call_duration: 1.2,
+        };
+        let tilted = |seed| {
+            CallEventGenerator::new(1, source::default(seed), &InputModel::default())
+                .with_tilt(tilt)
+                .unwrap()
+        };
+
+        // calls arrive faster than under the input model
+        let last = tilted(1).take(STAT_SAMPLES).last().unwrap();
+        let expected = expected_mean("inter_arrival") * STAT_SAMPLES as f64;
+        assert!(last.time < expected / 1.1, "{} vs {}", last.time, expected);
+
+        // likelihood ratios of short runs average to one
+        let weights = (0..400)
+            .map(|seed| {
+                let mut gen = tilted(seed + 1);
+                gen.by_ref().take(20).for_each(drop);
+                gen.log_likelihood_ratio().exp()
+            })
+            .collect::<Vec<_>>();
+        let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+        assert!((mean - 1.0).abs() < 0.3, "mean weight {}", mean);
+    }
+
+    #[test]
+    fn test_antithetic_call_event_gen() {
+        let model = InputModel::default().or_default();
+        let (events_a, events_b): (Vec<_>, Vec<_>) =
+            CallEventGenerator::new(1, source::default(7), &InputModel::default())
+                .antithetic()
+                .take(STAT_SAMPLES)
+                .unzip();
+
+        let samples_a = continuous_samples(&events_a);
+        let samples_b = continuous_samples(&events_b);
+
+        for ((variable, a), (_, b)) in samples_a.into_iter().zip(samples_b) {
+            let dist = configured(&model, variable);
+            for samples in [&a, &b] {
+                let ks = gof::kolmogorov_smirnov(samples, dist);
+                assert!(ks.p_value > ALPHA, "{}: {:?}", variable, ks);
+            }
+
+            let pairs = a.into_iter().zip(b).collect::<Vec<_>>();
+            assert!(correlation(&pairs) < 0.0, "{} pairs", variable);
+
+            // pair averages are unbiased estimates of the mean, and exact for symmetric variates
+            let averages = pairs.iter().map(|(a, b)| (a + b) / 2.0).collect::<Vec<_>>();
+            let n = averages.len() as f64;
+            let mean = averages.iter().sum::<f64>() / n;
+            let sd = (averages.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+            let expected = expected_mean(variable);
+            assert!(
+                (mean - expected).abs() <= 4.0 * sd / n.sqrt() + 1e-9 * expected,
+                "{}: mean {} expected {}",
+                variable,
+                mean,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_input_model_override() {
+        let from_file: InputModel = toml::from_str(
+            r#"
+            call_duration = "lognormal(4.2, 0.8)"
+            inter_arrival = "exponential(2)"
+            "#,
+        )
+        .unwrap();
+
+        let model = from_file.overridden_by(InputModel {
+            inter_arrival: Some("weibull(1.2, 1.5)".parse().unwrap()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            model.call_duration.as_ref().unwrap().to_string(),
+            "lognormal(4.2, 0.8)"
+        );
+        assert_eq!(
+            model.inter_arrival.as_ref().unwrap().to_string(),
+            "weibull(1.2, 1.5)"
         );
+        assert!(model.vehicle_velocity.is_none());
 
-        let num_events = 100000;
-        println!("generating {} events", num_events);
-        let call_events = generator.antithetic().take(num_events).collect::<Vec<_>>();
-        println!("{} events generated", num_events);
+        let model = model.or_default();
+        assert!(matches!(
+            model.vehicle_velocity,
+            Some(Variate::Truncated(_))
+        ));
+
+        assert!(toml::from_str::<InputModel>(r#"call_duration = "lognormal(4.2)""#).is_err());
+        assert!(toml::from_str::<InputModel>(r#"duration = "exponential(1)""#).is_err());
+    }
+
+    #[test]
+    fn test_input_model_support() {
+        let check = |input: InputModel| input.check_support();
+
+        assert!(check(InputModel::default()).is_ok());
+        assert!(check(InputModel::default().or_default()).is_ok());
+        assert!(check(InputModel {
+            call_duration: Some("truncated(normal(50, 100), 1, 500)".parse().unwrap()),
+            inter_arrival: Some("exponential(2)".parse().unwrap()),
+            vehicle_velocity: Some("clamped(normal(20, 60), 1, 250)".parse().unwrap()),
+            vehicle_position: Some("uniform(500, 1500)".parse().unwrap()),
+            ..Default::default()
+        })
+        .is_ok());
+
+        let duration = check(InputModel {
+            call_duration: Some("normal(50, 100)".parse().unwrap()),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(duration.contains("call_duration must be non-negative"));
+        assert!(duration.contains("truncated("));
+        assert!(check(InputModel {
+            inter_arrival: Some("exponential(2, -1)".parse().unwrap()),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .contains("inter_arrival"));
+        assert!(check(InputModel {
+            vehicle_velocity: Some("normal(20, 60)".parse().unwrap()),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .contains("vehicle_velocity must be positive"));
+        assert!(check(InputModel {
+            vehicle_velocity: Some("lognormal(4.8, 0.1)".parse().unwrap()),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(check(InputModel {
+            vehicle_position: Some("uniform(0, 3000)".parse().unwrap()),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .contains("vehicle_position must be within [0, 2000]"));
     }
 
     #[test]
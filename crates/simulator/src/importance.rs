@@ -0,0 +1,199 @@
+//! Importance sampling of blocked and dropped calls, for loads at which they are rare.
+//!
+//! Runs draw more frequent and longer calls than the input model, and every blocked or dropped
+//! call is weighted by the likelihood ratio of the call initiations it can depend on, so the
+//! weighted mean is an unbiased estimate under the input model.
+//!
+//! The variance of the log likelihood ratio of a run grows with the number of calls times the
+//! squared deviation of the tilting factors from one, so longer runs need milder tilts, and the
+//! effective sample size shows how many runs the estimate really rests on.
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+use simulator_core::{
+    importance::{weighted_estimate, WeightedEstimate},
+    stats::Summary,
+};
+
+use crate::{
+    experiment::{run_source, Scenario},
+    generator::Tilt,
+};
+
+/// Performance measure of a tilted run.
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportanceRow {
+    pub run: usize,
+    pub blocked_calls: f64,
+    pub dropped_calls: f64,
+
+    /// Blocked calls weighted by their likelihood ratios
+    pub weighted_blocked_calls: f64,
+
+    /// Dropped calls weighted by their likelihood ratios
+    pub weighted_dropped_calls: f64,
+
+    pub log_likelihood_ratio: f64,
+}
+
+/// Weighted estimates over tilted runs.
+#[derive(Clone, Debug)]
+pub struct ImportanceReport {
+    pub runs: Vec<ImportanceRow>,
+    pub blocked: WeightedEstimate,
+    pub dropped: WeightedEstimate,
+
+    /// Blocked and dropped calls over untilted runs on the same streams, for comparison
+    pub naive: Option<(Summary, Summary)>,
+}
+
+/// Simulate runs `0..num_runs` of `scenario` under `tilt` and weight their performance
+/// measures, optionally along with untilted runs.
+pub fn importance_runs(
+    scenario: &Scenario,
+    tilt: Tilt,
+    num_runs: usize,
+    seed: u64,
+    naive: bool,
+    confidence: f64,
+) -> Result<ImportanceReport, String> {
+    let runs = (0..num_runs)
+        .into_par_iter()
+        .map(|run| {
+            let tilted = scenario.simulate_tilted(run, run_source(seed, run), tilt)?;
+            Ok(ImportanceRow {
+                run: run + 1,
+                blocked_calls: tilted.perf.blocked_calls as f64,
+                dropped_calls: tilted.perf.dropped_calls as f64,
+                weighted_blocked_calls: tilted.weighted.blocked_calls as f64,
+                weighted_dropped_calls: tilted.weighted.dropped_calls as f64,
+                log_likelihood_ratio: tilted.log_likelihood_ratio,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let column = |value: fn(&ImportanceRow) -> f64| runs.iter().map(value).collect::<Vec<_>>();
+    let log_ratios = column(|row| row.log_likelihood_ratio);
+
+    let naive = naive.then(|| {
+        let perf = scenario.simulate_runs(num_runs, seed);
+        let blocked = perf
+            .iter()
+            .map(|p| p.blocked_calls as f64)
+            .collect::<Vec<_>>();
+        let dropped = perf
+            .iter()
+            .map(|p| p.dropped_calls as f64)
+            .collect::<Vec<_>>();
+        (
+            Summary::new(&blocked, confidence),
+            Summary::new(&dropped, confidence),
+        )
+    });
+
+    Ok(ImportanceReport {
+        blocked: weighted_estimate(
+            &column(|row| row.weighted_blocked_calls),
+            &column(|row| row.blocked_calls),
+            &log_ratios,
+            confidence,
+        )?,
+        dropped: weighted_estimate(
+            &column(|row| row.weighted_dropped_calls),
+            &column(|row| row.dropped_calls),
+            &log_ratios,
+            confidence,
+        )?,
+        runs,
+        naive,
+    })
+}
+
+/// Print the weighted estimates, next to untilted ones if they were simulated.
+pub fn print_report(report: &ImportanceReport) {
+    let measures = [
+        (
+            "blocked",
+            &report.blocked,
+            report.naive.as_ref().map(|n| &n.0),
+        ),
+        (
+            "dropped",
+            &report.dropped,
+            report.naive.as_ref().map(|n| &n.1),
+        ),
+    ];
+
+    println!(
+        "{:>8} {:>24} {:>14} {:>24} {:>10}",
+        "measure", "weighted", "self-normed", "naive", "efficiency"
+    );
+    for (name, estimate, naive) in measures {
+        let (naive, efficiency) = match naive {
+            Some(naive) => (
+                format!("{:.4e} ± {:.2e}", naive.mean, naive.half_width),
+                format!("{:.2}", (naive.half_width / estimate.half_width).powi(2)),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+
+        println!(
+            "{:>8} {:>24} {:>14.4e} {:>24} {:>10}",
+            name,
+            format!("{:.4e} ± {:.2e}", estimate.mean, estimate.half_width),
+            estimate.self_normalized,
+            naive,
+            efficiency
+        );
+    }
+
+    println!(
+        "effective sample size: {:.1} of {} runs, mean weight: {:.3}",
+        report.blocked.effective_sample_size,
+        report.runs.len(),
+        report.blocked.mean_weight
+    );
+    if report.blocked.effective_sample_size < report.runs.len() as f64 / 10.0 {
+        println!("warning: few runs carry most of the weight, try a milder tilt or shorter runs");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::InputModel;
+
+    #[test]
+    fn test_untilted_runs() {
+        // without a tilt, the weighted estimate is the naive one
+        let scenario = Scenario::new(InputModel::default(), 1, 300, 0);
+        let tilt = Tilt {
+            arrival_rate: 1.0,
+            call_duration: 1.0,
+        };
+
+        let report = importance_runs(&scenario, tilt, 4, 3, true, 0.95).unwrap();
+        let (blocked, dropped) = report.naive.unwrap();
+        assert!((report.blocked.mean - blocked.mean).abs() < 1e-12);
+        assert!((report.dropped.mean - dropped.mean).abs() < 1e-12);
+        assert!(report
+            .runs
+            .iter()
+            .all(|row| row.log_likelihood_ratio == 0.0));
+    }
+
+    #[test]
+    fn test_tilt_needs_exponential_inputs() {
+        let model = InputModel {
+            inter_arrival: Some("uniform(0, 2)".parse().unwrap()),
+            ..Default::default()
+        };
+        let tilt = Tilt {
+            arrival_rate: 1.5,
+            call_duration: 1.0,
+        };
+
+        let scenario = Scenario::new(model, 1, 10, 0);
+        assert!(importance_runs(&scenario, tilt, 2, 1, false, 0.95).is_err());
+    }
+}
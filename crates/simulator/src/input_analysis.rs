@@ -0,0 +1,398 @@
+//! Goodness-of-fit analysis of input distributions.
+//!
+//! Every input column of the course's test data is fitted to the candidate distribution families
+//! and tested with chi-square, Kolmogorov-Smirnov and Anderson-Darling tests. Generated call
+//! initiations are tested against the configured input model in the same way.
+
+use std::{io, path::Path};
+
+use serde::Serialize;
+use simulator_core::{
+    fit,
+    gof::{self, TestResult},
+    read_csv_column, Variate,
+};
+
+use crate::{event::CellEvent, generator::InputModel, FloatingPoint};
+
+/// Header of the arrival time column in the test data
+pub const ARRIVAL_TIME_COLUMN: &str = "Arrival time (sec)";
+
+/// Header of the base station column in the test data
+pub const STATION_COLUMN: &str = "Base station";
+
+/// Header of the call duration column in the test data
+pub const CALL_DURATION_COLUMN: &str = "Call duration (sec)";
+
+/// Header of the velocity column in the test data
+pub const VELOCITY_COLUMN: &str = "velocity (km/h)";
+
+/// Minimum expected count per chi-square bin.
+const MIN_EXPECTED_PER_BIN: usize = 5;
+
+/// Where the tested samples came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleSource {
+    /// Test data, against distributions fitted to it
+    Data,
+
+    /// Call event generator output, against the input model
+    Generated,
+}
+
+/// A test of one variable against one distribution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GofTest {
+    ChiSquare,
+    KolmogorovSmirnov,
+    AndersonDarling,
+}
+
+/// A row of the input analysis report.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportRow {
+    pub source: SampleSource,
+    pub variable: &'static str,
+    pub num_samples: usize,
+    pub distribution: String,
+    pub log_likelihood: Option<FloatingPoint>,
+    pub test: GofTest,
+    pub statistic: FloatingPoint,
+    pub dof: Option<usize>,
+    pub p_value: FloatingPoint,
+}
+
+/// Samples of each input variable.
+#[derive(Clone, Debug, Default)]
+pub struct InputSamples {
+    pub inter_arrival: Vec<FloatingPoint>,
+
+    /// Zero-based station indices
+    pub station: Vec<FloatingPoint>,
+
+    pub call_duration: Vec<FloatingPoint>,
+    pub velocity: Vec<FloatingPoint>,
+}
+
+impl InputSamples {
+    /// Read input samples from the course's test data.
+    pub fn from_data<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let column = |name: &str| {
+            read_csv_column(&path, name).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+
+        Ok(Self {
+            inter_arrival: inter_arrivals(column(ARRIVAL_TIME_COLUMN)?),
+            station: column(STATION_COLUMN)?
+                .into_iter()
+                .map(|s| s - 1.0)
+                .collect(),
+            call_duration: column(CALL_DURATION_COLUMN)?,
+            velocity: column(VELOCITY_COLUMN)?,
+        })
+    }
+
+    /// Collect input samples from generated call initiations.
+    pub fn from_events(events: &[CellEvent]) -> Self {
+        Self {
+            inter_arrival: inter_arrivals(events.iter().map(|ev| ev.time).collect()),
+            station: events
+                .iter()
+                .map(|ev| ev.station as usize as FloatingPoint)
+                .collect(),
+            call_duration: events.iter().map(|ev| ev.remaining_time).collect(),
+            velocity: events.iter().map(|ev| ev.velocity).collect(),
+        }
+    }
+}
+
+/// Fit every candidate family to the test data and test the fits.
+///
+/// Stations are tested against the input model's cell tower distribution with one bin per
+/// station, as they are discrete.
+pub fn analyze_data(samples: &InputSamples, model: &InputModel, bins: usize) -> Vec<ReportRow> {
+    let model = model.clone().or_default();
+    let mut rows = Vec::new();
+
+    for (variable, data) in [
+        ("inter_arrival", &samples.inter_arrival),
+        ("call_duration", &samples.call_duration),
+        ("velocity", &samples.velocity),
+    ] {
+        for fit in fit::fit_all(data) {
+            rows.extend(test_continuous(
+                SampleSource::Data,
+                variable,
+                data,
+                &fit.variate,
+                fit.log_likelihood,
+                fit.num_params,
+                bins,
+            ));
+        }
+    }
+
+    rows.push(test_station(
+        SampleSource::Data,
+        &samples.station,
+        model.cell_tower.as_ref().unwrap(),
+    ));
+
+    rows
+}
+
+/// Test generated input samples against the input model they were generated from.
+pub fn analyze_generated(
+    samples: &InputSamples,
+    model: &InputModel,
+    bins: usize,
+) -> Vec<ReportRow> {
+    let model = model.clone().or_default();
+    let mut rows = Vec::new();
+
+    for (variable, data, variate) in [
+        (
+            "inter_arrival",
+            &samples.inter_arrival,
+            &model.inter_arrival,
+        ),
+        (
+            "call_duration",
+            &samples.call_duration,
+            &model.call_duration,
+        ),
+        ("velocity", &samples.velocity, &model.vehicle_velocity),
+    ] {
+        rows.extend(test_continuous(
+            SampleSource::Generated,
+            variable,
+            data,
+            variate.as_ref().unwrap(),
+            None,
+            0,
+            bins,
+        ));
+    }
+
+    rows.push(test_station(
+        SampleSource::Generated,
+        &samples.station,
+        model.cell_tower.as_ref().unwrap(),
+    ));
+
+    rows
+}
+
+/// Write the report as csv.
+pub fn write_report<P: AsRef<Path>>(path: P, rows: &[ReportRow]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
+
+/// Print the p-values of each tested distribution, one line per distribution.
+pub fn print_summary(rows: &[ReportRow]) {
+    println!(
+        "{:<9} {:<13} {:<52} {:>9} {:>9} {:>9}",
+        "source", "variable", "distribution", "chi2 p", "ks p", "ad p"
+    );
+
+    for chunk in rows.chunk_by(|a, b| {
+        a.source == b.source && a.variable == b.variable && a.distribution == b.distribution
+    }) {
+        let p = |test: GofTest| {
+            chunk
+                .iter()
+                .find(|row| row.test == test)
+                .map(|row| format!("{:.4}", row.p_value))
+                .unwrap_or_else(|| "-".to_string())
+        };
+
+        let first = &chunk[0];
+        let source = match first.source {
+            SampleSource::Data => "data",
+            SampleSource::Generated => "generated",
+        };
+
+        println!(
+            "{:<9} {:<13} {:<52} {:>9} {:>9} {:>9}",
+            source,
+            first.variable,
+            first.distribution,
+            p(GofTest::ChiSquare),
+            p(GofTest::KolmogorovSmirnov),
+            p(GofTest::AndersonDarling)
+        );
+    }
+}
+
+/// Chi-square, Kolmogorov-Smirnov and Anderson-Darling tests of continuous samples.
+///
+/// `num_fitted` parameters of the distribution were estimated from the samples.
+#[allow(clippy::too_many_arguments)]
+fn test_continuous(
+    source: SampleSource,
+    variable: &'static str,
+    data: &[FloatingPoint],
+    variate: &Variate,
+    log_likelihood: Option<FloatingPoint>,
+    num_fitted: usize,
+    bins: usize,
+) -> Vec<ReportRow> {
+    let bins = bins.min(data.len() / MIN_EXPECTED_PER_BIN).max(2);
+    let edges = gof::equiprobable_edges(variate, bins);
+
+    [
+        (
+            GofTest::ChiSquare,
+            gof::chi_square(data, variate, &edges, num_fitted),
+        ),
+        (
+            GofTest::KolmogorovSmirnov,
+            gof::kolmogorov_smirnov(data, variate),
+        ),
+        (
+            GofTest::AndersonDarling,
+            gof::anderson_darling(data, variate),
+        ),
+    ]
+    .into_iter()
+    .map(|(test, result)| {
+        report_row(
+            source,
+            variable,
+            data.len(),
+            variate,
+            log_likelihood,
+            test,
+            result,
+        )
+    })
+    .collect()
+}
+
+/// Chi-square test of zero-based station indices, with one bin per station.
+fn test_station(source: SampleSource, stations: &[FloatingPoint], variate: &Variate) -> ReportRow {
+    let edges = (0..=20).map(|s| s as FloatingPoint).collect::<Vec<_>>();
+    let result = gof::chi_square(stations, variate, &edges, 0);
+
+    report_row(
+        source,
+        "station",
+        stations.len(),
+        variate,
+        None,
+        GofTest::ChiSquare,
+        result,
+    )
+}
+
+fn report_row(
+    source: SampleSource,
+    variable: &'static str,
+    num_samples: usize,
+    variate: &Variate,
+    log_likelihood: Option<FloatingPoint>,
+    test: GofTest,
+    result: TestResult,
+) -> ReportRow {
+    ReportRow {
+        source,
+        variable,
+        num_samples,
+        distribution: variate.to_string(),
+        log_likelihood,
+        test,
+        statistic: result.statistic,
+        dof: result.dof,
+        p_value: result.p_value,
+    }
+}
+
+/// Inter-arrival times of arrival times, in order of arrival.
+fn inter_arrivals(mut times: Vec<FloatingPoint>) -> Vec<FloatingPoint> {
+    times.sort_by(FloatingPoint::total_cmp);
+    times.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use super::*;
+    use crate::generator::CallEventGenerator;
+
+    #[test]
+    fn test_analyze_data() {
+        let path = std::env::temp_dir().join(format!(
+            "simulator_test_analyze_data_{}.csv",
+            std::process::id()
+        ));
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(
+            file,
+            "Arrival no,Arrival time (sec),Base station ,Call duration (sec),velocity (km/h)"
+        )
+        .unwrap();
+
+        let events =
+            CallEventGenerator::new(1, probability::source::default(5), &InputModel::default())
+                .take(500)
+                .collect::<Vec<_>>();
+        for ev in &events {
+            writeln!(
+                file,
+                "{},{},{},{},{}",
+                ev.idx,
+                ev.time,
+                ev.station as usize + 1,
+                ev.remaining_time,
+                ev.velocity
+            )
+            .unwrap();
+        }
+        drop(file);
+
+        let samples = InputSamples::from_data(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(samples.inter_arrival.len(), 499);
+        assert_eq!(samples.station.len(), 500);
+        assert!(samples.station.iter().all(|s| (0.0..20.0).contains(s)));
+
+        let rows = analyze_data(&samples, &InputModel::default(), 20);
+        // three tests per fitted family of each continuous variable, one for stations
+        let families = ["inter_arrival", "call_duration", "velocity"]
+            .iter()
+            .map(|v| rows.iter().filter(|r| r.variable == *v).count())
+            .collect::<Vec<_>>();
+        assert!(families.iter().all(|n| *n >= 9 && n % 3 == 0));
+        assert_eq!(rows.last().unwrap().variable, "station");
+        assert!(rows
+            .iter()
+            .all(|r| (0.0..=1.0).contains(&r.p_value) && r.statistic.is_finite()));
+
+        let chi = rows.iter().find(|r| r.test == GofTest::ChiSquare).unwrap();
+        assert_eq!(chi.dof, Some(20 - 1 - 2));
+
+        let generated = analyze_generated(
+            &InputSamples::from_events(&events),
+            &InputModel::default(),
+            20,
+        );
+        assert_eq!(generated.len(), 3 * 3 + 1);
+        assert!(generated
+            .iter()
+            .all(|r| r.source == SampleSource::Generated));
+    }
+
+    #[test]
+    fn test_inter_arrivals() {
+        assert_eq!(inter_arrivals(vec![0.0, 3.0, 1.0]), vec![1.0, 2.0]);
+        assert!(inter_arrivals(vec![1.0]).is_empty());
+    }
+}
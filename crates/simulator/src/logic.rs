@@ -5,13 +5,14 @@ use std::{collections::VecDeque, fmt::Display};
 use simulator_core::EventLike;
 
 use crate::{
-    base_station::{BaseStation, StationRequest, StationResponse},
+    base_station::{BaseStation, StationRequest, StationResponse, NUM_STATIONS},
     debug_println,
     event::{
         CellEvent, CellEventResult, CellEventType, PerfMeasure, RelativeVehiclePosition,
         VehicleDirection,
     },
     generator::{calculate_ttn, VEHICLE_LOC_DIST},
+    ramp::{boundary, OffHighway, OffHighwayStats, Ramp},
     FloatingPoint,
 };
 
@@ -28,7 +29,13 @@ pub struct EventProcessor {
 #[derive(Clone, Debug, Default)]
 pub struct Shared {
     /// Base stations in the simulation
-    base_stations: [BaseStation; 20],
+    base_stations: [BaseStation; NUM_STATIONS],
+
+    /// Ramps along the highway
+    ramps: Vec<Ramp>,
+
+    /// Calls that left the highway through an off-ramp
+    pub off_highway: OffHighwayStats,
 }
 
 impl EventLike for EventProcessor {
@@ -61,13 +68,22 @@ impl EventLike for EventProcessor {
             CellEventType::Initiate => self.process_call_initiation(next_event, shared),
             CellEventType::Terminate => self.process_call_terminate(next_event, shared),
             CellEventType::Handover => self.process_call_handover(next_event, shared),
+            CellEventType::Exit => self.process_call_exit(next_event, shared),
+            CellEventType::Enter => self.process_call_entry(next_event, shared),
         };
 
         Some(results)
     }
 
     fn calculate_performance_measure(results: &[Self::EventStats]) -> Self::PerformanceMeasure {
-        let num_initiated_calls = results.iter().map(|res| res.idx).max().unwrap_or_default();
+        // calls entering at a ramp were initiated off the highway, but can still be dropped
+        let num_calls = results.iter().map(|res| res.idx).max().unwrap_or_default();
+        let num_initiated_calls = results
+            .iter()
+            .filter(|res| matches!(res.ty, CellEventType::Initiate))
+            .map(|res| res.idx)
+            .max()
+            .unwrap_or_default();
 
         let num_blocked_calls: usize = results
             .iter()
@@ -88,10 +104,19 @@ impl EventLike for EventProcessor {
         PerfMeasure {
             blocked_calls: num_blocked_calls as FloatingPoint
                 / num_initiated_calls as FloatingPoint,
-            dropped_calls: num_terminated_calls as FloatingPoint
-                / num_initiated_calls as FloatingPoint,
+            dropped_calls: num_terminated_calls as FloatingPoint / num_calls as FloatingPoint,
         }
     }
+
+    /// The highway regenerates whenever every station is idle. Every call in progress holds a
+    /// channel, so the FEL then holds only call initiations and ramp entries, which are
+    /// independent of the past only when inter-arrival times are memoryless.
+    fn is_regeneration_point(&self, shared: &Self::SharedResources) -> bool {
+        shared
+            .base_stations
+            .iter()
+            .all(|s| s.available_channels == s.channels)
+    }
 }
 
 impl Display for Shared {
@@ -109,13 +134,33 @@ impl Display for Shared {
 }
 
 impl Shared {
-    pub fn new(handover_reserve: usize) -> Self {
+    /// Create shared resources with the given number of channels in every station.
+    pub fn with_channels(channels: usize, handover_reserve: usize) -> Self {
+        Self {
+            base_stations: {
+                core::array::from_fn(|_idx| BaseStation::new(channels, Some(handover_reserve)))
+            },
+            ramps: Vec::new(),
+            off_highway: OffHighwayStats::default(),
+        }
+    }
+
+    /// Create shared resources with per-station reserved handover channels, west to east.
+    pub fn with_reserves(channels: usize, handover_reserves: &[usize; NUM_STATIONS]) -> Self {
         Self {
             base_stations: {
-                core::array::from_fn(|_idx| BaseStation::new(10, Some(handover_reserve)))
+                core::array::from_fn(|idx| BaseStation::new(channels, Some(handover_reserves[idx])))
             },
+            ramps: Vec::new(),
+            off_highway: OffHighwayStats::default(),
         }
     }
+
+    /// Add ramps along the highway.
+    pub fn with_ramps(mut self, ramps: Vec<Ramp>) -> Self {
+        self.ramps = ramps;
+        self
+    }
 }
 
 impl EventProcessor {
@@ -132,7 +177,7 @@ impl EventProcessor {
     }
 
     /// Should not be needed
-    #[cfg(notset)]
+    #[cfg(any())]
     fn sort_inner(&mut self) {
         self.fel.rotate_right(self.fel.as_slices().1.len());
         assert!(self.fel.as_slices().1.is_empty());
@@ -158,6 +203,28 @@ impl EventProcessor {
                 let remaining_call_time = event.remaining_time - tt_next;
 
                 let next_ev = match event.station.next_station(event.direction) {
+                    // vehicle leaves the highway before the next station, enqueue exit event
+                    Some(next_station)
+                        if event.exit == Some(boundary(event.station, next_station)) =>
+                    {
+                        CellEvent {
+                            idx: event.idx,
+                            run: event.run,
+                            time: event.time + tt_next,
+                            ty: CellEventType::Exit,
+                            remaining_time: remaining_call_time,
+                            ttn: None,
+                            velocity: event.velocity,
+                            direction: event.direction,
+                            station: event.station,
+                            position: match event.direction {
+                                VehicleDirection::EastToWest => RelativeVehiclePosition::WestEnd,
+                                VehicleDirection::WestToEast => RelativeVehiclePosition::EastEnd,
+                            },
+                            exit: event.exit,
+                        }
+                    }
+
                     // next station exists, enqueue handover event
                     Some(next_station) => CellEvent {
                         idx: event.idx,
@@ -180,6 +247,7 @@ impl EventProcessor {
                             VehicleDirection::EastToWest => RelativeVehiclePosition::EastEnd,
                             VehicleDirection::WestToEast => RelativeVehiclePosition::WestEnd,
                         },
+                        exit: event.exit,
                     },
                     // next station does not exist, enqueue terminate event
                     None => CellEvent {
@@ -196,6 +264,7 @@ impl EventProcessor {
                             VehicleDirection::EastToWest => RelativeVehiclePosition::WestEnd,
                             VehicleDirection::WestToEast => RelativeVehiclePosition::EastEnd,
                         },
+                        exit: event.exit,
                     },
                 };
 
@@ -238,6 +307,7 @@ impl EventProcessor {
 
                         pos
                     },
+                    exit: event.exit,
                 };
 
                 // TODO: handle event immediately if future event occurrs at the same time
@@ -294,6 +364,33 @@ impl EventProcessor {
         vec![result]
     }
 
+    fn process_call_exit(&mut self, event: CellEvent, shared: &mut Shared) -> Vec<CellEventResult> {
+        assert!(matches!(event.ty, CellEventType::Exit));
+
+        let station = &mut shared.base_stations[event.station as usize];
+
+        let res = station.process_request(StationRequest::HandoverDisconnect, event.idx);
+        assert!(matches!(res, StationResponse::Success));
+
+        let result = event.to_result(res, station.available_channels);
+
+        let ramp = shared
+            .ramps
+            .iter()
+            .find(|r| Some(r.after) == event.exit)
+            .expect("exiting vehicle must leave through a configured ramp");
+
+        match ramp.off_highway {
+            OffHighway::Release => shared.off_highway.released += 1,
+            OffHighway::Sink => {
+                shared.off_highway.sink_calls += 1;
+                shared.off_highway.sink_call_time += event.remaining_time;
+            }
+        }
+
+        vec![result]
+    }
+
     fn process_call_handover(
         &mut self,
         event: CellEvent,
@@ -326,23 +423,48 @@ impl EventProcessor {
 
         results
     }
+
+    /// A call entering at an on-ramp is handed over from off the highway, so there is no
+    /// station to disconnect from.
+    fn process_call_entry(
+        &mut self,
+        event: CellEvent,
+        shared: &mut Shared,
+    ) -> Vec<CellEventResult> {
+        assert!(matches!(event.ty, CellEventType::Enter));
+
+        let station = &mut shared.base_stations[event.station as usize];
+
+        let res = station.process_request(StationRequest::HandoverConnect, event.idx);
+
+        let mut results = vec![event.to_result(res, station.available_channels)];
+
+        if let StationResponse::Terminated = res {
+            return results;
+        }
+
+        let additional_res = self.handle_handover_terminate(event);
+        results.extend(additional_res);
+
+        results
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{event::BaseStationIdx, FloatingPoint};
+    use crate::{base_station::NUM_CHANNELS, event::BaseStationIdx, FloatingPoint};
 
     use super::*;
 
     #[test]
     fn test_shared_display() {
-        let shared = Shared::new(1);
+        let shared = Shared::with_channels(NUM_CHANNELS, 1);
         debug_println!("{}", shared);
     }
 
     #[test]
     fn test_insert_into_fel() {
-        let events = (0..10).into_iter().map(|idx| CellEvent {
+        let events = (0..10).map(|idx| CellEvent {
             idx,
             run: 0,
             time: idx as FloatingPoint,
@@ -353,6 +475,7 @@ mod tests {
             direction: VehicleDirection::EastToWest,
             station: BaseStationIdx::One,
             position: RelativeVehiclePosition::EastEnd,
+            exit: None,
         });
 
         let mut proc = EventProcessor::new(1, vec![]);
@@ -363,4 +486,96 @@ mod tests {
         debug_println!("fel len: {}", proc.fel.len());
         debug_println!("{:#?}", proc.fel);
     }
+
+    /// A vehicle leaving at an off-ramp frees its channel and is handed to the sink
+    #[test]
+    fn test_exit_at_ramp() {
+        let ramps = vec!["2:1.0:0:sink".parse::<Ramp>().unwrap()];
+        let mut shared = Shared::with_channels(NUM_CHANNELS, 0).with_ramps(ramps);
+
+        let call = CellEvent {
+            idx: 1,
+            run: 0,
+            time: 0.0,
+            ty: CellEventType::Initiate,
+            remaining_time: 250.0,
+            ttn: calculate_ttn(250.0, 0.0, 72.0, VehicleDirection::WestToEast),
+            velocity: 72.0,
+            direction: VehicleDirection::WestToEast,
+            station: BaseStationIdx::One,
+            position: RelativeVehiclePosition::Other(0.0),
+            exit: Some(BaseStationIdx::Two),
+        };
+
+        let mut proc = EventProcessor::new(1, vec![call]);
+        let mut results = vec![];
+        while let Some(res) = proc.step(&mut shared) {
+            results.extend(res);
+        }
+
+        let types = results.iter().map(|res| res.ty).collect::<Vec<_>>();
+        assert!(matches!(
+            types.as_slice(),
+            [
+                CellEventType::Initiate,
+                CellEventType::Handover,
+                CellEventType::Exit
+            ]
+        ));
+
+        assert!(results
+            .iter()
+            .all(|res| matches!(res.outcome, StationResponse::Success)));
+        assert!(shared
+            .base_stations
+            .iter()
+            .all(|s| s.active_users.is_empty()));
+        assert_eq!(shared.off_highway.sink_calls, 1);
+        assert_eq!(shared.off_highway.released, 0);
+        assert_eq!(shared.off_highway.sink_call_time, 250.0 - 100.0 - 100.0);
+    }
+
+    #[test]
+    fn test_entry_dropped_when_station_full() {
+        let mut shared = Shared::with_channels(1, 0);
+
+        let call = CellEvent {
+            idx: 1,
+            run: 0,
+            time: 0.0,
+            ty: CellEventType::Initiate,
+            remaining_time: 10.0,
+            ttn: calculate_ttn(10.0, 1000.0, 72.0, VehicleDirection::WestToEast),
+            velocity: 72.0,
+            direction: VehicleDirection::WestToEast,
+            station: BaseStationIdx::One,
+            position: RelativeVehiclePosition::Other(1000.0),
+            exit: None,
+        };
+        let entry = CellEvent {
+            idx: 2,
+            time: 1.0,
+            ty: CellEventType::Enter,
+            ttn: calculate_ttn(10.0, 0.0, 72.0, VehicleDirection::WestToEast),
+            position: RelativeVehiclePosition::WestEnd,
+            ..call
+        };
+
+        let mut proc = EventProcessor::new(1, vec![call, entry]);
+        let mut results = vec![];
+        while let Some(res) = proc.step(&mut shared) {
+            results.extend(res);
+        }
+
+        let entry_res = results
+            .iter()
+            .find(|res| matches!(res.ty, CellEventType::Enter))
+            .unwrap();
+        assert!(matches!(entry_res.outcome, StationResponse::Terminated));
+
+        // the refused entry is a dropped call, and does not count as an initiated call
+        let perf = EventProcessor::calculate_performance_measure(&results);
+        assert_eq!(perf.blocked_calls, 0.0);
+        assert_eq!(perf.dropped_calls, 0.5);
+    }
 }
@@ -1,26 +1,62 @@
 // #![allow(unused)]
+// `FloatingPoint` may be switched to `f32`, so casts between it and `f64` are kept.
+#![allow(clippy::unnecessary_cast)]
 
+mod analytic;
+mod antithetic;
 mod args;
 mod base_station;
+mod columnar;
+mod compare;
+mod config;
+mod database;
 mod event;
+mod experiment;
 mod generator;
+mod importance;
+mod input_analysis;
 mod logic;
+mod manifest;
+mod optimize;
+mod perf_stats;
+mod ramp;
+mod regenerative;
+mod replay;
+mod sampling;
+mod sweep;
 
 use clap::Parser;
-use event::PerfMeasure;
-use logic::{EventProcessor, Shared};
+use event::{CellEvent, CellEventResult, PerfMeasure};
+use logic::EventProcessor;
 use probability::prelude::*;
-use probability::source::Source;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use simulator_core::EventRunner;
+use simulator_core::{
+    output::Compression, qmc::Sequence, stats::DEFAULT_CONFIDENCE, EventRunner, SplitSource,
+};
 use std::{
     io,
-    path::PathBuf,
-    sync::{mpsc, Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
+    time::Instant,
 };
 
-use crate::generator::CallEventGenerator;
+use crate::{
+    antithetic::AntitheticPair,
+    args::{AnalyzeCommand, Command},
+    columnar::{ColumnarWriter, Format},
+    config::ScenarioConfig,
+    database::Database,
+    event::PerfRecord,
+    experiment::InputMeans,
+    generator::{CallEventGenerator, InputModel, Tilt},
+    input_analysis::InputSamples,
+    manifest::Manifest,
+    ramp::OffHighwayStats,
+};
 
 /// Common float type for the simulator
 type FloatingPoint = f64;
@@ -35,7 +71,14 @@ impl<T: rand::RngCore> source::Source for RngSource<T> {
     }
 }
 
+impl<T: rand::RngCore + rand::SeedableRng + Clone> SplitSource for RngSource<T> {
+    fn split(&mut self) -> Self {
+        RngSource(T::from_rng(&mut self.0).expect("seeding from a generator does not fail"))
+    }
+}
+
 /// A deterministic source used for testing
+#[allow(dead_code)]
 #[derive(Clone, Debug)]
 struct DetermnisticSource(u64);
 
@@ -61,12 +104,95 @@ impl rand::RngCore for DetermnisticSource {
 }
 
 fn main() -> io::Result<()> {
-    let args = args::CliArgs::parse();
+    match &args::CliArgs::parse().command {
+        Command::Run(run) => simulate(&run.config()?),
+        Command::Generate(generate) => {
+            let config = generate.config()?;
+            with_manifest(&config, &[&generate.output], |seed| {
+                let generator =
+                    CallEventGenerator::new(1, experiment::run_source(seed, 0), &config.inputs)
+                        .with_ramps(config.topology.ramps.clone());
+                generate_num_to_file(generator, generate.count, &generate.output)
+            })
+        }
+        Command::Replay(replay) => replay_trace(&replay.config()?, replay),
+        Command::Sweep(sweep) => {
+            let config = sweep.config()?;
+            with_manifest(&config, &[&sweep.output], |seed| {
+                run_sweep(&config, sweep, seed)
+            })
+        }
+        Command::Compare(compare) => {
+            let config = compare.config()?;
+            with_manifest(&config, &[&compare.output], |seed| {
+                run_compare(&config, compare, seed)
+            })
+        }
+        Command::Optimize(optimize) => {
+            let config = optimize.config()?;
+            with_manifest(&config, &[&optimize.output], |seed| {
+                run_optimize(&config, optimize, seed)
+            })
+        }
+        Command::SamplingReport(report) => {
+            let config = report.config()?;
+            with_manifest(&config, &[&report.output], |seed| {
+                run_sampling_report(&config, report, seed)
+            })
+        }
+        Command::Importance(importance) => {
+            let config = importance.config()?;
+            with_manifest(&config, &[&importance.output], |seed| {
+                run_importance(&config, importance, seed)
+            })
+        }
+        Command::Regenerative(regenerative) => {
+            let config = regenerative.config()?;
+            with_manifest(&config, &[&regenerative.output], |seed| {
+                run_regenerative(&config, regenerative, seed)
+            })
+        }
+        Command::Analyze(analyze) => match &analyze.command {
+            AnalyzeCommand::Input(input) => {
+                let config = input.config()?;
+                with_manifest(&config, &[&input.report], |seed| {
+                    analyze_input(input, &config.inputs, seed)
+                })
+            }
+            AnalyzeCommand::Analytic(analytic) => {
+                let config = analytic.config()?;
+                with_manifest(&config, &[], |_| {
+                    let simulated = match &analytic.compare {
+                        Some(path) => Some(analytic::read_perf_measures(path)?),
+                        None => None,
+                    };
+
+                    analytic::HighwayModel::new(
+                        &config.inputs,
+                        config.topology.channels,
+                        config.admission.reserved_handover_channels,
+                    )
+                    .solve()
+                    .print(simulated.as_deref());
+                    Ok(())
+                })
+            }
+            AnalyzeCommand::PerfStats(perf_stats) => {
+                with_manifest(&perf_stats.config(), &[], |seed| {
+                    run_perf_stats(perf_stats, seed)
+                })
+            }
+        },
+    }
+}
 
-    let (event_log_path, perf_measure_path) = match args.common_postfix {
+/// Paths of the event log and performance measure files, with the common postfix appended and
+/// the extensions of the output format and compression, if they are set.
+fn output_paths(output: &config::Outputs) -> (PathBuf, PathBuf) {
+    let (ev_path, perf_path) = match &output.common_postfix {
         Some(post) => {
-            let mut ev_path = PathBuf::from(&args.event_log_output);
-            let mut perf_path = PathBuf::from(&args.perf_measure_output);
+            let mut ev_path = PathBuf::from(&output.event_log);
+            let mut perf_path = PathBuf::from(&output.perf_measure);
 
             let pre = ev_path.file_stem();
             let ext = ev_path.extension();
@@ -89,146 +215,404 @@ fn main() -> io::Result<()> {
             (ev_path, perf_path)
         }
         None => (
-            PathBuf::from(&args.event_log_output),
-            PathBuf::from(&args.perf_measure_output),
+            PathBuf::from(&output.event_log),
+            PathBuf::from(&output.perf_measure),
         ),
     };
 
+    let with_format = |path: PathBuf| {
+        if output.format.is_none() && output.compression.is_none() {
+            return path;
+        }
+
+        let compression = output
+            .compression
+            .unwrap_or_else(|| Compression::from_path(&path));
+        let path = Compression::strip(&path);
+        let format = output.format.unwrap_or_else(|| Format::from_path(&path));
+        let path = path.with_extension(format.extension());
+        match format.is_columnar() {
+            true => path,
+            false => compression.append_to(&path),
+        }
+    };
+
+    (with_format(ev_path), with_format(perf_path))
+}
+
+/// Event log of the runs, written one run at a time in the order the runs complete.
+enum EventLog {
+    /// Text file appended to by every run, with whether the first run has replaced the file
+    Text(PathBuf, Mutex<bool>),
+    Columnar(Box<Mutex<ColumnarWriter>>),
+}
+
+impl EventLog {
+    /// Create the event log in the format of its extension.
+    fn create(path: &Path) -> io::Result<Self> {
+        match Format::from_path(path) {
+            Format::Csv | Format::JsonLines => {
+                Ok(EventLog::Text(path.to_path_buf(), Mutex::new(false)))
+            }
+            format => ColumnarWriter::create::<CellEventResult, _>(path, format)
+                .map(|writer| EventLog::Columnar(Box::new(Mutex::new(writer)))),
+        }
+    }
+
+    /// Write the results of a run after those of earlier runs.
+    fn write(&self, run: &EventRunner<EventProcessor>) -> io::Result<()> {
+        match self {
+            EventLog::Text(path, started) => {
+                // runs are streamed to the file, so only one may write at a time
+                let mut started = started.lock().unwrap();
+                run.write_to_file(path, *started)?;
+                *started = true;
+                Ok(())
+            }
+            EventLog::Columnar(writer) => writer.lock().unwrap().write(run.results()),
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            EventLog::Text(..) => Ok(()),
+            EventLog::Columnar(writer) => writer.into_inner().unwrap().finish(),
+        }
+    }
+}
+
+/// Replay every run of a trace of call initiations.
+fn replay_trace(config: &ScenarioConfig, replay_args: &args::ReplayArgs) -> io::Result<()> {
+    let output = &config.output;
+    let (event_log_path, perf_measure_path) = output_paths(output);
+    let scenario = config.scenario();
+    let seed = config.run.seed.unwrap_or_else(experiment::random_seed);
+    let manifest = Manifest::start(config, seed);
+
+    let mut written = vec![perf_measure_path.clone()];
+    if !output.skip_event_log {
+        written.push(event_log_path.clone());
+    }
+    if let Some(path) = &output.database {
+        written.push(PathBuf::from(path));
+    }
+
+    let runs = replay::read_trace(
+        &replay_args.trace,
+        replay::ReplayDefaults {
+            position: replay_args.position,
+            direction: replay_args.direction,
+        },
+        &config.inputs,
+        &scenario.ramps,
+        experiment::run_source(seed, 0),
+    )?;
+    let shared_resources = scenario.shared();
+
+    let event_log = match output.skip_event_log {
+        true => None,
+        false => Some(EventLog::create(&event_log_path)?),
+    };
+    let database = match &output.database {
+        Some(path) => Some(Mutex::new(Database::open(
+            path,
+            &manifest,
+            output.database_events,
+        )?)),
+        None => None,
+    };
+    let mut records = Vec::new();
+    let mut events_processed = 0;
+
+    for (run_idx, events) in runs.into_iter().enumerate() {
+        let run_num = events
+            .first()
+            .map(|ev| ev.run as usize)
+            .unwrap_or(run_idx + 1);
+        let sim = EventProcessor::new(run_num, events);
+        let mut run = EventRunner::init(sim, Some(shared_resources.clone()));
+
+        run.run();
+        events_processed += run.results().len();
+
+        let perf = run.performance_measure(scenario.warmup);
+        println!(
+            "run {}: {:.4}% blocked, {:.4}% dropped",
+            run_num,
+            perf.blocked_calls * 100.0,
+            perf.dropped_calls * 100.0
+        );
+        records.push(PerfRecord::new(run_num, seed, perf));
+
+        if let Some(database) = &database {
+            database
+                .lock()
+                .unwrap()
+                .write_run(run_num, perf, &[run.results()], scenario.warmup)?;
+        }
+
+        if let Some(event_log) = &event_log {
+            event_log.write(&run)?;
+        }
+    }
+
+    if let Some(event_log) = event_log {
+        event_log.finish()?;
+    }
+    columnar::write_records(&perf_measure_path, &records)?;
+    write_manifest(manifest.with_events(events_processed), &written)
+}
+
+/// Simulate every run of the settings and write their performance measures and event log.
+fn simulate(config: &ScenarioConfig) -> io::Result<()> {
+    let output = &config.output;
+    let (event_log_path, perf_measure_path) = output_paths(output);
+    let input_model = &config.inputs;
+    let scenario = config.scenario();
+    let seed = config.run.seed.unwrap_or_else(experiment::random_seed);
+    let manifest = Manifest::start(config, seed);
+
+    println!("seed: {}", seed);
+
     let (handle, send_chan) = progress_task();
 
     // println!("event log path: {:#?}", event_log_path);
     // println!("perf measure path: {:#?}", perf_measure_path);
 
-    if let Some(num_gen) = args.generate {
-        let generator = CallEventGenerator::new(
-            1,
-            RngSource(rand::rngs::OsRng),
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        );
-
-        generate_num_to_file(generator, num_gen, &args.generate_to)?;
-        return Ok(());
+    let mut written = vec![perf_measure_path.clone()];
+    if !output.skip_event_log {
+        written.push(event_log_path.clone());
+    }
+    if let Some(path) = &output.database {
+        written.push(PathBuf::from(path));
     }
 
-    let shared_resources = Shared::new(args.reserved_handover_channels as usize);
+    let shared_resources = scenario.shared();
     // debug_println!("base stations: {:#?}", shared_resources);
 
-    let perf_measures: Arc<Mutex<Vec<PerfMeasure>>> = Arc::new(Mutex::new(Vec::new()));
-
-    (0..args.num_runs as usize)
-        .into_par_iter()
-        .for_each(|run_idx| {
-            // println!("#{} starting run", run_idx);
-
-            // new generator for each iteration
-            let generator = CallEventGenerator::new(
-                run_idx + 1,
-                RngSource(rand::rngs::OsRng),
-                None,
-                None,
-                None,
-                None,
-                None,
-                None,
-            );
-
-            match args.antithetic {
-                true => {
-                    // println!("#{} generating antithetic events", run_idx);
-                    let (events_a, events_b): (Vec<_>, Vec<_>) = generator
-                        .antithetic()
-                        .take(args.num_events as usize)
-                        .unzip();
-
-                    let sim_a = EventProcessor::new(run_idx + 1, events_a);
-                    let sim_b = EventProcessor::new(run_idx + 1, events_b);
-
-                    let mut run_a = EventRunner::init(sim_a, Some(shared_resources.clone()));
-                    let mut run_b = EventRunner::init(sim_b, Some(shared_resources.clone()));
-
-                    // println!("#{} starting simulation", run_idx);
-
-                    run_a.run();
-                    run_b.run();
-
-                    // println!(
-                    //     "#{} simulation complete, calculating perf measure ",
-                    //     run_idx
-                    // );
-                    let avg_perf_measure = (run_a.performance_measure(args.warmup)
-                        + run_b.performance_measure(args.warmup))
-                        / 2.0;
-
-                    perf_measures.lock().unwrap().push(avg_perf_measure);
-
-                    send_chan.send(()).unwrap();
-
-                    if args.skip_event_log {
-                        return;
-                    }
-
-                    if run_idx == 0 {
-                        run_a
-                            .write_to_file(&event_log_path, false)
-                            .expect("failed to write to file");
-                    } else {
-                        run_a
-                            .write_to_file(&event_log_path, true)
-                            .expect("failed to write to file");
-                    }
-
-                    run_b
-                        .write_to_file(&event_log_path, true)
-                        .expect("failed to write to file");
+    let event_log = match output.skip_event_log {
+        true => None,
+        false => Some(EventLog::create(&event_log_path)?),
+    };
+    let database = match &output.database {
+        Some(path) => Some(Mutex::new(Database::open(
+            path,
+            &manifest,
+            output.database_events,
+        )?)),
+        None => None,
+    };
+
+    let perf_measures: Arc<Mutex<Vec<(usize, PerfMeasure)>>> = Arc::new(Mutex::new(Vec::new()));
+    let events_processed = AtomicUsize::new(0);
+    let off_highway: Arc<Mutex<OffHighwayStats>> = Arc::new(Mutex::new(Default::default()));
+    let controlled: Arc<Mutex<Vec<(PerfMeasure, InputMeans)>>> = Arc::new(Mutex::new(Vec::new()));
+    let pairs: Arc<Mutex<Vec<AntitheticPair>>> = Arc::new(Mutex::new(Vec::new()));
+
+    (0..config.run.runs).into_par_iter().for_each(|run_idx| {
+        // println!("#{} starting run", run_idx);
+
+        // new generator for each iteration
+        let mut generator = CallEventGenerator::new(
+            run_idx + 1,
+            experiment::run_source(seed, run_idx),
+            input_model,
+        )
+        .with_ramps(scenario.ramps.clone());
+
+        match config.run.antithetic {
+            true => {
+                // println!("#{} generating antithetic events", run_idx);
+                let start = Instant::now();
+                let (mut events_a, mut events_b): (Vec<_>, Vec<_>) =
+                    generator.antithetic().take(scenario.num_events).unzip();
+                events_a.retain(|ev| scenario.within_horizon(ev));
+                events_b.retain(|ev| scenario.within_horizon(ev));
+
+                // entering vehicles are not paired, each half gets its own stream
+                let events_a = add_ramp_entries(&mut generator, events_a);
+                let events_b = add_ramp_entries(&mut generator, events_b);
+                let generation_seconds = start.elapsed().as_secs_f64();
+
+                let sim_a = EventProcessor::new(run_idx + 1, events_a);
+                let sim_b = EventProcessor::new(run_idx + 1, events_b);
+
+                let mut run_a = EventRunner::init(sim_a, Some(shared_resources.clone()));
+                let mut run_b = EventRunner::init(sim_b, Some(shared_resources.clone()));
+
+                // println!("#{} starting simulation", run_idx);
+
+                let start = Instant::now();
+                run_a.run();
+                run_b.run();
+                let simulation_seconds = start.elapsed().as_secs_f64();
+
+                // println!(
+                //     "#{} simulation complete, calculating perf measure ",
+                //     run_idx
+                // );
+                let perf_a = run_a.performance_measure(scenario.warmup);
+                let perf_b = run_b.performance_measure(scenario.warmup);
+                let avg_perf_measure = (perf_a + perf_b) / 2.0;
+
+                perf_measures
+                    .lock()
+                    .unwrap()
+                    .push((run_idx + 1, avg_perf_measure));
+                events_processed.fetch_add(
+                    run_a.results().len() + run_b.results().len(),
+                    Ordering::Relaxed,
+                );
+                pairs.lock().unwrap().push(AntitheticPair {
+                    generation_seconds,
+                    simulation_seconds,
+                    ..AntitheticPair::new(run_idx + 1, perf_a, perf_b)
+                });
+
+                let mut off = off_highway.lock().unwrap();
+                off.merge(&run_a.shared_resources().off_highway);
+                off.merge(&run_b.shared_resources().off_highway);
+                drop(off);
+
+                if let Some(database) = &database {
+                    database
+                        .lock()
+                        .unwrap()
+                        .write_run(
+                            run_idx + 1,
+                            avg_perf_measure,
+                            &[run_a.results(), run_b.results()],
+                            scenario.warmup,
+                        )
+                        .expect("failed to write to database");
                 }
-                false => {
-                    let gen_events = generator.take(args.num_events as usize).collect::<Vec<_>>();
 
-                    let sim = EventProcessor::new(run_idx + 1, gen_events);
-                    let mut run = EventRunner::init(sim, Some(shared_resources.clone()));
+                send_chan.send(()).unwrap();
+
+                let Some(event_log) = &event_log else {
+                    return;
+                };
+
+                event_log.write(&run_a).expect("failed to write to file");
+                event_log.write(&run_b).expect("failed to write to file");
+            }
+            false => {
+                let gen_events = generator
+                    .by_ref()
+                    .take(scenario.num_events)
+                    .take_while(|ev| scenario.within_horizon(ev))
+                    .collect::<Vec<_>>();
+                let inputs = InputMeans::from_events(&gen_events);
+                let gen_events = add_ramp_entries(&mut generator, gen_events);
+
+                let sim = EventProcessor::new(run_idx + 1, gen_events);
+                let mut run = EventRunner::init(sim, Some(shared_resources.clone()));
+
+                run.run();
+                let perf = run.performance_measure(scenario.warmup);
+                perf_measures.lock().unwrap().push((run_idx + 1, perf));
+                events_processed.fetch_add(run.results().len(), Ordering::Relaxed);
+                if config.run.control_variates {
+                    controlled.lock().unwrap().push((perf, inputs));
+                }
+                off_highway
+                    .lock()
+                    .unwrap()
+                    .merge(&run.shared_resources().off_highway);
 
-                    run.run();
-                    perf_measures
+                if let Some(database) = &database {
+                    database
                         .lock()
                         .unwrap()
-                        .push(run.performance_measure(args.warmup));
-
-                    send_chan.send(()).unwrap();
-
-                    if args.skip_event_log {
-                        return;
-                    }
-
-                    match run_idx == 0 {
-                        true => run
-                            .write_to_file(&event_log_path, false)
-                            .expect("failed to write to file"),
-                        false => run
-                            .write_to_file(&event_log_path, true)
-                            .expect("failed to write to file"),
-                    }
+                        .write_run(run_idx + 1, perf, &[run.results()], scenario.warmup)
+                        .expect("failed to write to database");
                 }
+
+                send_chan.send(()).unwrap();
+
+                let Some(event_log) = &event_log else {
+                    return;
+                };
+
+                event_log.write(&run).expect("failed to write to file");
             }
+        }
 
-            // println!("#{} ending run", run_idx);
-        });
+        // println!("#{} ending run", run_idx);
+    });
 
     drop(send_chan);
     handle.join().unwrap();
 
-    let mut writer = csv::Writer::from_path(&perf_measure_path)?;
-    for perf in perf_measures.lock().unwrap().iter() {
-        writer.serialize(perf)?;
+    if let Some(event_log) = event_log {
+        event_log.finish()?;
     }
 
-    writer.flush()?;
+    if !scenario.ramps.is_empty() {
+        let off = off_highway.lock().unwrap();
+        println!(
+            "\noff-highway: {} calls released, {} calls ({:.1}s) handed to sink",
+            off.released, off.sink_calls, off.sink_call_time
+        );
+    }
 
-    Ok(())
+    if config.run.control_variates {
+        let (blocked, dropped) = experiment::controlled_perf_measure(
+            &controlled.lock().unwrap(),
+            input_model,
+            DEFAULT_CONFIDENCE,
+        )
+        .map_err(invalid_input)?;
+        experiment::print_controlled(&blocked, &dropped);
+    }
+
+    if config.run.antithetic {
+        let mut pairs = pairs.lock().unwrap();
+        pairs.sort_by_key(|pair| pair.run);
+
+        // reference time of a naive run's events, on the streams of the first run
+        let start = Instant::now();
+        let mut naive = CallEventGenerator::new(1, experiment::run_source(seed, 0), input_model)
+            .with_ramps(scenario.ramps.clone());
+        let naive_events = naive
+            .by_ref()
+            .take(scenario.num_events)
+            .take_while(|ev| scenario.within_horizon(ev))
+            .collect();
+        drop(add_ramp_entries(&mut naive, naive_events));
+        let naive_seconds = start.elapsed().as_secs_f64();
+
+        match antithetic::effectiveness(&pairs, naive_seconds) {
+            Ok((blocked, dropped)) => {
+                antithetic::print_effectiveness(&pairs, naive_seconds, &blocked, &dropped)
+            }
+            Err(e) => println!("\n{}", e),
+        }
+
+        let mut writer = csv::Writer::from_path(&output.antithetic)?;
+        for pair in pairs.iter() {
+            writer.serialize(pair)?;
+        }
+        writer.flush()?;
+    }
+
+    let mut perf_measures = perf_measures.lock().unwrap();
+    perf_measures.sort_by_key(|(run, _)| *run);
+
+    let records = perf_measures
+        .iter()
+        .map(|(run, perf)| PerfRecord::new(*run, seed, *perf))
+        .collect::<Vec<_>>();
+    columnar::write_records(&perf_measure_path, &records)?;
+
+    if config.run.antithetic {
+        written.push(PathBuf::from(&output.antithetic));
+    }
+    println!();
+    write_manifest(
+        manifest.with_events(events_processed.into_inner()),
+        &written,
+    )
 }
 
 /// Run the csv writer in a separate task
@@ -252,16 +636,19 @@ fn progress_task() -> (thread::JoinHandle<()>, mpsc::SyncSender<()>) {
 }
 
 fn generate_num_to_file<S>(
-    event_gen: CallEventGenerator<S>,
+    mut event_gen: CallEventGenerator<S>,
     num_gen: u32,
     file: &str,
 ) -> io::Result<()>
 where
-    S: Source + Clone,
+    S: SplitSource,
 {
     let mut writer = csv::Writer::from_path(file)?;
 
-    for ev in event_gen.take(num_gen as usize) {
+    let events = event_gen.by_ref().take(num_gen as usize).collect();
+    let events = add_ramp_entries(&mut event_gen, events);
+
+    for ev in events {
         // println!("event time: {}", ev.time);
         writer.serialize(ev)?;
     }
@@ -271,6 +658,286 @@ where
     Ok(())
 }
 
+/// Test input distributions fitted to the test data, and generated call initiations.
+fn analyze_input(
+    args: &args::AnalyzeInputArgs,
+    input_model: &InputModel,
+    seed: u64,
+) -> io::Result<()> {
+    let data = InputSamples::from_data(&args.data)?;
+    let mut rows = input_analysis::analyze_data(&data, input_model, args.bins);
+
+    if args.num_generated > 0 {
+        let events = CallEventGenerator::new(1, experiment::run_source(seed, 0), input_model)
+            .take(args.num_generated as usize)
+            .collect::<Vec<_>>();
+
+        rows.extend(input_analysis::analyze_generated(
+            &InputSamples::from_events(&events),
+            input_model,
+            args.bins,
+        ));
+    }
+
+    input_analysis::print_summary(&rows);
+    input_analysis::write_report(&args.report, &rows)
+}
+
+/// Simulate a full factorial design, with factors not swept kept at the main arguments.
+fn run_sweep(config: &ScenarioConfig, sweep_args: &args::SweepArgs, seed: u64) -> io::Result<()> {
+    let base = config.scenario();
+    let points = sweep_args.factors.design(&base).map_err(invalid_input)?;
+
+    println!(
+        "seed: {}, {} design points of {} runs",
+        seed,
+        points.len(),
+        config.run.runs
+    );
+    let rows = sweep::run_sweep(&points, &base, config.run.runs, seed, sweep_args.confidence);
+
+    let mut writer = csv::Writer::from_path(&sweep_args.output)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
+
+/// Compare configurations to the first one, by paired differences over common random numbers.
+fn run_compare(
+    config: &ScenarioConfig,
+    compare_args: &args::CompareArgs,
+    seed: u64,
+) -> io::Result<()> {
+    let base = config.scenario();
+    let points = compare_args.factors.design(&base).map_err(invalid_input)?;
+
+    println!(
+        "seed: {}, {} configurations of {} runs",
+        seed,
+        points.len(),
+        config.run.runs
+    );
+    let rows = compare::run_compare(
+        &points,
+        &base,
+        config.run.runs,
+        seed,
+        !compare_args.independent,
+        compare_args.confidence,
+    )
+    .map_err(invalid_input)?;
+    compare::print_comparison(&points[0], &rows);
+
+    let mut writer = csv::Writer::from_path(&compare_args.output)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
+
+/// Search for the fewest reserved handover channels meeting the blocking and dropping limits.
+fn run_optimize(
+    config: &ScenarioConfig,
+    optimize_args: &args::OptimizeArgs,
+    seed: u64,
+) -> io::Result<()> {
+    let base = config.scenario();
+    let candidates = (0..=optimize_args.max_reserved)
+        .map(optimize::StationReserves::uniform)
+        .chain(optimize_args.profiles.iter().copied())
+        .collect::<Vec<_>>();
+
+    println!("seed: {}, {} candidates", seed, candidates.len());
+    let optimum = optimize::optimize(
+        &base,
+        &candidates,
+        optimize::Constraints {
+            blocked_limit: optimize_args.blocked_limit,
+            dropped_limit: optimize_args.dropped_limit,
+            tolerance: optimize_args.tolerance,
+        },
+        optimize::SelectionSettings {
+            pcs: optimize_args.pcs,
+            indifference: optimize_args.indifference,
+            initial_runs: optimize_args.initial_runs,
+            max_runs: optimize_args.max_runs,
+        },
+        seed,
+    )
+    .map_err(invalid_input)?;
+    optimize::print_optimum(&optimum, optimize_args.pcs);
+
+    let mut writer = csv::Writer::from_path(&optimize_args.output)?;
+    for row in optimum.rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
+
+/// Compare the variance of sampling schemes over independent designs.
+fn run_sampling_report(
+    config: &ScenarioConfig,
+    report_args: &args::SamplingReportArgs,
+    seed: u64,
+) -> io::Result<()> {
+    let scenario = config.scenario();
+    let samplings = [
+        sampling::Sampling::Naive,
+        sampling::Sampling::Antithetic,
+        sampling::Sampling::Stratified(report_args.stratify.clone()),
+        sampling::Sampling::LatinHypercube,
+        sampling::Sampling::Quasi(Sequence::Sobol),
+        sampling::Sampling::Quasi(Sequence::Halton),
+    ];
+
+    println!(
+        "seed: {}, {} designs of {} runs per scheme",
+        seed, report_args.designs, report_args.runs_per_design
+    );
+    let rows = sampling::sampling_report(
+        &scenario,
+        &samplings,
+        report_args.designs,
+        report_args.runs_per_design,
+        seed,
+    )
+    .map_err(invalid_input)?;
+    sampling::print_report(&rows);
+
+    let mut writer = csv::Writer::from_path(&report_args.output)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
+
+/// Estimate blocked and dropped calls from tilted runs.
+fn run_importance(
+    config: &ScenarioConfig,
+    importance_args: &args::ImportanceArgs,
+    seed: u64,
+) -> io::Result<()> {
+    let scenario = config.scenario();
+    let default_tilt = 1.0 + 1.0 / (scenario.num_events.max(1) as f64).sqrt();
+    let tilt = Tilt {
+        arrival_rate: importance_args.arrival_tilt.unwrap_or(default_tilt),
+        call_duration: importance_args.duration_tilt.unwrap_or(default_tilt),
+    };
+
+    println!(
+        "seed: {}, arrival rate x{:.3}, call duration x{:.3}",
+        seed, tilt.arrival_rate, tilt.call_duration
+    );
+    let report = importance::importance_runs(
+        &scenario,
+        tilt,
+        config.run.runs,
+        seed,
+        importance_args.naive,
+        importance_args.confidence,
+    )
+    .map_err(invalid_input)?;
+    importance::print_report(&report);
+
+    let mut writer = csv::Writer::from_path(&importance_args.output)?;
+    for row in &report.runs {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
+
+/// Summarise performance measure files, and compare them if there are two.
+fn run_perf_stats(perf_args: &args::PerfStatsArgs, seed: u64) -> io::Result<()> {
+    println!("seed: {}", seed);
+    let mut source = experiment::run_source(seed, 0);
+
+    let perf = perf_args
+        .files
+        .iter()
+        .map(analytic::read_perf_measures)
+        .collect::<io::Result<Vec<_>>>()?;
+    for (path, perf) in perf_args.files.iter().zip(&perf) {
+        let stats =
+            perf_stats::describe(perf, perf_args.resamples, perf_args.confidence, &mut source)
+                .map_err(|e| invalid_input(format!("{}: {}", path, e)))?;
+        perf_stats::print_stats(path, &stats);
+    }
+
+    if let [a, b] = perf.as_slice() {
+        println!("{} - {}", perf_args.files[0], perf_args.files[1]);
+        perf_stats::print_tests(&perf_stats::compare(a, b).map_err(invalid_input)?);
+    }
+
+    Ok(())
+}
+
+/// Estimate blocked and dropped calls over the regeneration cycles of one run.
+fn run_regenerative(
+    config: &ScenarioConfig,
+    regenerative_args: &args::RegenerativeArgs,
+    seed: u64,
+) -> io::Result<()> {
+    let scenario = config.scenario();
+
+    println!("seed: {}", seed);
+    let report = regenerative::regenerative_run(&scenario, 0, seed, regenerative_args.confidence)
+        .map_err(invalid_input)?;
+    regenerative::print_report(&report);
+
+    let mut writer = csv::Writer::from_path(&regenerative_args.output)?;
+    for cycle in &report.cycles {
+        writer.serialize(cycle)?;
+    }
+
+    writer.flush()
+}
+
+/// Run a mode with the seed of its settings, and write its manifest next to its outputs.
+fn with_manifest<F>(config: &ScenarioConfig, outputs: &[&String], mode: F) -> io::Result<()>
+where
+    F: FnOnce(u64) -> io::Result<()>,
+{
+    let seed = config.run.seed.unwrap_or_else(experiment::random_seed);
+    let manifest = Manifest::start(config, seed);
+
+    mode(seed)?;
+    write_manifest(manifest, outputs)
+}
+
+/// Write the manifest of an invocation next to its outputs.
+fn write_manifest<P: AsRef<Path>>(manifest: Manifest, outputs: &[P]) -> io::Result<()> {
+    let path = manifest.write(outputs)?;
+    println!("manifest: {}", path.display());
+    Ok(())
+}
+
+fn invalid_input(e: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, e)
+}
+
+/// Add calls of vehicles entering the highway at ramps, up to the last generated call initiation.
+fn add_ramp_entries<S>(
+    event_gen: &mut CallEventGenerator<S>,
+    events: Vec<CellEvent>,
+) -> Vec<CellEvent>
+where
+    S: SplitSource,
+{
+    let horizon = events.last().map(|ev| ev.time).unwrap_or_default();
+    let entries = event_gen
+        .ramp_entries()
+        .take_while(|ev| ev.time <= horizon)
+        .collect();
+
+    ramp::merge_entries(events, entries)
+}
+
 /// Prints as per normal when in debug mode.
 /// Does not print when in release mode.
 #[macro_export]
@@ -0,0 +1,132 @@
+//! Run manifests, recording how every set of output files was produced.
+//!
+//! Each invocation writes a json manifest next to its first output file, with the resolved
+//! settings and seed, the build of the simulator, how long it took and which files it wrote.
+//! The settings under `config` can be copied into a scenario file to reproduce the outputs.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::config::ScenarioConfig;
+
+/// Provenance of the outputs of one invocation.
+#[derive(Clone, Debug, Serialize)]
+pub struct Manifest {
+    /// Command line of the invocation
+    pub command: Vec<String>,
+
+    pub version: &'static str,
+
+    /// Commit the simulator was built from, if it was built in a git checkout
+    pub git_hash: Option<&'static str>,
+
+    /// Base seed of the runs
+    pub seed: u64,
+
+    /// Resolved settings, with the seed filled in
+    pub config: ScenarioConfig,
+
+    /// Start of the invocation, in seconds since the Unix epoch
+    pub started_at: u64,
+
+    pub wall_clock_seconds: f64,
+
+    /// Events processed over all runs, if counted
+    pub events_processed: Option<usize>,
+
+    pub events_per_second: Option<f64>,
+
+    /// Files written by the invocation
+    pub outputs: Vec<PathBuf>,
+
+    #[serde(skip)]
+    start: Instant,
+}
+
+impl Manifest {
+    /// Start recording an invocation with the given settings and seed.
+    pub fn start(config: &ScenarioConfig, seed: u64) -> Self {
+        let mut config = config.clone();
+        config.run.seed = Some(seed);
+
+        Self {
+            command: std::env::args().collect(),
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: option_env!("SIMULATOR_GIT_HASH"),
+            seed,
+            config,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            wall_clock_seconds: 0.0,
+            events_processed: None,
+            events_per_second: None,
+            outputs: Vec::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Record the number of events processed over all runs.
+    pub fn with_events(mut self, events: usize) -> Self {
+        self.events_processed = Some(events);
+        self
+    }
+
+    /// Finish recording and write the manifest next to the first of `outputs`, as
+    /// `<name>.manifest.json`. Returns the path of the manifest.
+    pub fn write<P: AsRef<Path>>(mut self, outputs: &[P]) -> io::Result<PathBuf> {
+        self.wall_clock_seconds = self.start.elapsed().as_secs_f64();
+        self.events_per_second = self
+            .events_processed
+            .map(|events| events as f64 / self.wall_clock_seconds);
+        self.outputs = outputs.iter().map(|p| p.as_ref().to_path_buf()).collect();
+
+        let path = match self.outputs.first() {
+            Some(output) => manifest_path(output),
+            None => PathBuf::from(concat!(env!("CARGO_BIN_NAME"), ".manifest.json")),
+        };
+        let json = serde_json::to_string_pretty(&self).map_err(io::Error::other)?;
+        fs::write(&path, json + "\n")?;
+
+        Ok(path)
+    }
+}
+
+/// Path of the manifest of an output file, replacing its extension.
+fn manifest_path(output: &Path) -> PathBuf {
+    output.with_extension("manifest.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_path() {
+        assert_eq!(
+            manifest_path(Path::new("out/simulator_perf_a.csv")),
+            PathBuf::from("out/simulator_perf_a.manifest.json")
+        );
+        assert_eq!(
+            manifest_path(Path::new("trace")),
+            PathBuf::from("trace.manifest.json")
+        );
+    }
+
+    #[test]
+    fn test_manifest_json() {
+        let manifest = Manifest::start(&ScenarioConfig::default(), 42).with_events(1000);
+        assert_eq!(manifest.config.run.seed, Some(42));
+
+        let json = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(json["seed"], 42);
+        assert_eq!(json["config"]["run"]["runs"], 100);
+        assert_eq!(json["events_processed"], 1000);
+        assert!(json.get("start").is_none());
+    }
+}
@@ -0,0 +1,436 @@
+//! Smallest handover reservation that meets blocking and dropping limits.
+//!
+//! Candidates cost the total number of channels they reserve. Blocked and dropped calls of every
+//! candidate are checked against their limits with sequential feasibility checks, and ties among
+//! the cheapest feasible candidates are broken by the fewest dropped calls with Kim and Nelson's
+//! procedure. Both stages get half of the allowed probability of incorrect selection.
+//! Every candidate is simulated under common random numbers.
+
+use std::{fmt::Display, str::FromStr};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+use simulator_core::selection::{Feasibility, FeasibilityCheck, KnSelection};
+
+use crate::{
+    base_station::NUM_STATIONS,
+    event::PerfMeasure,
+    experiment::{run_source, Scenario},
+};
+
+/// Reserved handover channels per station, west to east, as 20 comma separated counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StationReserves(pub [usize; NUM_STATIONS]);
+
+/// Limits on the mean fraction of blocked and dropped calls.
+#[derive(Clone, Copy, Debug)]
+pub struct Constraints {
+    pub blocked_limit: f64,
+    pub dropped_limit: f64,
+
+    /// Means within this of a limit may be declared either feasible or infeasible
+    pub tolerance: f64,
+}
+
+/// Settings of the selection procedures.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionSettings {
+    /// Probability of correct selection
+    pub pcs: f64,
+
+    /// Feasible candidates with fewer dropped calls than this are equally good
+    pub indifference: f64,
+
+    /// Runs of every candidate before any decision
+    pub initial_runs: usize,
+
+    /// Runs after which undecided candidates are given up on
+    pub max_runs: usize,
+}
+
+/// Outcome of a candidate.
+#[derive(Clone, Debug, Serialize)]
+pub struct CandidateRow {
+    pub reserved: String,
+    pub reserved_total: usize,
+    pub runs: usize,
+    pub blocked_mean: f64,
+    pub dropped_mean: f64,
+    pub blocked: Feasibility,
+    pub dropped: Feasibility,
+    pub selected: bool,
+}
+
+/// Outcome of an optimisation.
+#[derive(Clone, Debug)]
+pub struct Optimum {
+    pub rows: Vec<CandidateRow>,
+
+    /// Index of the selected candidate, if any is feasible
+    pub selected: Option<usize>,
+
+    /// Whether the selection holds with the requested probability, which it does not when a
+    /// candidate stayed undecided after the maximum number of runs
+    pub guaranteed: bool,
+}
+
+impl StationReserves {
+    pub fn uniform(reserved: usize) -> Self {
+        Self([reserved; NUM_STATIONS])
+    }
+
+    /// Total reserved channels along the highway.
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+}
+
+impl FromStr for StationReserves {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let reserves = s
+            .split(',')
+            .map(|x| {
+                x.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid reserved channels '{}'", x.trim()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match reserves.try_into() {
+            Ok(reserves) => Ok(Self(reserves)),
+            Err(_) => Err(format!(
+                "expected reserved channels of all {} stations",
+                NUM_STATIONS
+            )),
+        }
+    }
+}
+
+impl Display for StationReserves {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.iter().all(|r| *r == self.0[0]) {
+            true => write!(f, "{}", self.0[0]),
+            false => {
+                let reserves = self.0.iter().map(usize::to_string).collect::<Vec<_>>();
+                write!(f, "{}", reserves.join(","))
+            }
+        }
+    }
+}
+
+/// Simulated runs of every candidate, extended in parallel as the procedures need them.
+struct Runs {
+    scenarios: Vec<Scenario>,
+    seed: u64,
+    perf: Vec<Vec<PerfMeasure>>,
+    batch: usize,
+}
+
+impl Runs {
+    fn new(base: &Scenario, candidates: &[StationReserves], seed: u64) -> Self {
+        let scenarios = candidates
+            .iter()
+            .map(|reserves| Scenario {
+                station_reserved: Some(reserves.0),
+                ..base.clone()
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            perf: vec![Vec::new(); scenarios.len()],
+            scenarios,
+            seed,
+            batch: rayon::current_num_threads().max(1),
+        }
+    }
+
+    /// Run `run` of a candidate, simulating a batch of runs of every active candidate if needed.
+    fn get(
+        &mut self,
+        candidate: usize,
+        run: usize,
+        active: &[usize],
+        max_runs: usize,
+    ) -> PerfMeasure {
+        if self.perf[candidate].len() <= run {
+            let end = (run + self.batch).min(max_runs).max(run + 1);
+            let jobs = active
+                .iter()
+                .flat_map(|c| (self.perf[*c].len()..end).map(move |r| (*c, r)))
+                .collect::<Vec<_>>();
+
+            let scenarios = &self.scenarios;
+            let seed = self.seed;
+            let results = jobs
+                .into_par_iter()
+                .map(|(c, r)| (c, scenarios[c].simulate(r, run_source(seed, r))))
+                .collect::<Vec<_>>();
+
+            // results keep the order of the jobs, so runs are appended in order
+            for (c, perf) in results {
+                self.perf[c].push(perf);
+            }
+        }
+
+        self.perf[candidate][run]
+    }
+}
+
+/// Select the candidate with the fewest reserved channels that meets the constraints.
+pub fn optimize(
+    base: &Scenario,
+    candidates: &[StationReserves],
+    constraints: Constraints,
+    settings: SelectionSettings,
+    seed: u64,
+) -> Result<Optimum, String> {
+    if candidates.is_empty() {
+        return Err("no candidates to optimise over".to_string());
+    }
+    if let Some(reserves) = candidates
+        .iter()
+        .find(|reserves| reserves.0.iter().any(|r| *r >= base.channels))
+    {
+        return Err(format!(
+            "reserved channels {} leave no channel for new calls out of {}",
+            reserves, base.channels
+        ));
+    }
+    if settings.initial_runs < 2 || settings.max_runs < settings.initial_runs {
+        return Err("needs at least 2 initial runs, and no fewer maximum runs".to_string());
+    }
+    // without a zone of indifference, the bounds never close and every candidate runs to the end
+    if !(constraints.tolerance > 0.0 && settings.indifference > 0.0) {
+        return Err("the tolerance and indifference must be positive".to_string());
+    }
+    if !(0.0..1.0).contains(&settings.pcs) {
+        return Err("the probability of correct selection must be below 1".to_string());
+    }
+
+    let error = (1.0 - settings.pcs) / 2.0;
+    let check_error = error / (2 * candidates.len()) as f64;
+    let check = |limit| {
+        FeasibilityCheck::new(
+            limit,
+            constraints.tolerance,
+            settings.initial_runs,
+            check_error,
+        )
+    };
+
+    let mut blocked = vec![check(constraints.blocked_limit); candidates.len()];
+    let mut dropped = vec![check(constraints.dropped_limit); candidates.len()];
+    let mut runs = Runs::new(base, candidates, seed);
+
+    let decision = |b: &FeasibilityCheck, d: &FeasibilityCheck| match (b.decision(), d.decision()) {
+        (Feasibility::Infeasible, _) | (_, Feasibility::Infeasible) => Feasibility::Infeasible,
+        (Feasibility::Feasible, Feasibility::Feasible) => Feasibility::Feasible,
+        _ => Feasibility::Undecided,
+    };
+
+    // check feasibility, until every candidate is decided or costs more than a feasible one
+    for run in 0..settings.max_runs {
+        let cheapest = (0..candidates.len())
+            .filter(|c| decision(&blocked[*c], &dropped[*c]) == Feasibility::Feasible)
+            .map(|c| candidates[c].total())
+            .min()
+            .unwrap_or(usize::MAX);
+        let active = (0..candidates.len())
+            .filter(|c| {
+                decision(&blocked[*c], &dropped[*c]) == Feasibility::Undecided
+                    && candidates[*c].total() <= cheapest
+            })
+            .collect::<Vec<_>>();
+
+        if active.is_empty() {
+            break;
+        }
+
+        for c in &active {
+            let perf = runs.get(*c, run, &active, settings.max_runs);
+            blocked[*c].add(perf.blocked_calls as f64);
+            dropped[*c].add(perf.dropped_calls as f64);
+        }
+    }
+
+    let decisions = (0..candidates.len())
+        .map(|c| decision(&blocked[c], &dropped[c]))
+        .collect::<Vec<_>>();
+    let cheapest = (0..candidates.len())
+        .filter(|c| decisions[*c] == Feasibility::Feasible)
+        .map(|c| candidates[c].total())
+        .min();
+    let mut guaranteed = (0..candidates.len()).all(|c| {
+        decisions[c] != Feasibility::Undecided
+            || cheapest.is_some_and(|cheapest| candidates[c].total() > cheapest)
+    });
+
+    // break ties among the cheapest feasible candidates by dropped calls
+    let tied = (0..candidates.len())
+        .filter(|c| {
+            decisions[*c] == Feasibility::Feasible && Some(candidates[*c].total()) == cheapest
+        })
+        .collect::<Vec<_>>();
+    let selected = match tied.len() {
+        0 => None,
+        1 => Some(tied[0]),
+        _ => {
+            let mut kn = KnSelection::new(
+                tied.len(),
+                settings.indifference,
+                settings.initial_runs,
+                error,
+            );
+            let mut run = 0;
+            while kn.selected().is_none() && run < settings.max_runs {
+                let surviving = kn.surviving().iter().map(|i| tied[*i]).collect::<Vec<_>>();
+                let obs = surviving
+                    .iter()
+                    .map(|c| {
+                        runs.get(*c, run, &surviving, settings.max_runs)
+                            .dropped_calls as f64
+                    })
+                    .collect::<Vec<_>>();
+                kn.add(&obs);
+                run += 1;
+            }
+
+            guaranteed &= kn.selected().is_some();
+            let means = kn.means();
+            kn.selected()
+                .or_else(|| {
+                    kn.surviving()
+                        .into_iter()
+                        .min_by(|a, b| means[*a].total_cmp(&means[*b]))
+                })
+                .map(|i| tied[i])
+        }
+    };
+
+    let rows = candidates
+        .iter()
+        .enumerate()
+        .map(|(c, reserves)| {
+            let perf = &runs.perf[c];
+            let n = perf.len().max(1) as f64;
+            CandidateRow {
+                reserved: reserves.to_string(),
+                reserved_total: reserves.total(),
+                runs: perf.len(),
+                blocked_mean: perf.iter().map(|p| p.blocked_calls as f64).sum::<f64>() / n,
+                dropped_mean: perf.iter().map(|p| p.dropped_calls as f64).sum::<f64>() / n,
+                blocked: blocked[c].decision(),
+                dropped: dropped[c].decision(),
+                selected: selected == Some(c),
+            }
+        })
+        .collect();
+
+    Ok(Optimum {
+        rows,
+        selected,
+        guaranteed,
+    })
+}
+
+/// Print the outcome of every candidate and the selection.
+pub fn print_optimum(optimum: &Optimum, pcs: f64) {
+    println!(
+        "{:>20} {:>6} {:>10} {:>10} {:>10} {:>10}",
+        "reserved", "runs", "blocked", "dropped", "blocking", "dropping"
+    );
+    for row in &optimum.rows {
+        println!(
+            "{:>20} {:>6} {:>9.4}% {:>9.4}% {:>10} {:>10}{}",
+            row.reserved,
+            row.runs,
+            row.blocked_mean * 100.0,
+            row.dropped_mean * 100.0,
+            format!("{:?}", row.blocked),
+            format!("{:?}", row.dropped),
+            match row.selected {
+                true => "  <- selected",
+                false => "",
+            }
+        );
+    }
+
+    match (optimum.selected, optimum.guaranteed) {
+        (None, _) => println!("\nno candidate meets the constraints"),
+        (Some(c), true) => println!(
+            "\nselected reserved channels {} with probability of correct selection at least {}",
+            optimum.rows[c].reserved, pcs
+        ),
+        (Some(c), false) => println!(
+            "\nselected reserved channels {}, but some candidates were undecided after the \
+             maximum number of runs",
+            optimum.rows[c].reserved
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::InputModel;
+
+    #[test]
+    fn test_parse_station_reserves() {
+        let reserves = "0,0,1,1,2,2,1,1,0,0,0,0,1,1,2,2,1,1,0,0"
+            .parse::<StationReserves>()
+            .unwrap();
+        assert_eq!(reserves.total(), 16);
+        assert_eq!(reserves.0[4], 2);
+        assert_eq!(StationReserves::uniform(2).to_string(), "2");
+
+        assert!("1,2,3".parse::<StationReserves>().is_err());
+        assert!("a".parse::<StationReserves>().is_err());
+    }
+
+    #[test]
+    fn test_optimize() {
+        let base = Scenario::new(InputModel::default(), 0, 2_000, 0);
+        let candidates = (0..3).map(StationReserves::uniform).collect::<Vec<_>>();
+        let constraints = Constraints {
+            blocked_limit: 0.05,
+            dropped_limit: 0.005,
+            tolerance: 0.001,
+        };
+        let settings = SelectionSettings {
+            pcs: 0.95,
+            indifference: 0.0005,
+            initial_runs: 10,
+            max_runs: 400,
+        };
+
+        // no reservation drops too many calls, one reserved channel is the cheapest fix
+        let optimum = optimize(&base, &candidates, constraints, settings, 3).unwrap();
+        assert_eq!(optimum.selected, Some(1));
+        assert!(optimum.guaranteed);
+        assert_eq!(optimum.rows[0].dropped, Feasibility::Infeasible);
+        assert!(optimum.rows[1].selected);
+
+        let tight = Constraints {
+            dropped_limit: 0.0,
+            ..constraints
+        };
+        let optimum = optimize(&base, &candidates, tight, settings, 3).unwrap();
+        assert_eq!(optimum.selected, None);
+
+        let full = [StationReserves::uniform(10)];
+        assert!(optimize(&base, &full, constraints, settings, 3).is_err());
+
+        let exact = Constraints {
+            tolerance: 0.0,
+            ..constraints
+        };
+        assert!(optimize(&base, &candidates, exact, settings, 3).is_err());
+        let indifferent = SelectionSettings {
+            indifference: 0.0,
+            ..settings
+        };
+        assert!(optimize(&base, &candidates, constraints, indifferent, 3).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Bootstrap intervals and hypothesis tests of performance measures read back from csv files.
+//!
+//! Blocked and dropped calls are proportions that are often close to zero, so their spread
+//! across runs is skewed and Student-t intervals can cross zero. Bootstrap intervals are shown
+//! next to them, and two files are compared with paired and Welch t tests.
+
+use probability::source::Source;
+use simulator_core::{
+    bootstrap::{bootstrap_mean, BootstrapCi},
+    stats::{paired_t_test, welch_t_test, Summary, TestResult},
+};
+
+use crate::event::PerfMeasure;
+
+/// Name of a performance measure, and its value in a run.
+type Measure = (&'static str, fn(&PerfMeasure) -> f64);
+
+/// Performance measures summarised across runs.
+const MEASURES: [Measure; 2] = [
+    ("blocked", |p| p.blocked_calls as f64),
+    ("dropped", |p| p.dropped_calls as f64),
+];
+
+/// Intervals of the mean of a performance measure.
+#[derive(Clone, Debug)]
+pub struct MeasureStats {
+    pub measure: &'static str,
+    pub summary: Summary,
+    pub bootstrap: BootstrapCi,
+}
+
+/// Tests of a difference in a performance measure between two files.
+#[derive(Clone, Debug)]
+pub struct MeasureTests {
+    pub measure: &'static str,
+
+    /// Test of run `r` of one file against run `r` of the other, if they have as many runs
+    pub paired: Option<TestResult>,
+
+    pub welch: TestResult,
+}
+
+/// Student-t and bootstrap intervals of the mean of every performance measure.
+pub fn describe<S: Source>(
+    perf: &[PerfMeasure],
+    resamples: usize,
+    confidence: f64,
+    source: &mut S,
+) -> Result<Vec<MeasureStats>, String> {
+    MEASURES
+        .iter()
+        .map(|(measure, value)| {
+            let samples = perf.iter().map(value).collect::<Vec<_>>();
+            Ok(MeasureStats {
+                measure,
+                summary: Summary::new(&samples, confidence),
+                bootstrap: bootstrap_mean(&samples, resamples, confidence, source)?,
+            })
+        })
+        .collect()
+}
+
+/// Test every performance measure for a difference between `a` and `b`.
+///
+/// Runs are only paired when both have the same number, which they do when simulated with the
+/// same seed, e.g. under common random numbers.
+pub fn compare(a: &[PerfMeasure], b: &[PerfMeasure]) -> Result<Vec<MeasureTests>, String> {
+    MEASURES
+        .iter()
+        .map(|(measure, value)| {
+            let a = a.iter().map(value).collect::<Vec<_>>();
+            let b = b.iter().map(value).collect::<Vec<_>>();
+            Ok(MeasureTests {
+                measure,
+                paired: match a.len() == b.len() {
+                    true => Some(paired_t_test(&a, &b)?),
+                    false => None,
+                },
+                welch: welch_t_test(&a, &b)?,
+            })
+        })
+        .collect()
+}
+
+/// Print the intervals of a file.
+pub fn print_stats(path: &str, stats: &[MeasureStats]) {
+    let interval = |(low, high): (f64, f64)| format!("[{:.4e}, {:.4e}]", low, high);
+
+    println!(
+        "{} ({} runs)",
+        path,
+        stats.first().map_or(0, |s| s.summary.n)
+    );
+    println!(
+        "{:>8} {:>12} {:>26} {:>26} {:>26}",
+        "measure", "mean", "student-t", "percentile", "bca"
+    );
+    for stats in stats {
+        println!(
+            "{:>8} {:>12.4e} {:>26} {:>26} {:>26}",
+            stats.measure,
+            stats.summary.mean,
+            interval(stats.summary.ci()),
+            interval(stats.bootstrap.percentile),
+            interval(stats.bootstrap.bca),
+        );
+    }
+}
+
+/// Print the tests of a difference between two files.
+pub fn print_tests(tests: &[MeasureTests]) {
+    println!(
+        "{:>8} {:>8} {:>12} {:>10} {:>8} {:>10} {:>10}",
+        "measure", "test", "difference", "t", "dof", "p-value", "effect"
+    );
+    for tests in tests {
+        let results = [
+            ("paired", tests.paired.as_ref()),
+            ("welch", Some(&tests.welch)),
+        ];
+        for (name, result) in results {
+            match result {
+                Some(result) => println!(
+                    "{:>8} {:>8} {:>12.4e} {:>10.3} {:>8.1} {:>10.4} {:>10.3}",
+                    tests.measure,
+                    name,
+                    result.difference,
+                    result.t,
+                    result.dof,
+                    result.p_value,
+                    result.effect_size
+                ),
+                None => println!("{:>8} {:>8} {:>12}", tests.measure, name, "unequal runs"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::source;
+
+    use super::*;
+
+    fn perf(blocked: &[f64]) -> Vec<PerfMeasure> {
+        blocked
+            .iter()
+            .map(|b| PerfMeasure {
+                blocked_calls: *b,
+                dropped_calls: b / 2.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_describe_and_compare() {
+        let a = perf(&[0.01, 0.02, 0.0, 0.03, 0.01, 0.02]);
+        let stats = describe(&a, 1000, 0.95, &mut source::default(2)).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert!((stats[0].summary.mean - 0.015).abs() < 1e-12);
+        assert_eq!(stats[0].bootstrap.estimate, stats[0].summary.mean);
+        assert!(stats[1].bootstrap.bca.0 >= 0.0);
+
+        // a file against itself does not differ
+        let same = compare(&a, &a).unwrap();
+        assert!(same
+            .iter()
+            .all(|t| t.paired.unwrap().p_value == 1.0 && t.welch.p_value == 1.0));
+
+        // shifting every run is a certain difference when paired
+        let b = perf(&[0.02, 0.03, 0.01, 0.04, 0.02, 0.03]);
+        let shifted = compare(&b, &a).unwrap();
+        let paired = shifted[0].paired.unwrap();
+        assert!((paired.difference - 0.01).abs() < 1e-12 && paired.p_value < 1e-6);
+        assert!(shifted[0].welch.p_value > 0.05);
+
+        assert!(compare(&a, &b[..3]).unwrap()[0].paired.is_none());
+    }
+}
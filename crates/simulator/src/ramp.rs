@@ -0,0 +1,465 @@
+//! Highway on-ramps and off-ramps.
+//!
+//! Ramps sit on the boundary between two adjacent base stations.
+//! Vehicles passing a ramp may leave the highway, taking their call off-network,
+//! and new vehicles may join the highway at a ramp with a call already in progress.
+//! An entering call is handed over from off the highway: it is dropped, not blocked,
+//! when the station it drives into has no channel for it.
+
+use std::{fmt::Display, str::FromStr};
+
+use probability::{distribution, source::Source};
+use serde::Serialize;
+use simulator_core::{SingleVariateIterator, SplitSource, Variate};
+
+use crate::{
+    event::{BaseStationIdx, CellEvent, CellEventType, RelativeVehiclePosition, VehicleDirection},
+    generator::calculate_ttn,
+    FloatingPoint,
+};
+
+/// What happens to a call after its vehicle leaves the highway.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum OffHighway {
+    /// The call is released from the highway system and no longer tracked.
+    #[default]
+    Release,
+
+    /// The call is handed over to the off-highway network, which keeps its own statistics.
+    Sink,
+}
+
+/// A ramp on the boundary between a base station and its eastern neighbour.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    /// Station on the western side of the ramp.
+    pub after: BaseStationIdx,
+
+    /// Probability that a vehicle passing the ramp leaves the highway.
+    pub exit_prob: FloatingPoint,
+
+    /// Rate of vehicles entering the highway at this ramp, in calls per second.
+    pub entry_rate: FloatingPoint,
+
+    /// Where calls go once their vehicle has left the highway.
+    pub off_highway: OffHighway,
+}
+
+/// Statistics for calls that left the highway through an off-ramp.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct OffHighwayStats {
+    /// Number of calls released from the highway system
+    pub released: usize,
+
+    /// Number of calls handed over to the off-highway sink
+    pub sink_calls: usize,
+
+    /// Total call time handed over to the off-highway sink, in seconds
+    pub sink_call_time: FloatingPoint,
+}
+
+/// Generator iterator for call events of vehicles entering the highway at ramps.
+///
+/// Entries at every ramp are Poisson processes, so they are generated as a single
+/// superposed stream and thinned to a ramp afterwards.
+pub struct RampEntryGenerator<S>
+where
+    S: Source,
+{
+    ramps: Vec<Ramp>,
+
+    time: FloatingPoint,
+
+    count: usize,
+
+    run: usize,
+
+    /// Total entry rate over all ramps
+    total_rate: FloatingPoint,
+
+    inter_arrival: SingleVariateIterator<distribution::Exponential, S>,
+    ramp_choice: SingleVariateIterator<distribution::Uniform, S>,
+    call_duration: SingleVariateIterator<Variate, S>,
+    vehicle_velocity: SingleVariateIterator<Variate, S>,
+    vehicle_direction: SingleVariateIterator<Variate, S>,
+    exit_ramp: SingleVariateIterator<distribution::Uniform, S>,
+}
+
+/// Parses a ramp from `<after>:<exit_prob>[:<entry_rate>[:<release|sink>]]`,
+/// where `after` is the 1-based station on the western side of the ramp.
+impl FromStr for Ramp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').map(str::trim).collect::<Vec<_>>();
+
+        if !(2..=4).contains(&parts.len()) {
+            return Err(format!(
+                "expected <after>:<exit_prob>[:<entry_rate>[:<release|sink>]], got '{}'",
+                s
+            ));
+        }
+
+        let after = parts[0]
+            .parse::<usize>()
+            .map_err(|e| format!("invalid ramp station '{}': {}", parts[0], e))?;
+
+        if !(1..=19).contains(&after) {
+            return Err(format!(
+                "ramp must sit between two stations, after station 1 to 19, got {}",
+                after
+            ));
+        }
+
+        let exit_prob = parts[1]
+            .parse::<FloatingPoint>()
+            .map_err(|e| format!("invalid exit probability '{}': {}", parts[1], e))?;
+
+        if !(0.0..=1.0).contains(&exit_prob) {
+            return Err(format!(
+                "exit probability must be within [0, 1], got {}",
+                exit_prob
+            ));
+        }
+
+        let entry_rate = match parts.get(2) {
+            Some(rate) => rate
+                .parse::<FloatingPoint>()
+                .map_err(|e| format!("invalid entry rate '{}': {}", rate, e))?,
+            None => 0.0,
+        };
+
+        if !(entry_rate >= 0.0 && entry_rate.is_finite()) {
+            return Err(format!(
+                "entry rate must be finite and non-negative, got {}",
+                entry_rate
+            ));
+        }
+
+        let off_highway = match parts.get(3) {
+            Some(&"release") | None => OffHighway::Release,
+            Some(&"sink") => OffHighway::Sink,
+            Some(other) => {
+                return Err(format!(
+                    "off-highway mode must be 'release' or 'sink', got '{}'",
+                    other
+                ))
+            }
+        };
+
+        Ok(Self {
+            after: BaseStationIdx::try_from(after - 1)?,
+            exit_prob,
+            entry_rate,
+            off_highway,
+        })
+    }
+}
+
+impl Serialize for Ramp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Ramp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let spec = String::deserialize(deserializer)?;
+        spec.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Display for Ramp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.after as usize + 1,
+            self.exit_prob,
+            self.entry_rate,
+            match self.off_highway {
+                OffHighway::Release => "release",
+                OffHighway::Sink => "sink",
+            }
+        )
+    }
+}
+
+impl OffHighwayStats {
+    /// Accumulate the statistics of another run into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.released += other.released;
+        self.sink_calls += other.sink_calls;
+        self.sink_call_time += other.sink_call_time;
+    }
+}
+
+/// Check that no two ramps sit on the same boundary, which would make the exit probability
+/// there ambiguous.
+pub fn check_unique(ramps: &[Ramp]) -> Result<(), String> {
+    match ramps
+        .iter()
+        .enumerate()
+        .find(|(idx, ramp)| ramps[..*idx].iter().any(|r| r.after == ramp.after))
+    {
+        Some((_, ramp)) => Err(format!(
+            "more than one ramp after station {}",
+            ramp.after as usize + 1
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Boundary between two adjacent stations, identified by the station on its western side.
+pub fn boundary(station: BaseStationIdx, next: BaseStationIdx) -> BaseStationIdx {
+    match (station as usize) < (next as usize) {
+        true => station,
+        false => next,
+    }
+}
+
+/// Choose the ramp a vehicle exits the highway at, if any.
+///
+/// Ramps are visited in the order the vehicle passes them, and a single uniform sample
+/// is rescaled at every ramp it does not exit at. This keeps the choice monotone in `u`,
+/// so antithetic samples exit at ramps on opposite ends of the highway.
+///
+/// The returned station is the western side of the exit ramp.
+pub fn choose_exit(
+    ramps: &[Ramp],
+    station: BaseStationIdx,
+    direction: VehicleDirection,
+    u: FloatingPoint,
+) -> Option<BaseStationIdx> {
+    let mut downstream = ramps
+        .iter()
+        .filter(|ramp| match direction {
+            VehicleDirection::WestToEast => ramp.after as usize >= station as usize,
+            VehicleDirection::EastToWest => (ramp.after as usize) < station as usize,
+        })
+        .collect::<Vec<_>>();
+
+    downstream.sort_by_key(|ramp| match direction {
+        VehicleDirection::WestToEast => ramp.after as isize,
+        VehicleDirection::EastToWest => -(ramp.after as isize),
+    });
+
+    let mut u = u;
+    for ramp in downstream {
+        if u < ramp.exit_prob {
+            return Some(ramp.after);
+        }
+
+        u = (u - ramp.exit_prob) / (1.0 - ramp.exit_prob);
+    }
+
+    None
+}
+
+/// Merge ramp entries into the main call initiation stream.
+///
+/// Events are sorted by time. Main stream indices are kept and entries are indexed after them
+/// in order of arrival, so the largest index of a call initiation is the number of calls
+/// initiated on the highway and the largest index overall counts ramp entries as well.
+pub fn merge_entries(main: Vec<CellEvent>, mut entries: Vec<CellEvent>) -> Vec<CellEvent> {
+    if entries.is_empty() {
+        return main;
+    }
+
+    let offset = main.iter().map(|ev| ev.idx).max().unwrap_or_default();
+    entries.sort();
+    for (idx, ev) in entries.iter_mut().enumerate() {
+        ev.idx = offset + idx + 1;
+    }
+
+    let mut merged = main;
+    merged.extend(entries);
+    merged.sort();
+
+    merged
+}
+
+impl<S> RampEntryGenerator<S>
+where
+    S: SplitSource,
+{
+    /// Create an entry generator for the given ramps.
+    ///
+    /// Call durations, velocities and directions are drawn from the same distributions as
+    /// call initiations on the highway, each on its own stream split off `source`.
+    pub fn new(
+        run: usize,
+        mut source: S,
+        ramps: Vec<Ramp>,
+        call_duration: Variate,
+        vehicle_velocity: Variate,
+        vehicle_direction: Variate,
+    ) -> Self {
+        let total_rate = ramps.iter().map(|r| r.entry_rate).sum::<FloatingPoint>();
+
+        Self {
+            ramps,
+            time: 0.0,
+            count: 0,
+            run,
+            total_rate,
+            inter_arrival: SingleVariateIterator::new(
+                // a zero rate is never sampled, see `next`
+                distribution::Exponential::new(match total_rate > 0.0 {
+                    true => total_rate as f64,
+                    false => 1.0,
+                }),
+                source.split(),
+            ),
+            ramp_choice: SingleVariateIterator::new(
+                distribution::Uniform::new(0.0, 1.0),
+                source.split(),
+            ),
+            call_duration: SingleVariateIterator::new(call_duration, source.split()),
+            vehicle_velocity: SingleVariateIterator::new(vehicle_velocity, source.split()),
+            vehicle_direction: SingleVariateIterator::new(vehicle_direction, source.split()),
+            exit_ramp: SingleVariateIterator::new(distribution::Uniform::new(0.0, 1.0), source),
+        }
+    }
+}
+
+impl<S> Iterator for RampEntryGenerator<S>
+where
+    S: Source,
+{
+    type Item = CellEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.total_rate <= 0.0 {
+            return None;
+        }
+
+        let inter_arr = self.inter_arrival.next()?;
+        let choice = self.ramp_choice.next()? as FloatingPoint * self.total_rate;
+        let call_dur = self.call_duration.next()? as FloatingPoint;
+        let velocity = self.vehicle_velocity.next()? as FloatingPoint;
+        let direction = self.vehicle_direction.next()?;
+        let exit_u = self.exit_ramp.next()? as FloatingPoint;
+
+        // thin the superposed stream to a single ramp
+        let mut acc = 0.0;
+        let ramp = self
+            .ramps
+            .iter()
+            .filter(|r| r.entry_rate > 0.0)
+            .find(|r| {
+                acc += r.entry_rate;
+                choice < acc
+            })
+            .or_else(|| self.ramps.iter().rev().find(|r| r.entry_rate > 0.0))?;
+
+        let direction = match direction > 0.5 {
+            true => VehicleDirection::WestToEast,
+            false => VehicleDirection::EastToWest,
+        };
+
+        // the vehicle joins the highway at the edge of the station it drives into
+        let (station, position) = match direction {
+            VehicleDirection::WestToEast => (
+                ramp.after.next_station(direction)?,
+                RelativeVehiclePosition::WestEnd,
+            ),
+            VehicleDirection::EastToWest => (ramp.after, RelativeVehiclePosition::EastEnd),
+        };
+
+        self.count += 1;
+        self.time += inter_arr as FloatingPoint;
+
+        Some(CellEvent {
+            idx: self.count,
+            run: self.run as u32,
+            time: self.time,
+            ty: CellEventType::Enter,
+            remaining_time: call_dur,
+            ttn: calculate_ttn(call_dur, position.to_float(), velocity, direction),
+            velocity,
+            direction,
+            station,
+            position,
+            exit: choose_exit(&self.ramps, station, direction, exit_u),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ramp() {
+        let ramp: Ramp = "5:0.25".parse().unwrap();
+        assert_eq!(ramp.after, BaseStationIdx::Five);
+        assert_eq!(ramp.exit_prob, 0.25);
+        assert_eq!(ramp.entry_rate, 0.0);
+        assert_eq!(ramp.off_highway, OffHighway::Release);
+
+        let ramp: Ramp = "19:0.1:0.5:sink".parse().unwrap();
+        assert_eq!(ramp.after, BaseStationIdx::Nineteen);
+        assert_eq!(ramp.entry_rate, 0.5);
+        assert_eq!(ramp.off_highway, OffHighway::Sink);
+        assert_eq!(ramp.to_string().parse::<Ramp>().unwrap(), ramp);
+
+        assert!("20:0.1".parse::<Ramp>().is_err());
+        assert!("0:0.1".parse::<Ramp>().is_err());
+        assert!("5:1.5".parse::<Ramp>().is_err());
+        assert!("5:0.1:-1".parse::<Ramp>().is_err());
+        assert!("5:0.1:1:elsewhere".parse::<Ramp>().is_err());
+    }
+
+    #[test]
+    fn test_check_unique() {
+        let ramps = ["3:0.5", "10:0.5"].map(|r| r.parse::<Ramp>().unwrap());
+        assert!(check_unique(&ramps).is_ok());
+
+        let ramps = ["3:0.5", "10:0.5", "3:0.1:1"].map(|r| r.parse::<Ramp>().unwrap());
+        assert!(check_unique(&ramps)
+            .unwrap_err()
+            .contains("after station 3"));
+    }
+
+    #[test]
+    fn test_choose_exit() {
+        let ramps = vec![
+            "3:0.5".parse::<Ramp>().unwrap(),
+            "10:0.5".parse::<Ramp>().unwrap(),
+        ];
+
+        // vehicles only pass ramps ahead of them
+        let dir = VehicleDirection::WestToEast;
+        assert_eq!(
+            choose_exit(&ramps, BaseStationIdx::One, dir, 0.1),
+            Some(BaseStationIdx::Three)
+        );
+        assert_eq!(
+            choose_exit(&ramps, BaseStationIdx::One, dir, 0.6),
+            Some(BaseStationIdx::Ten)
+        );
+        assert_eq!(choose_exit(&ramps, BaseStationIdx::One, dir, 0.9), None);
+        assert_eq!(
+            choose_exit(&ramps, BaseStationIdx::Five, dir, 0.1),
+            Some(BaseStationIdx::Ten)
+        );
+
+        let dir = VehicleDirection::EastToWest;
+        assert_eq!(
+            choose_exit(&ramps, BaseStationIdx::Twenty, dir, 0.1),
+            Some(BaseStationIdx::Ten)
+        );
+        assert_eq!(
+            choose_exit(&ramps, BaseStationIdx::Twenty, dir, 0.6),
+            Some(BaseStationIdx::Three)
+        );
+        assert_eq!(choose_exit(&ramps, BaseStationIdx::Three, dir, 0.1), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Regenerative estimates of blocked and dropped calls from one long run.
+//!
+//! The highway regenerates whenever every station is idle, since the calls that follow are
+//! independent of everything before. The run is split into cycles between these points, and
+//! the fractions of blocked and dropped calls are ratios of their counts to the calls initiated
+//! over the cycles, with ramp entries counted among the calls that can be dropped, so no warmup
+//! has to be chosen.
+//!
+//! Idle instants are regeneration points only when inter-arrival times are memoryless, so runs
+//! with any other inter-arrival distribution than an exponential without a location are rejected.
+//!
+//! Every station is idle only when few calls are in progress, so cycles are frequent at light
+//! loads and may never complete at the default load.
+
+use serde::Serialize;
+use simulator_core::regenerative::{ratio_estimate, RatioEstimate};
+
+use crate::{
+    base_station::StationResponse,
+    event::{CellEventResult, CellEventType},
+    experiment::{run_source, Scenario},
+    generator::as_exponential,
+    FloatingPoint,
+};
+
+/// Counts of a regeneration cycle.
+#[derive(Clone, Debug, Serialize)]
+pub struct Cycle {
+    /// Time of the first event of the cycle
+    pub start_time: FloatingPoint,
+
+    pub initiated_calls: usize,
+    pub entered_calls: usize,
+    pub blocked_calls: usize,
+    pub dropped_calls: usize,
+}
+
+/// Ratio estimates over the cycles of a run.
+#[derive(Clone, Debug)]
+pub struct RegenerativeReport {
+    pub cycles: Vec<Cycle>,
+    pub blocked: RatioEstimate,
+    pub dropped: RatioEstimate,
+}
+
+impl Cycle {
+    /// Count the calls of the results of a cycle.
+    pub fn from_results(results: &[CellEventResult]) -> Self {
+        let count = |matches: fn(&CellEventResult) -> bool| {
+            results.iter().filter(|res| matches(res)).count()
+        };
+
+        Self {
+            start_time: results.first().map(|res| res.time).unwrap_or_default(),
+            initiated_calls: count(|res| matches!(res.ty, CellEventType::Initiate)),
+            entered_calls: count(|res| matches!(res.ty, CellEventType::Enter)),
+            blocked_calls: count(|res| matches!(res.outcome, StationResponse::Blocked)),
+            dropped_calls: count(|res| matches!(res.outcome, StationResponse::Terminated)),
+        }
+    }
+}
+
+/// Simulate run `run` of `scenario` and estimate blocked and dropped calls over its cycles.
+pub fn regenerative_run(
+    scenario: &Scenario,
+    run: usize,
+    seed: u64,
+    confidence: f64,
+) -> Result<RegenerativeReport, String> {
+    let model = scenario.input_model.clone().or_default();
+    let inter_arrival = model.inter_arrival.expect("defaulted");
+    if !as_exponential(&inter_arrival).is_some_and(|exp| exp.loc() == 0.0) {
+        return Err(format!(
+            "regeneration needs an exponential inter-arrival without a location, got {}",
+            inter_arrival
+        ));
+    }
+
+    let cycles = scenario.simulate_cycles(run, run_source(seed, run));
+    if cycles.len() < 2 {
+        return Err(format!(
+            "only {} complete regeneration cycles, the highway is rarely idle at this load",
+            cycles.len()
+        ));
+    }
+
+    let column =
+        |value: fn(&Cycle) -> usize| cycles.iter().map(|c| value(c) as f64).collect::<Vec<_>>();
+    let initiated = column(|c| c.initiated_calls);
+    let blocked = ratio_estimate(&column(|c| c.blocked_calls), &initiated, confidence)?;
+    let calls = column(|c| c.initiated_calls + c.entered_calls);
+    let dropped = ratio_estimate(&column(|c| c.dropped_calls), &calls, confidence)?;
+
+    Ok(RegenerativeReport {
+        cycles,
+        blocked,
+        dropped,
+    })
+}
+
+/// Print the ratio estimates and cycle statistics.
+pub fn print_report(report: &RegenerativeReport) {
+    let calls = report
+        .cycles
+        .iter()
+        .map(|c| c.initiated_calls)
+        .sum::<usize>();
+    println!(
+        "{} cycles, {:.2} calls per cycle",
+        report.cycles.len(),
+        calls as f64 / report.cycles.len() as f64
+    );
+
+    println!("{:>8} {:>12} {:>26}", "measure", "ratio", "ci");
+    for (name, estimate) in [("blocked", &report.blocked), ("dropped", &report.dropped)] {
+        let (low, high) = estimate.ci();
+        println!(
+            "{:>8} {:>12.4e} {:>26}",
+            name,
+            estimate.ratio,
+            format!("[{:.4e}, {:.4e}]", low, high)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::InputModel;
+
+    #[test]
+    fn test_regenerative_run() {
+        // at a light load the highway is often idle
+        let model = InputModel {
+            inter_arrival: Some("exponential(200)".parse().unwrap()),
+            ..Default::default()
+        };
+        let scenario = Scenario::new(model, 0, 2000, 0);
+        let report = regenerative_run(&scenario, 0, 4, 0.95).unwrap();
+
+        assert!(report.cycles.len() > 100, "{} cycles", report.cycles.len());
+        assert!(report.cycles.iter().all(|c| c.initiated_calls > 0));
+        assert!(report
+            .cycles
+            .windows(2)
+            .all(|w| w[0].start_time < w[1].start_time));
+        // nothing is blocked with idle stations
+        assert_eq!(report.blocked.ratio, 0.0);
+
+        let calls = report
+            .cycles
+            .iter()
+            .map(|c| c.initiated_calls)
+            .sum::<usize>();
+        assert!(calls <= 2000);
+
+        // idle instants are not regeneration points without memoryless arrivals
+        let model = InputModel {
+            inter_arrival: Some("exponential(200, 5)".parse().unwrap()),
+            ..Default::default()
+        };
+        let scenario = Scenario::new(model, 0, 2000, 0);
+        assert!(regenerative_run(&scenario, 0, 4, 0.95).is_err());
+
+        // the default load is too heavy to regenerate within a short run
+        let scenario = Scenario::new(InputModel::default(), 0, 300, 0);
+        assert!(regenerative_run(&scenario, 0, 4, 0.95).is_err());
+    }
+}
@@ -0,0 +1,445 @@
+//! Trace-driven replay of call initiations.
+//!
+//! Reads call initiations from either the course's test data
+//! (`Arrival no,Arrival time (sec),Base station ,Call duration (sec),velocity (km/h)`)
+//! or a file written by the `generate` subcommand, so the model can be validated against recorded
+//! arrivals. Ramp entries in a generated file are replayed as well.
+
+use std::{fs, io, path::Path, str::FromStr};
+
+use probability::{distribution::Sample, source::Source};
+
+use crate::{
+    event::{BaseStationIdx, CellEvent, CellEventType, RelativeVehiclePosition, VehicleDirection},
+    generator::{calculate_ttn, InputModel, VEHICLE_LOC_DIST},
+    ramp::{choose_exit, merge_entries, Ramp},
+    FloatingPoint,
+};
+
+/// Position used for call initiations without a position column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PositionDefault {
+    /// Fixed position in meters, from the western end of the station
+    Fixed(FloatingPoint),
+
+    /// Drawn from the vehicle position distribution of the input model
+    Random,
+}
+
+/// Direction used for call initiations without a direction column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirectionDefault {
+    WestToEast,
+    EastToWest,
+
+    /// Alternate between directions, starting west to east
+    Alternate,
+
+    /// Drawn from the vehicle direction distribution of the input model
+    Random,
+}
+
+/// Defaults for columns missing from a trace.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayDefaults {
+    pub position: PositionDefault,
+    pub direction: DirectionDefault,
+}
+
+/// Column positions of a trace, matched by header name.
+#[derive(Debug)]
+struct TraceColumns {
+    run: Option<usize>,
+    ty: Option<usize>,
+    time: usize,
+    station: usize,
+    duration: usize,
+    velocity: usize,
+    position: Option<usize>,
+    direction: Option<usize>,
+    exit: Option<usize>,
+}
+
+impl FromStr for PositionDefault {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "random" => Ok(Self::Random),
+            pos => {
+                let pos = pos
+                    .parse::<FloatingPoint>()
+                    .map_err(|_| format!("expected 'random' or meters, got '{}'", s))?;
+
+                match (VEHICLE_LOC_DIST.0..=VEHICLE_LOC_DIST.1).contains(&pos) {
+                    true => Ok(Self::Fixed(pos)),
+                    false => Err(format!(
+                        "position must be within {:?} meters, got {}",
+                        VEHICLE_LOC_DIST, pos
+                    )),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for DirectionDefault {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "west-to-east" => Ok(Self::WestToEast),
+            "east-to-west" => Ok(Self::EastToWest),
+            "alternate" => Ok(Self::Alternate),
+            "random" => Ok(Self::Random),
+            other => Err(format!(
+                "expected west-to-east, east-to-west, alternate or random, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+impl TraceColumns {
+    fn from_headers(headers: &csv::StringRecord) -> Result<Self, String> {
+        let find = |names: &[&str]| {
+            headers
+                .iter()
+                .position(|h| names.iter().any(|name| h.trim().eq_ignore_ascii_case(name)))
+        };
+
+        let required = |names: &[&str]| {
+            find(names).ok_or_else(|| {
+                format!(
+                    "missing column {:?}, found {:?}",
+                    names,
+                    headers.iter().map(str::trim).collect::<Vec<_>>()
+                )
+            })
+        };
+
+        Ok(Self {
+            run: find(&["run"]),
+            ty: find(&["ty"]),
+            time: required(&["time", "Arrival time (sec)"])?,
+            station: required(&["station", "Base station"])?,
+            duration: required(&["remaining_time", "Call duration (sec)"])?,
+            velocity: required(&["velocity", "velocity (km/h)"])?,
+            position: find(&["position"]),
+            direction: find(&["direction"]),
+            exit: find(&["exit"]),
+        })
+    }
+}
+
+/// Read call initiations from a trace file, grouped by simulation run.
+///
+/// Traces without a run column hold a single run.
+/// Randomised defaults and exits at ramps are drawn from `source`.
+pub fn read_trace<P, S>(
+    path: P,
+    defaults: ReplayDefaults,
+    model: &InputModel,
+    ramps: &[Ramp],
+    source: S,
+) -> io::Result<Vec<Vec<CellEvent>>>
+where
+    P: AsRef<Path>,
+    S: Source,
+{
+    let file = fs::File::open(&path)?;
+
+    read_trace_from(file, defaults, model, ramps, source).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.as_ref().display(), e),
+        )
+    })
+}
+
+fn read_trace_from<R, S>(
+    reader: R,
+    defaults: ReplayDefaults,
+    model: &InputModel,
+    ramps: &[Ramp],
+    mut source: S,
+) -> Result<Vec<Vec<CellEvent>>, String>
+where
+    R: io::Read,
+    S: Source,
+{
+    let model = model.clone().or_default();
+    let position_dist = model.vehicle_position.unwrap();
+    let direction_dist = model.vehicle_direction.unwrap();
+
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let columns = TraceColumns::from_headers(&headers)?;
+
+    let mut runs: Vec<Vec<CellEvent>> = Vec::new();
+
+    for (row, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let row = row + 1;
+
+        let field = |idx: usize| record.get(idx).unwrap_or_default().trim();
+        let number = |idx: usize, what: &str| {
+            field(idx)
+                .parse::<FloatingPoint>()
+                .map_err(|_| format!("row {}: invalid {} '{}'", row, what, field(idx)))
+        };
+
+        // only calls arriving on the highway are replayed, the simulation produces the rest
+        let ty = match columns.ty.map(field) {
+            None | Some("Initiate") => CellEventType::Initiate,
+            Some("Enter") => CellEventType::Enter,
+            Some(_) => continue,
+        };
+
+        let run = match columns.run {
+            Some(run) => field(run)
+                .parse::<u32>()
+                .map_err(|_| format!("row {}: invalid run '{}'", row, field(run)))?,
+            None => 1,
+        };
+
+        let time = number(columns.time, "time")?;
+        let remaining_time = number(columns.duration, "call duration")?;
+        let velocity = number(columns.velocity, "velocity")?;
+
+        if !(time.is_finite() && time >= 0.0) {
+            return Err(format!(
+                "row {}: time must be non-negative, got {}",
+                row, time
+            ));
+        }
+        if !(remaining_time.is_finite() && remaining_time >= 0.0) {
+            return Err(format!(
+                "row {}: call duration must be non-negative, got {}",
+                row, remaining_time
+            ));
+        }
+        if !(velocity.is_finite() && velocity > 0.0) {
+            return Err(format!("row {}: velocity must be positive", row));
+        }
+
+        let station = match field(columns.station).parse::<usize>() {
+            Ok(station) if (1..=20).contains(&station) => unsafe {
+                std::mem::transmute::<usize, BaseStationIdx>(station - 1)
+            },
+            _ => {
+                return Err(format!(
+                    "row {}: station must be within 1 to 20, got '{}'",
+                    row,
+                    field(columns.station)
+                ))
+            }
+        };
+
+        let position = match columns.position.map(field) {
+            Some("WestEnd") => RelativeVehiclePosition::WestEnd,
+            Some("EastEnd") => RelativeVehiclePosition::EastEnd,
+            Some(_) => match number(columns.position.unwrap(), "position")? {
+                pos if (VEHICLE_LOC_DIST.0..=VEHICLE_LOC_DIST.1).contains(&pos) => {
+                    RelativeVehiclePosition::Other(pos)
+                }
+                pos => {
+                    return Err(format!(
+                        "row {}: position must be within {:?} meters, got {}",
+                        row, VEHICLE_LOC_DIST, pos
+                    ))
+                }
+            },
+            None => match defaults.position {
+                PositionDefault::Fixed(pos) => RelativeVehiclePosition::Other(pos),
+                PositionDefault::Random => RelativeVehiclePosition::Other(
+                    position_dist.sample(&mut source) as FloatingPoint,
+                ),
+            },
+        };
+
+        let direction = match columns.direction.map(field) {
+            Some("WestToEast") => VehicleDirection::WestToEast,
+            Some("EastToWest") => VehicleDirection::EastToWest,
+            Some(other) => return Err(format!("row {}: invalid direction '{}'", row, other)),
+            None => match defaults.direction {
+                DirectionDefault::WestToEast => VehicleDirection::WestToEast,
+                DirectionDefault::EastToWest => VehicleDirection::EastToWest,
+                DirectionDefault::Alternate => match row % 2 {
+                    1 => VehicleDirection::WestToEast,
+                    _ => VehicleDirection::EastToWest,
+                },
+                DirectionDefault::Random => match direction_dist.sample(&mut source) > 0.5 {
+                    true => VehicleDirection::WestToEast,
+                    false => VehicleDirection::EastToWest,
+                },
+            },
+        };
+
+        let exit = match columns.exit.map(field) {
+            Some("") => None,
+            Some(exit) => match exit.parse::<usize>() {
+                Ok(exit) if ramps.iter().any(|r| r.after as usize + 1 == exit) => {
+                    Some(unsafe { std::mem::transmute::<usize, BaseStationIdx>(exit - 1) })
+                }
+                _ => {
+                    return Err(format!(
+                        "row {}: exit ramp '{}' is not a configured ramp",
+                        row, exit
+                    ))
+                }
+            },
+            None => choose_exit(
+                ramps,
+                station,
+                direction,
+                source.read::<f64>() as FloatingPoint,
+            ),
+        };
+
+        let events = match runs.iter_mut().find(|evs| evs[0].run == run) {
+            Some(events) => events,
+            None => {
+                runs.push(Vec::new());
+                runs.last_mut().unwrap()
+            }
+        };
+
+        events.push(CellEvent {
+            idx: 0,
+            run,
+            time,
+            ty,
+            remaining_time,
+            ttn: calculate_ttn(remaining_time, position.to_float(), velocity, direction),
+            velocity,
+            direction,
+            station,
+            position,
+            exit,
+        });
+    }
+
+    for events in runs.iter_mut() {
+        let (mut initiations, entries): (Vec<_>, Vec<_>) = std::mem::take(events)
+            .into_iter()
+            .partition(|ev| matches!(ev.ty, CellEventType::Initiate));
+        initiations.sort();
+        for (idx, ev) in initiations.iter_mut().enumerate() {
+            ev.idx = idx + 1;
+        }
+        *events = merge_entries(initiations, entries);
+    }
+
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::source;
+
+    use super::*;
+
+    const DEFAULTS: ReplayDefaults = ReplayDefaults {
+        position: PositionDefault::Fixed(500.0),
+        direction: DirectionDefault::Alternate,
+    };
+
+    #[test]
+    fn test_read_course_trace() {
+        let trace = "\
+Arrival no,Arrival time (sec),Base station ,Call duration (sec),velocity (km/h)
+1,0.000,18,76.134,125.934
+2,0.068,7,159.056,95.511
+3,0.559,20,179.465,127.757
+";
+
+        let runs = read_trace_from(
+            trace.as_bytes(),
+            DEFAULTS,
+            &InputModel::default(),
+            &[],
+            source::default(1),
+        )
+        .unwrap();
+
+        assert_eq!(runs.len(), 1);
+        let events = &runs[0];
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].idx, 1);
+        assert_eq!(events[0].station, BaseStationIdx::Eighteen);
+        assert_eq!(events[2].station, BaseStationIdx::Twenty);
+        assert_eq!(events[1].remaining_time, 159.056);
+        assert!(matches!(events[0].direction, VehicleDirection::WestToEast));
+        assert!(matches!(events[1].direction, VehicleDirection::EastToWest));
+        assert!(events
+            .iter()
+            .all(|ev| ev.position.to_float() == 500.0 && ev.exit.is_none()));
+    }
+
+    #[test]
+    fn test_read_generated_trace() {
+        let trace = "\
+idx,run,time,ty,remaining_time,ttn,velocity,direction,station,position,exit
+1,1,0.5,Initiate,41.9,0.19,135.9,WestToEast,6,1992.6,
+1,2,0.2,Initiate,91.1,,106.0,EastToWest,18,WestEnd,
+2,1,0.1,Initiate,105.9,28.7,127.3,WestToEast,5,982.2,7
+3,1,0.05,Enter,60.0,,90.0,WestToEast,8,WestEnd,
+3,1,0.3,Handover,60.0,,90.0,WestToEast,9,WestEnd,
+";
+
+        let ramps = ["7:0.5".parse::<Ramp>().unwrap()];
+        let runs = read_trace_from(
+            trace.as_bytes(),
+            DEFAULTS,
+            &InputModel::default(),
+            &ramps,
+            source::default(1),
+        )
+        .unwrap();
+
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].len(), 3);
+        assert_eq!(runs[1].len(), 1);
+
+        // sorted by time, ramp entries indexed after the call initiations
+        assert!(matches!(runs[0][0].ty, CellEventType::Enter));
+        assert_eq!(runs[0][0].idx, 3);
+        assert_eq!(runs[0][1].time, 0.1);
+        assert_eq!(runs[0][1].idx, 1);
+        assert_eq!(runs[0][1].exit, Some(BaseStationIdx::Seven));
+        assert_eq!(runs[0][2].idx, 2);
+        assert!(matches!(
+            runs[1][0].position,
+            RelativeVehiclePosition::WestEnd
+        ));
+    }
+
+    #[test]
+    fn test_read_invalid_trace() {
+        let read = |trace: &str| {
+            read_trace_from(
+                trace.as_bytes(),
+                DEFAULTS,
+                &InputModel::default(),
+                &[],
+                source::default(1),
+            )
+        };
+
+        assert!(read("time,station,remaining_time\n1,2,3\n").is_err());
+        assert!(read("time,station,remaining_time,velocity\n1,21,3,100\n").is_err());
+        assert!(read("time,station,remaining_time,velocity\n1,2,3,-5\n").is_err());
+        assert!(read("time,station,remaining_time,velocity\n1,2,abc,100\n").is_err());
+
+        let position = read("time,station,remaining_time,velocity,position\n1,2,30,100,2500\n");
+        assert!(position.unwrap_err().starts_with("row 1: position"));
+        let duration = read("time,station,remaining_time,velocity\n1,2,-50,100\n");
+        assert!(duration.unwrap_err().starts_with("row 1: call duration"));
+        let time = read("time,station,remaining_time,velocity\n1,2,30,100\nNaN,2,30,100\n");
+        assert!(time.unwrap_err().starts_with("row 2: time"));
+        assert!(read("time,station,remaining_time,velocity\n1,2,inf,100\n").is_err());
+        assert!(read("time,station,remaining_time,velocity\n1,2,30,NaN\n").is_err());
+    }
+}
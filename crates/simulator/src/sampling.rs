@@ -0,0 +1,279 @@
+//! Variance of the mean performance measure under different sampling schemes.
+//!
+//! Every scheme simulates the same number of runs per design, and the variance of its estimator
+//! is taken across independent designs, since stratified runs of one design are dependent.
+
+use std::fmt::Display;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+use simulator_core::{
+    qmc::{QmcSource, Sequence},
+    splitmix64,
+    stratified::StratifiedSource,
+};
+
+use crate::{
+    event::PerfMeasure,
+    experiment::{run_seed, run_source, Scenario},
+    generator::Input,
+};
+
+/// How the runs of a design draw their random numbers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sampling {
+    /// Independent runs
+    Naive,
+
+    /// Antithetic pairs, each counting as two runs
+    Antithetic,
+
+    /// Draws of the given inputs stratified across the runs of a design
+    Stratified(Vec<Input>),
+
+    /// Draws of every input stratified across the runs of a design
+    LatinHypercube,
+
+    /// Runs of a design as the points of a scrambled low-discrepancy sequence over the inputs
+    Quasi(Sequence),
+}
+
+/// Variance of a sampling scheme's estimator.
+#[derive(Clone, Debug, Serialize)]
+pub struct SamplingRow {
+    pub sampling: String,
+    pub designs: usize,
+    pub runs_per_design: usize,
+    pub blocked_mean: f64,
+    pub blocked_variance: f64,
+
+    /// Variance of independent runs over that of the scheme
+    pub blocked_efficiency: f64,
+
+    pub dropped_mean: f64,
+    pub dropped_variance: f64,
+    pub dropped_efficiency: f64,
+}
+
+impl Sampling {
+    /// Mean performance measure of design `design` (zero-based) of `runs` runs.
+    pub fn design_mean(
+        &self,
+        scenario: &Scenario,
+        seed: u64,
+        design: usize,
+        runs: usize,
+    ) -> PerfMeasure {
+        let run = |r| design * runs + r;
+        let design_seed = run_seed_of_design(seed, design);
+
+        let stratified = |inputs: &[Input], r: usize| {
+            let indices = inputs.iter().map(|i| i.stream_index()).collect::<Vec<_>>();
+            StratifiedSource::new(run_source(seed, run(r)), design_seed, r, runs)
+                .with_selected(&indices)
+        };
+
+        let perf = match self {
+            Sampling::Naive => (0..runs)
+                .into_par_iter()
+                .map(|r| scenario.simulate(r, run_source(seed, run(r))))
+                .collect::<Vec<_>>(),
+            Sampling::Antithetic => (0..runs / 2)
+                .into_par_iter()
+                .map(|r| scenario.simulate_antithetic(r, run_source(seed, run(r))))
+                .collect(),
+            Sampling::Stratified(inputs) => (0..runs)
+                .into_par_iter()
+                .map(|r| scenario.simulate(r, stratified(inputs, r)))
+                .collect(),
+            Sampling::LatinHypercube => (0..runs)
+                .into_par_iter()
+                .map(|r| scenario.simulate(r, stratified(&Input::ALL, r)))
+                .collect(),
+            Sampling::Quasi(sequence) => (0..runs)
+                .into_par_iter()
+                .map(|r| scenario.simulate(r, QmcSource::new(*sequence, design_seed, r)))
+                .collect(),
+        };
+
+        let n = perf.len() as f64;
+        perf.into_iter().fold(
+            PerfMeasure {
+                blocked_calls: 0.0,
+                dropped_calls: 0.0,
+            },
+            |a, b| a + b,
+        ) / n
+    }
+}
+
+impl Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sampling::Naive => write!(f, "naive"),
+            Sampling::Antithetic => write!(f, "antithetic"),
+            Sampling::Stratified(inputs) => {
+                let inputs = inputs.iter().map(Input::to_string).collect::<Vec<_>>();
+                write!(f, "stratified({})", inputs.join(","))
+            }
+            Sampling::LatinHypercube => write!(f, "latin-hypercube"),
+            Sampling::Quasi(sequence) => write!(f, "{}", sequence),
+        }
+    }
+}
+
+/// Seed of the stratification permutations of a design.
+fn run_seed_of_design(seed: u64, design: usize) -> u64 {
+    run_seed(splitmix64(seed), design)
+}
+
+/// Estimate the variance of every scheme over `designs` designs of `runs` runs each.
+///
+/// Efficiencies are relative to the first scheme, which is meant to be naive sampling.
+pub fn sampling_report(
+    scenario: &Scenario,
+    samplings: &[Sampling],
+    designs: usize,
+    runs: usize,
+    seed: u64,
+) -> Result<Vec<SamplingRow>, String> {
+    if designs < 2 {
+        return Err("variances need at least two designs".to_string());
+    }
+    if runs < 2 || !runs.is_multiple_of(2) {
+        return Err("runs per design must be even, for antithetic pairs".to_string());
+    }
+
+    let moments = |values: &[f64]| {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let var = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (mean, var)
+    };
+
+    let mut rows: Vec<SamplingRow> = Vec::new();
+    for sampling in samplings {
+        let means = (0..designs)
+            .map(|design| sampling.design_mean(scenario, seed, design, runs))
+            .collect::<Vec<_>>();
+        let (blocked_mean, blocked_variance) = moments(
+            &means
+                .iter()
+                .map(|p| p.blocked_calls as f64)
+                .collect::<Vec<_>>(),
+        );
+        let (dropped_mean, dropped_variance) = moments(
+            &means
+                .iter()
+                .map(|p| p.dropped_calls as f64)
+                .collect::<Vec<_>>(),
+        );
+
+        let (blocked_base, dropped_base) = rows
+            .first()
+            .map_or((blocked_variance, dropped_variance), |row| {
+                (row.blocked_variance, row.dropped_variance)
+            });
+
+        rows.push(SamplingRow {
+            sampling: sampling.to_string(),
+            designs,
+            runs_per_design: runs,
+            blocked_mean,
+            blocked_variance,
+            blocked_efficiency: blocked_base / blocked_variance,
+            dropped_mean,
+            dropped_variance,
+            dropped_efficiency: dropped_base / dropped_variance,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Print the variance and efficiency of every scheme.
+pub fn print_report(rows: &[SamplingRow]) {
+    println!(
+        "{:>40} {:>10} {:>12} {:>10} {:>10} {:>12} {:>10}",
+        "sampling", "blocked", "variance", "efficiency", "dropped", "variance", "efficiency"
+    );
+    for row in rows {
+        println!(
+            "{:>40} {:>9.4}% {:>12.4e} {:>10.2} {:>9.4}% {:>12.4e} {:>10.2}",
+            row.sampling,
+            row.blocked_mean * 100.0,
+            row.blocked_variance,
+            row.blocked_efficiency,
+            row.dropped_mean * 100.0,
+            row.dropped_variance,
+            row.dropped_efficiency
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::distribution::Distribution;
+
+    use super::*;
+    use crate::generator::{CallEventGenerator, InputModel};
+
+    #[test]
+    fn test_stratified_inputs() {
+        let runs = 10;
+        let model = InputModel::default();
+        let first_calls = |inputs: &[Input]| {
+            let indices = inputs.iter().map(|i| i.stream_index()).collect::<Vec<_>>();
+            (0..runs)
+                .map(|r| {
+                    let source =
+                        StratifiedSource::new(run_source(1, r), 7, r, runs).with_selected(&indices);
+                    CallEventGenerator::new(r + 1, source, &model)
+                        .next()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // call durations of the first call cover every decile of the distribution
+        let calls = first_calls(&[Input::CallDuration]);
+        let duration = model.clone().or_default().call_duration.unwrap();
+        let mut deciles = calls
+            .iter()
+            .map(|ev| {
+                (duration.distribution(ev.remaining_time as f64) * runs as f64).floor() as usize
+            })
+            .collect::<Vec<_>>();
+        deciles.sort();
+        assert_eq!(deciles, (0..runs).collect::<Vec<_>>());
+
+        // unselected inputs are drawn as without stratification
+        assert!(calls.iter().enumerate().all(|(r, ev)| {
+            let naive = CallEventGenerator::new(r + 1, run_source(1, r), &model)
+                .next()
+                .unwrap();
+            ev.time == naive.time && ev.velocity == naive.velocity
+        }));
+    }
+
+    #[test]
+    fn test_sampling_report() {
+        let scenario = Scenario::new(InputModel::default(), 0, 500, 0);
+        let samplings = [
+            Sampling::Naive,
+            Sampling::Antithetic,
+            Sampling::Stratified(vec![Input::InterArrival]),
+            Sampling::LatinHypercube,
+            Sampling::Quasi(Sequence::Sobol),
+        ];
+
+        let rows = sampling_report(&scenario, &samplings, 3, 4, 5).unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[4].sampling, "sobol");
+        assert_eq!(rows[0].blocked_efficiency, 1.0);
+        assert_eq!(rows[2].sampling, "stratified(inter_arrival)");
+        assert!(rows.iter().all(|row| row.dropped_mean > 0.0));
+
+        assert!(sampling_report(&scenario, &samplings, 3, 3, 5).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Full factorial parameter sweeps.
+//!
+//! Every design point is simulated with the same seeds, and summarised in one row of a tidy csv.
+
+use std::{fmt::Display, str::FromStr};
+
+use probability::distribution::Mean;
+use serde::Serialize;
+use simulator_core::{stats::Summary, Variate};
+
+use crate::experiment::Scenario;
+
+/// Levels of a factor: a comma separated list of values and inclusive ranges
+/// `start:end[:step]`, e.g. `0,1,2`, `0:3` or `0.5:2:0.25`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Levels(pub Vec<f64>);
+
+/// A combination of factor levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DesignPoint {
+    pub reserved: usize,
+    pub arrival_scale: f64,
+    pub channels: usize,
+    pub velocity_mean: f64,
+}
+
+/// Summary of the runs at a design point.
+#[derive(Clone, Debug, Serialize)]
+pub struct SweepRow {
+    pub reserved_channels: usize,
+    pub arrival_scale: f64,
+    pub channels: usize,
+    pub velocity_mean: f64,
+    pub seed: u64,
+    pub runs: usize,
+    pub blocked_mean: f64,
+    pub blocked_std_dev: f64,
+    pub blocked_ci_low: f64,
+    pub blocked_ci_high: f64,
+    pub dropped_mean: f64,
+    pub dropped_std_dev: f64,
+    pub dropped_ci_low: f64,
+    pub dropped_ci_high: f64,
+}
+
+impl Levels {
+    /// Levels as non-negative whole numbers.
+    pub fn counts(&self, factor: &str) -> Result<Vec<usize>, String> {
+        self.0
+            .iter()
+            .map(|x| match *x >= 0.0 && x.fract() == 0.0 {
+                true => Ok(*x as usize),
+                false => Err(format!("{} must be whole numbers, got {}", factor, x)),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Levels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |x: &str| {
+            x.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("invalid level '{}'", x.trim()))
+        };
+
+        let mut levels = Vec::new();
+        for item in s.split(',') {
+            let parts = item.split(':').collect::<Vec<_>>();
+            match parts.as_slice() {
+                [value] => levels.push(number(value)?),
+                [start, end] | [start, end, _] => {
+                    let (start, end) = (number(start)?, number(end)?);
+                    let step = match parts.get(2) {
+                        Some(step) => number(step)?,
+                        None => 1.0,
+                    };
+                    if step <= 0.0 || end < start {
+                        return Err(format!(
+                            "range '{}' needs start <= end and a positive step",
+                            item.trim()
+                        ));
+                    }
+
+                    let count = ((end - start) / step + 1e-9).floor() as usize;
+                    levels.extend((0..=count).map(|i| start + i as f64 * step));
+                }
+                _ => return Err(format!("invalid range '{}'", item.trim())),
+            }
+        }
+
+        match levels.iter().all(|x| x.is_finite()) {
+            true => Ok(Levels(levels)),
+            false => Err("levels must be finite".to_string()),
+        }
+    }
+}
+
+impl Display for Levels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let levels = self.0.iter().map(f64::to_string).collect::<Vec<_>>();
+        write!(f, "{}", levels.join(","))
+    }
+}
+
+impl DesignPoint {
+    /// Apply the factor levels to a base scenario.
+    ///
+    /// Arrival rates are scaled by shrinking inter-arrival times, and the velocity distribution
+    /// is scaled to the given mean.
+    pub fn scenario(&self, base: &Scenario) -> Scenario {
+        let model = base.input_model.clone().or_default();
+        let inter_arrival = model.inter_arrival.clone().unwrap();
+        let velocity = model.vehicle_velocity.clone().unwrap();
+        let base_velocity = velocity.mean();
+
+        let inter_arrival = match self.arrival_scale == 1.0 {
+            true => inter_arrival,
+            false => Variate::Scaled(Box::new(inter_arrival), 1.0 / self.arrival_scale),
+        };
+        let velocity = match self.velocity_mean == base_velocity {
+            true => velocity,
+            false => Variate::Scaled(Box::new(velocity), self.velocity_mean / base_velocity),
+        };
+
+        let mut scenario = base.clone();
+        scenario.input_model.inter_arrival = Some(inter_arrival);
+        scenario.input_model.vehicle_velocity = Some(velocity);
+        scenario.channels = self.channels;
+        scenario.reserved = self.reserved;
+        scenario
+    }
+}
+
+/// Full factorial design over the factor levels.
+pub fn design(
+    reserved: &[usize],
+    arrival_scale: &[f64],
+    channels: &[usize],
+    velocity_mean: &[f64],
+) -> Result<Vec<DesignPoint>, String> {
+    if arrival_scale.iter().any(|x| *x <= 0.0) {
+        return Err("arrival scales must be positive".to_string());
+    }
+    if velocity_mean.iter().any(|x| *x <= 0.0) {
+        return Err("velocity means must be positive".to_string());
+    }
+
+    let mut points = Vec::new();
+    for &channels in channels {
+        for &reserved in reserved {
+            if reserved >= channels {
+                return Err(format!(
+                    "{} reserved channels leave no channel for new calls out of {}",
+                    reserved, channels
+                ));
+            }
+
+            for &arrival_scale in arrival_scale {
+                for &velocity_mean in velocity_mean {
+                    points.push(DesignPoint {
+                        reserved,
+                        arrival_scale,
+                        channels,
+                        velocity_mean,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(points)
+}
+
+/// Simulate every design point with the same seeds, in parallel over runs.
+pub fn run_sweep(
+    points: &[DesignPoint],
+    base: &Scenario,
+    num_runs: usize,
+    seed: u64,
+    confidence: f64,
+) -> Vec<SweepRow> {
+    points
+        .iter()
+        .map(|point| {
+            let perf = point.scenario(base).simulate_runs(num_runs, seed);
+
+            let blocked = Summary::new(
+                &perf.iter().map(|p| p.blocked_calls).collect::<Vec<_>>(),
+                confidence,
+            );
+            let dropped = Summary::new(
+                &perf.iter().map(|p| p.dropped_calls).collect::<Vec<_>>(),
+                confidence,
+            );
+
+            let row = SweepRow {
+                reserved_channels: point.reserved,
+                arrival_scale: point.arrival_scale,
+                channels: point.channels,
+                velocity_mean: point.velocity_mean,
+                seed,
+                runs: num_runs,
+                blocked_mean: blocked.mean,
+                blocked_std_dev: blocked.std_dev,
+                blocked_ci_low: blocked.ci().0,
+                blocked_ci_high: blocked.ci().1,
+                dropped_mean: dropped.mean,
+                dropped_std_dev: dropped.std_dev,
+                dropped_ci_low: dropped.ci().0,
+                dropped_ci_high: dropped.ci().1,
+            };
+
+            println!(
+                "reserved {} scale {} channels {} velocity {:.3}: {:.4}% blocked, {:.4}% dropped",
+                row.reserved_channels,
+                row.arrival_scale,
+                row.channels,
+                row.velocity_mean,
+                row.blocked_mean * 100.0,
+                row.dropped_mean * 100.0
+            );
+
+            row
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::InputModel;
+
+    #[test]
+    fn test_parse_levels() {
+        assert_eq!("0,1,2".parse::<Levels>().unwrap().0, vec![0.0, 1.0, 2.0]);
+        assert_eq!("0:3".parse::<Levels>().unwrap().0, vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(
+            "0.5:1.5:0.5, 4".parse::<Levels>().unwrap().0,
+            vec![0.5, 1.0, 1.5, 4.0]
+        );
+        assert_eq!("0.1:0.3:0.1".parse::<Levels>().unwrap().0.len(), 3);
+
+        assert!("".parse::<Levels>().is_err());
+        assert!("1:0".parse::<Levels>().is_err());
+        assert!("0:2:0".parse::<Levels>().is_err());
+        assert!("0:1:2:3".parse::<Levels>().is_err());
+        assert!("0.5".parse::<Levels>().unwrap().counts("channels").is_err());
+    }
+
+    #[test]
+    fn test_design() {
+        let points = design(&[0, 1], &[1.0, 2.0], &[10], &[120.0]).unwrap();
+        assert_eq!(points.len(), 4);
+        assert!(points.contains(&DesignPoint {
+            reserved: 1,
+            arrival_scale: 2.0,
+            channels: 10,
+            velocity_mean: 120.0,
+        }));
+
+        assert!(design(&[10], &[1.0], &[10], &[120.0]).is_err());
+        assert!(design(&[0], &[0.0], &[10], &[120.0]).is_err());
+    }
+
+    #[test]
+    fn test_design_point_scenario() {
+        let base = Scenario::new(InputModel::default(), 0, 100, 0);
+        let point = DesignPoint {
+            reserved: 2,
+            arrival_scale: 2.0,
+            channels: 12,
+            velocity_mean: 100.0,
+        };
+
+        let scenario = point.scenario(&base);
+        let model = scenario.input_model.or_default();
+        let base = base.input_model.or_default();
+        assert_eq!((scenario.channels, scenario.reserved), (12, 2));
+        assert!(
+            (model.inter_arrival.unwrap().mean() * 2.0 - base.inter_arrival.unwrap().mean()).abs()
+                < 1e-9
+        );
+        assert!((model.vehicle_velocity.unwrap().mean() - 100.0).abs() < 1e-6);
+    }
+}
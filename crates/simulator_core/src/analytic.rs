@@ -0,0 +1,134 @@
+//! Exact results for loss systems, used to cross-check simulations.
+
+/// Erlang-B blocking probability of `channels` servers offered `load` Erlangs.
+pub fn erlang_b(load: f64, channels: usize) -> f64 {
+    (1..=channels).fold(1.0, |b, c| load * b / (c as f64 + load * b))
+}
+
+/// A single cell with guard channels reserved for handovers.
+///
+/// Busy channels form a birth-death CTMC. New calls are admitted while more than `guard`
+/// channels are free, handovers while any channel is free, and every call releases its
+/// channel at `service_rate`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GuardChannelCell {
+    pub channels: usize,
+    pub guard: usize,
+
+    /// Arrival rate of new calls
+    pub new_rate: f64,
+
+    /// Arrival rate of handovers
+    pub handover_rate: f64,
+
+    /// Channel release rate of a single call
+    pub service_rate: f64,
+}
+
+/// Stationary solution of a guard channel cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuardChannelSolution {
+    /// Probability of each number of busy channels
+    pub distribution: Vec<f64>,
+
+    /// Probability that a new call is blocked
+    pub blocking: f64,
+
+    /// Probability that a handover is dropped
+    pub dropping: f64,
+
+    /// Mean number of busy channels
+    pub carried: f64,
+}
+
+impl GuardChannelCell {
+    /// Solve the balance equations of the CTMC.
+    pub fn solve(&self) -> GuardChannelSolution {
+        let threshold = self.channels.saturating_sub(self.guard);
+
+        let mut distribution = Vec::with_capacity(self.channels + 1);
+        distribution.push(1.0);
+        for n in 0..self.channels {
+            let arrivals = match n < threshold {
+                true => self.new_rate + self.handover_rate,
+                false => self.handover_rate,
+            };
+            distribution.push(distribution[n] * arrivals / ((n + 1) as f64 * self.service_rate));
+        }
+
+        let total = distribution.iter().sum::<f64>();
+        distribution.iter_mut().for_each(|p| *p /= total);
+
+        GuardChannelSolution {
+            blocking: distribution[threshold..].iter().sum(),
+            dropping: distribution[self.channels],
+            carried: distribution
+                .iter()
+                .enumerate()
+                .map(|(n, p)| n as f64 * p)
+                .sum(),
+            distribution,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_erlang_b() {
+        assert_eq!(erlang_b(3.0, 0), 1.0);
+        assert!((erlang_b(1.0, 1) - 0.5).abs() < 1e-12);
+        assert!((erlang_b(10.0, 10) - 0.214582).abs() < 1e-6);
+        assert!((erlang_b(5.0, 10) - 0.018385).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_guard_channel_cell() {
+        // without guard channels, new calls and handovers are one poisson stream
+        let cell = GuardChannelCell {
+            channels: 10,
+            guard: 0,
+            new_rate: 4.0,
+            handover_rate: 2.0,
+            service_rate: 0.8,
+        };
+        let solution = cell.solve();
+        let expected = erlang_b(6.0 / 0.8, 10);
+        assert!((solution.blocking - expected).abs() < 1e-12);
+        assert!((solution.dropping - expected).abs() < 1e-12);
+        assert!((solution.carried - 6.0 / 0.8 * (1.0 - expected)).abs() < 1e-9);
+
+        // two channels, one guarded: p1 = p0 (l_n + l_h) / mu, p2 = p1 l_h / (2 mu)
+        let cell = GuardChannelCell {
+            channels: 2,
+            guard: 1,
+            new_rate: 1.0,
+            handover_rate: 1.0,
+            service_rate: 1.0,
+        };
+        let solution = cell.solve();
+        let p = [1.0 / 4.0, 2.0 / 4.0, 1.0 / 4.0];
+        assert!(solution
+            .distribution
+            .iter()
+            .zip(p)
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert!((solution.blocking - 0.75).abs() < 1e-12);
+        assert!((solution.dropping - 0.25).abs() < 1e-12);
+
+        // guard channels trade blocking for dropping
+        let guarded = GuardChannelCell { guard: 2, ..cell }.solve();
+        assert_eq!(guarded.blocking, 1.0);
+        let cell = GuardChannelCell {
+            channels: 10,
+            guard: 1,
+            ..cell
+        };
+        let open = GuardChannelCell { guard: 0, ..cell }.solve();
+        let guarded = cell.solve();
+        assert!(guarded.blocking > open.blocking);
+        assert!(guarded.dropping < open.dropping);
+    }
+}
@@ -0,0 +1,184 @@
+//! Bootstrap confidence intervals of a statistic of independent replications.
+//!
+//! Replications are resampled with replacement, and the interval is read off the quantiles of
+//! the resampled statistic. The percentile interval takes them as they are, while the
+//! bias-corrected and accelerated (BCa) interval shifts them for the median bias of the
+//! resamples and the skewness of the statistic, estimated by the jackknife. Both respect the
+//! bounds of the statistic, unlike Student-t intervals of skewed proportions.
+
+use probability::{
+    distribution::{Distribution, Gaussian, Inverse},
+    source::Source,
+};
+
+/// Bootstrap confidence intervals of a statistic.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct BootstrapCi {
+    /// Statistic of the replications
+    pub estimate: f64,
+
+    pub percentile: (f64, f64),
+
+    /// Bias-corrected and accelerated interval
+    pub bca: (f64, f64),
+
+    /// Median bias of the resampled statistic, as a standard normal quantile
+    pub bias_correction: f64,
+
+    /// Rate of change of the statistic's standard error with its value
+    pub acceleration: f64,
+}
+
+/// Bootstrap intervals of the mean of `samples` at `confidence`, from `resamples` resamples.
+pub fn bootstrap_mean<S: Source>(
+    samples: &[f64],
+    resamples: usize,
+    confidence: f64,
+    source: &mut S,
+) -> Result<BootstrapCi, String> {
+    let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+    bootstrap(samples, mean, resamples, confidence, source)
+}
+
+/// Bootstrap intervals of `statistic` of `samples` at `confidence`, from `resamples`
+/// resamples.
+pub fn bootstrap<S, F>(
+    samples: &[f64],
+    statistic: F,
+    resamples: usize,
+    confidence: f64,
+    source: &mut S,
+) -> Result<BootstrapCi, String>
+where
+    S: Source,
+    F: Fn(&[f64]) -> f64,
+{
+    let n = samples.len();
+    if n < 2 {
+        return Err("bootstrap intervals need at least two replications".to_string());
+    }
+    if resamples < 2 {
+        return Err("bootstrap intervals need at least two resamples".to_string());
+    }
+
+    let estimate = statistic(samples);
+    let mut resample = vec![0.0; n];
+    let mut statistics = (0..resamples)
+        .map(|_| {
+            for x in resample.iter_mut() {
+                let i = (source.read::<f64>() * n as f64) as usize;
+                *x = samples[i.min(n - 1)];
+            }
+            statistic(&resample)
+        })
+        .collect::<Vec<_>>();
+    statistics.sort_by(f64::total_cmp);
+
+    let alpha = (1.0 - confidence) / 2.0;
+    let percentile = (
+        quantile(&statistics, alpha),
+        quantile(&statistics, 1.0 - alpha),
+    );
+
+    // fraction of resamples below the estimate, counting ties as half
+    let below = statistics.iter().filter(|x| **x < estimate).count() as f64
+        + statistics.iter().filter(|x| **x == estimate).count() as f64 / 2.0;
+    let normal = Gaussian::new(0.0, 1.0);
+    let bias_correction = normal.inverse(below / resamples as f64);
+
+    // jackknife estimate of the acceleration
+    let jackknife = (0..n)
+        .map(|i| {
+            let rest = samples
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, x)| *x)
+                .collect::<Vec<_>>();
+            statistic(&rest)
+        })
+        .collect::<Vec<_>>();
+    let jack_mean = jackknife.iter().sum::<f64>() / n as f64;
+    let (cubes, squares) = jackknife.iter().fold((0.0, 0.0), |(c, s), x| {
+        let d = jack_mean - x;
+        (c + d.powi(3), s + d.powi(2))
+    });
+    let acceleration = match squares > 0.0 {
+        true => cubes / (6.0 * squares.powf(1.5)),
+        false => 0.0,
+    };
+
+    // without resamples on both sides of the estimate, there is no bias to correct
+    let bca = match bias_correction.is_finite() {
+        true => {
+            let adjusted = |z: f64| {
+                let shifted = bias_correction + z;
+                normal.distribution(bias_correction + shifted / (1.0 - acceleration * shifted))
+            };
+            (
+                quantile(&statistics, adjusted(normal.inverse(alpha))),
+                quantile(&statistics, adjusted(normal.inverse(1.0 - alpha))),
+            )
+        }
+        false => percentile,
+    };
+
+    Ok(BootstrapCi {
+        estimate,
+        percentile,
+        bca,
+        bias_correction,
+        acceleration,
+    })
+}
+
+/// Quantile of sorted values, interpolating between order statistics.
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    let h = (sorted.len() - 1) as f64 * p.clamp(0.0, 1.0);
+    let (low, high) = (h.floor() as usize, h.ceil() as usize);
+    sorted[low] + (h - low as f64) * (sorted[high] - sorted[low])
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::{
+        distribution::{Exponential, Sample},
+        source,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_bootstrap_mean() {
+        let mut source = source::default(8);
+        let exp = Exponential::new(1.0);
+
+        // intervals of skewed samples cover the true mean about as often as they should
+        let covered = (0..200)
+            .filter(|_| {
+                let samples = (0..30).map(|_| exp.sample(&mut source)).collect::<Vec<_>>();
+                let ci = bootstrap_mean(&samples, 500, 0.9, &mut source).unwrap();
+                assert!(ci.percentile.0 < ci.estimate && ci.estimate < ci.percentile.1);
+                // skewed to the right
+                assert!(ci.acceleration > 0.0);
+                ci.bca.0 < 1.0 && 1.0 < ci.bca.1
+            })
+            .count();
+        assert!((160..=195).contains(&covered), "{} of 200 covered", covered);
+    }
+
+    #[test]
+    fn test_bootstrap_bounds() {
+        // a statistic that never varies has a degenerate interval
+        let mut source = source::default(1);
+        let ci = bootstrap_mean(&[0.0; 10], 100, 0.95, &mut source).unwrap();
+        assert_eq!((ci.percentile, ci.bca), ((0.0, 0.0), (0.0, 0.0)));
+
+        // proportions stay within their bounds
+        let samples = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.01, 0.0, 0.02];
+        let ci = bootstrap_mean(&samples, 1000, 0.95, &mut source).unwrap();
+        assert!(ci.bca.0 >= 0.0 && ci.percentile.0 >= 0.0);
+
+        assert!(bootstrap_mean(&[1.0], 100, 0.95, &mut source).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Control-variate estimators of a mean over independent replications.
+//!
+//! The output of every replication is regressed on the controls centred at their known means,
+//! and the intercept estimates the output's mean with the variation explained by the controls
+//! removed.
+
+use crate::stats::{student_t_quantile, Summary};
+
+/// Mean estimated with control variates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControlledEstimate {
+    pub mean: f64,
+
+    /// Half-width of the confidence interval for the mean
+    pub half_width: f64,
+
+    /// Regression coefficient of the output on every control
+    pub coefficients: Vec<f64>,
+
+    /// Sample statistics of the output without controls
+    pub crude: Summary,
+
+    /// Fraction of the crude estimator's variance removed by the controls
+    pub variance_reduction: f64,
+}
+
+impl ControlledEstimate {
+    /// Bounds of the confidence interval.
+    pub fn ci(&self) -> (f64, f64) {
+        (self.mean - self.half_width, self.mean + self.half_width)
+    }
+}
+
+/// Estimate the mean of `outputs` with control variates.
+///
+/// `controls` holds the controls of every replication, in the same order as `control_means`.
+/// There must be more replications than controls plus one.
+pub fn controlled_mean(
+    outputs: &[f64],
+    controls: &[Vec<f64>],
+    control_means: &[f64],
+    confidence: f64,
+) -> Result<ControlledEstimate, String> {
+    let (n, q) = (outputs.len(), control_means.len());
+    if controls.len() != n || controls.iter().any(|x| x.len() != q) {
+        return Err("every replication needs a value of every control".to_string());
+    }
+    if n <= q + 1 {
+        return Err(format!(
+            "{} controls need at least {} replications, got {}",
+            q,
+            q + 2,
+            n
+        ));
+    }
+
+    // design matrix with an intercept and the centred controls
+    let rows = controls
+        .iter()
+        .map(|x| {
+            std::iter::once(1.0)
+                .chain(x.iter().zip(control_means).map(|(x, mu)| x - mu))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let gram = (0..=q)
+        .map(|i| {
+            (0..=q)
+                .map(|j| rows.iter().map(|z| z[i] * z[j]).sum())
+                .collect()
+        })
+        .collect::<Vec<Vec<f64>>>();
+    let moments = (0..=q)
+        .map(|i| rows.iter().zip(outputs).map(|(z, y)| z[i] * y).sum())
+        .collect::<Vec<f64>>();
+
+    let theta = solve(&gram, &moments).ok_or("controls are linearly dependent")?;
+    let residual = rows
+        .iter()
+        .zip(outputs)
+        .map(|(z, y)| (y - z.iter().zip(&theta).map(|(z, t)| z * t).sum::<f64>()).powi(2))
+        .sum::<f64>();
+
+    let dof = (n - q - 1) as f64;
+    let mut unit = vec![0.0; q + 1];
+    unit[0] = 1.0;
+    let variance =
+        residual / dof * solve(&gram, &unit).ok_or("controls are linearly dependent")?[0];
+
+    let crude = Summary::new(outputs, confidence);
+    let crude_variance = crude.std_dev.powi(2) / n as f64;
+
+    Ok(ControlledEstimate {
+        mean: theta[0],
+        half_width: student_t_quantile(0.5 + confidence / 2.0, dof) * variance.sqrt(),
+        coefficients: theta[1..].to_vec(),
+        crude,
+        variance_reduction: match crude_variance > 0.0 {
+            true => 1.0 - variance / crude_variance,
+            false => 0.0,
+        },
+    })
+}
+
+/// Solve a small linear system by Gaussian elimination with partial pivoting.
+fn solve(a: &[Vec<f64>], b: &[f64]) -> Option<Vec<f64>> {
+    let n = b.len();
+    let mut m = a
+        .iter()
+        .zip(b)
+        .map(|(row, b)| row.iter().copied().chain([*b]).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let scale = m.iter().flatten().fold(0.0f64, |s, x| s.max(x.abs()));
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|i, j| m[*i][col].abs().total_cmp(&m[*j][col].abs()))?;
+        if m[pivot][col].abs() <= 1e-12 * scale {
+            return None;
+        }
+        m.swap(col, pivot);
+
+        let (upper, lower) = m.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for row in lower {
+            let factor = row[col] / pivot_row[col];
+            for (x, p) in row.iter_mut().zip(pivot_row).skip(col) {
+                *x -= factor * p;
+            }
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum = (row + 1..n).map(|k| m[row][k] * x[k]).sum::<f64>();
+        x[row] = (m[row][n] - sum) / m[row][row];
+    }
+
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::{
+        distribution::{Gaussian, Sample},
+        source,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_controlled_mean() {
+        let mut source = source::default(3);
+        let control = Gaussian::new(5.0, 1.0);
+        let noise = Gaussian::new(0.0, 0.1);
+
+        let controls = (0..50)
+            .map(|_| vec![control.sample(&mut source), control.sample(&mut source)])
+            .collect::<Vec<_>>();
+        let outputs = controls
+            .iter()
+            .map(|x| 2.0 + 3.0 * (x[0] - 5.0) - (x[1] - 5.0) + noise.sample(&mut source))
+            .collect::<Vec<_>>();
+
+        let estimate = controlled_mean(&outputs, &controls, &[5.0, 5.0], 0.95).unwrap();
+        let (low, high) = estimate.ci();
+        assert!(low < 2.0 && 2.0 < high, "{:?}", estimate);
+        assert!((estimate.coefficients[0] - 3.0).abs() < 0.1);
+        assert!((estimate.coefficients[1] + 1.0).abs() < 0.1);
+        assert!(estimate.variance_reduction > 0.99);
+        assert!(estimate.half_width < estimate.crude.half_width);
+    }
+
+    #[test]
+    fn test_controlled_mean_errors() {
+        let controls = vec![vec![1.0], vec![2.0]];
+        assert!(controlled_mean(&[1.0, 2.0], &controls, &[1.5], 0.95).is_err());
+        assert!(controlled_mean(&[1.0, 2.0, 3.0], &controls, &[1.5], 0.95).is_err());
+
+        // a constant control duplicates the intercept
+        let constant = vec![vec![1.0]; 4];
+        assert!(controlled_mean(&[1.0, 2.0, 3.0, 4.0], &constant, &[1.0], 0.95).is_err());
+    }
+}
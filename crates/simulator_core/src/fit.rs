@@ -0,0 +1,423 @@
+//! Distribution fitting by maximum likelihood.
+//!
+//! Replaces fitting input distributions by hand: every candidate family is fitted to a
+//! column of data, and fits can be ranked by their log-likelihood.
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use probability::distribution;
+
+use crate::{read_csv_column, Empirical, ExponentialLoc, Variate, Weibull};
+
+/// Candidate distribution families.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    /// Exponential with a location parameter
+    Exponential,
+    Normal,
+    Uniform,
+    Lognormal,
+    Weibull,
+    /// Piecewise-linear CDF through the samples, not ranked against parametric fits
+    Empirical,
+}
+
+/// A distribution fitted to data.
+#[derive(Clone, Debug)]
+pub struct Fit {
+    pub family: Family,
+
+    /// The fitted distribution
+    pub variate: Variate,
+
+    /// Log-likelihood of the data under the fitted distribution.
+    ///
+    /// Not defined for empirical distributions.
+    pub log_likelihood: Option<f64>,
+
+    /// Number of fitted parameters
+    pub num_params: usize,
+}
+
+impl Family {
+    /// Parametric families, which are ranked against each other.
+    pub const PARAMETRIC: [Family; 5] = [
+        Family::Exponential,
+        Family::Normal,
+        Family::Uniform,
+        Family::Lognormal,
+        Family::Weibull,
+    ];
+}
+
+impl FromStr for Family {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "exponential" | "exp" => Ok(Family::Exponential),
+            "normal" | "gaussian" => Ok(Family::Normal),
+            "uniform" => Ok(Family::Uniform),
+            "lognormal" => Ok(Family::Lognormal),
+            "weibull" => Ok(Family::Weibull),
+            "empirical" => Ok(Family::Empirical),
+            other => Err(format!(
+                "unknown family '{}', expected exponential, normal, uniform, lognormal, weibull or empirical",
+                other
+            )),
+        }
+    }
+}
+
+impl Display for Family {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Family::Exponential => "exponential",
+            Family::Normal => "normal",
+            Family::Uniform => "uniform",
+            Family::Lognormal => "lognormal",
+            Family::Weibull => "weibull",
+            Family::Empirical => "empirical",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl Fit {
+    /// Akaike information criterion, lower is better.
+    pub fn aic(&self) -> Option<f64> {
+        self.log_likelihood
+            .map(|ll| 2.0 * self.num_params as f64 - 2.0 * ll)
+    }
+}
+
+impl From<Fit> for Variate {
+    fn from(value: Fit) -> Self {
+        value.variate
+    }
+}
+
+/// Fit a distribution family to data by maximum likelihood.
+pub fn fit(family: Family, data: &[f64]) -> Result<Fit, String> {
+    let n = data.len() as f64;
+
+    if data.len() < 2 {
+        return Err(format!(
+            "fitting requires at least 2 samples, got {}",
+            data.len()
+        ));
+    }
+
+    if data.iter().any(|x| !x.is_finite()) {
+        return Err("fitting requires finite samples".to_string());
+    }
+
+    let min = data.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = data.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mean = data.iter().sum::<f64>() / n;
+
+    let positive = || match min > 0.0 {
+        true => Ok(()),
+        false => Err(format!("{} fit requires positive samples", family)),
+    };
+
+    let (variate, log_likelihood, num_params): (Variate, _, _) = match family {
+        Family::Exponential => {
+            // the location estimate is the smallest sample
+            let loc = min;
+            if mean <= loc {
+                return Err("exponential fit requires samples that are not all equal".to_string());
+            }
+
+            let lambda = 1.0 / (mean - loc);
+            let ll = n * lambda.ln() - lambda * (mean - loc) * n;
+
+            (ExponentialLoc::new(lambda, loc).into(), Some(ll), 2)
+        }
+        Family::Normal => {
+            let var = data.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+            if var <= 0.0 {
+                return Err("normal fit requires samples that are not all equal".to_string());
+            }
+
+            let ll = -0.5 * n * (2.0 * std::f64::consts::PI * var).ln() - 0.5 * n;
+
+            (
+                distribution::Gaussian::new(mean, var.sqrt()).into(),
+                Some(ll),
+                2,
+            )
+        }
+        Family::Uniform => {
+            if max <= min {
+                return Err("uniform fit requires samples that are not all equal".to_string());
+            }
+
+            let ll = -n * (max - min).ln();
+
+            (distribution::Uniform::new(min, max).into(), Some(ll), 2)
+        }
+        Family::Lognormal => {
+            positive()?;
+
+            let logs = data.iter().map(|x| x.ln()).collect::<Vec<_>>();
+            let mu = logs.iter().sum::<f64>() / n;
+            let var = logs.iter().map(|l| (l - mu).powi(2)).sum::<f64>() / n;
+            if var <= 0.0 {
+                return Err("lognormal fit requires samples that are not all equal".to_string());
+            }
+
+            let ll = -logs.iter().sum::<f64>()
+                - 0.5 * n * (2.0 * std::f64::consts::PI * var).ln()
+                - 0.5 * n;
+
+            (
+                Variate::Lognormal(distribution::Lognormal::new(mu, var.sqrt())),
+                Some(ll),
+                2,
+            )
+        }
+        Family::Weibull => {
+            positive()?;
+
+            let (shape, scale) = fit_weibull(data)?;
+            let ll = data
+                .iter()
+                .map(|x| {
+                    shape.ln() - shape * scale.ln() + (shape - 1.0) * x.ln()
+                        - (x / scale).powf(shape)
+                })
+                .sum::<f64>();
+
+            (Variate::Weibull(Weibull::new(shape, scale)), Some(ll), 2)
+        }
+        Family::Empirical => (
+            Variate::Empirical(Empirical::new(data.to_vec())),
+            None,
+            data.len(),
+        ),
+    };
+
+    Ok(Fit {
+        family,
+        variate,
+        log_likelihood,
+        num_params,
+    })
+}
+
+/// Fit every parametric family that applies to the data, best fit first by AIC.
+pub fn fit_all(data: &[f64]) -> Vec<Fit> {
+    let mut fits = Family::PARAMETRIC
+        .iter()
+        .filter_map(|family| fit(*family, data).ok())
+        .collect::<Vec<_>>();
+
+    fits.sort_by(|a, b| {
+        a.aic()
+            .unwrap_or(f64::INFINITY)
+            .total_cmp(&b.aic().unwrap_or(f64::INFINITY))
+    });
+
+    fits
+}
+
+/// Fit a distribution family to a column of a csv file.
+///
+/// If no family is given, the best parametric fit by AIC is returned.
+pub fn fit_csv_column<P: AsRef<Path>>(
+    path: P,
+    column: &str,
+    family: Option<Family>,
+) -> Result<Fit, String> {
+    let data = read_csv_column(&path, column)?;
+
+    let fit = match family {
+        Some(family) => fit(family, &data),
+        None => fit_all(&data)
+            .into_iter()
+            .next()
+            .ok_or_else(|| "no distribution family fits the data".to_string()),
+    };
+
+    fit.map_err(|e| format!("{}, column '{}': {}", path.as_ref().display(), column, e))
+}
+
+/// Maximum likelihood estimate of the Weibull shape and scale, by Newton's method.
+fn fit_weibull(data: &[f64]) -> Result<(f64, f64), String> {
+    let n = data.len() as f64;
+
+    // the shape estimate is scale-free, so normalise for numerical stability
+    let norm = data.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let xs = data.iter().map(|x| x / norm).collect::<Vec<_>>();
+    let logs = xs.iter().map(|x| x.ln()).collect::<Vec<_>>();
+    let mean_log = logs.iter().sum::<f64>() / n;
+
+    let log_sd = (logs.iter().map(|l| (l - mean_log).powi(2)).sum::<f64>() / n).sqrt();
+    if log_sd <= 0.0 {
+        return Err("weibull fit requires samples that are not all equal".to_string());
+    }
+
+    // moment-based starting point, from the variance of log samples
+    let mut shape = 1.2825 / log_sd;
+
+    for _ in 0..100 {
+        let (mut s0, mut s1, mut s2) = (0.0, 0.0, 0.0);
+        for (x, l) in xs.iter().zip(&logs) {
+            let xk = x.powf(shape);
+            s0 += xk;
+            s1 += xk * l;
+            s2 += xk * l * l;
+        }
+
+        let f = s1 / s0 - 1.0 / shape - mean_log;
+        let df = (s2 * s0 - s1 * s1) / (s0 * s0) + 1.0 / (shape * shape);
+
+        let mut next = shape - f / df;
+        if next <= 0.0 {
+            next = shape / 2.0;
+        }
+
+        let converged = (next - shape).abs() < 1e-12 * shape;
+        shape = next;
+
+        if converged {
+            break;
+        }
+    }
+
+    if !shape.is_finite() {
+        return Err("weibull fit did not converge".to_string());
+    }
+
+    let scale = (xs.iter().map(|x| x.powf(shape)).sum::<f64>() / n).powf(1.0 / shape) * norm;
+
+    Ok((shape, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use probability::distribution::Sample;
+
+    use super::*;
+
+    fn samples(spec: &str, n: usize) -> Vec<f64> {
+        let variate = spec.parse::<Variate>().unwrap();
+        let mut source = probability::source::default(11);
+        (0..n).map(|_| variate.sample(&mut source)).collect()
+    }
+
+    fn close(actual: f64, expected: f64, rel: f64) -> bool {
+        (actual - expected).abs() <= rel * expected.abs()
+    }
+
+    #[test]
+    fn test_fit_parameters() {
+        let data = samples("exponential(100, 10)", 50_000);
+        let fit = fit(Family::Exponential, &data).unwrap();
+        match fit.variate {
+            Variate::Exponential(d) => {
+                assert!(close(1.0 / d.lambda(), 100.0, 0.02));
+                assert!(close(d.loc(), 10.0, 0.01));
+            }
+            _ => panic!("unexpected variate {}", fit.variate),
+        }
+
+        let data = samples("normal(120, 9)", 50_000);
+        let fit = super::fit(Family::Normal, &data).unwrap();
+        assert!(fit.variate.to_string().starts_with("normal("));
+        match fit.variate {
+            Variate::Normal(d) => {
+                assert!(close(d.mu(), 120.0, 0.01));
+                assert!(close(d.sigma(), 9.0, 0.02));
+            }
+            _ => unreachable!(),
+        }
+
+        let data = samples("lognormal(4.2, 0.8)", 50_000);
+        match super::fit(Family::Lognormal, &data).unwrap().variate {
+            Variate::Lognormal(d) => {
+                assert!(close(d.mu(), 4.2, 0.01));
+                assert!(close(d.sigma(), 0.8, 0.02));
+            }
+            _ => unreachable!(),
+        }
+
+        let data = samples("weibull(1.7, 90)", 50_000);
+        match super::fit(Family::Weibull, &data).unwrap().variate {
+            Variate::Weibull(d) => {
+                assert!(close(d.shape(), 1.7, 0.02), "shape {}", d.shape());
+                assert!(close(d.scale(), 90.0, 0.02), "scale {}", d.scale());
+            }
+            _ => unreachable!(),
+        }
+
+        let data = samples("uniform(0, 2000)", 50_000);
+        match super::fit(Family::Uniform, &data).unwrap().variate {
+            Variate::Uniform(d) => {
+                assert!(d.a() >= 0.0 && d.a() < 1.0);
+                assert!(close(d.b(), 2000.0, 0.01));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_fit_all_ranks_true_family_first() {
+        let data = samples("weibull(2.5, 90)", 20_000);
+        assert_eq!(fit_all(&data)[0].family, Family::Weibull);
+
+        let data = samples("lognormal(4.2, 0.8)", 20_000);
+        assert_eq!(fit_all(&data)[0].family, Family::Lognormal);
+
+        // lognormal and weibull do not apply to negative samples
+        let data = samples("normal(0, 1)", 1_000);
+        let fits = fit_all(&data);
+        assert_eq!(fits[0].family, Family::Normal);
+        assert!(fits
+            .iter()
+            .all(|f| !matches!(f.family, Family::Lognormal | Family::Weibull)));
+    }
+
+    #[test]
+    fn test_fit_csv_column() {
+        let path = std::env::temp_dir().join(format!(
+            "simulator_core_test_fit_csv_column_{}.csv",
+            std::process::id()
+        ));
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "idx,Call duration (sec) ").unwrap();
+        for (idx, x) in samples("exponential(100, 10)", 5_000).iter().enumerate() {
+            writeln!(file, "{},{}", idx, x).unwrap();
+        }
+        drop(file);
+
+        let fit = fit_csv_column(&path, "Call duration (sec)", Some(Family::Empirical)).unwrap();
+        assert!(fit.log_likelihood.is_none());
+        assert!(matches!(fit.variate, Variate::Empirical(_)));
+
+        let fit = fit_csv_column(&path, "Call duration (sec)", None).unwrap();
+        assert_eq!(fit.family, Family::Exponential);
+
+        let spec = format!("fit({:?}, \"Call duration (sec)\", \"exponential\")", path);
+        assert!(matches!(
+            spec.parse::<Variate>().unwrap(),
+            Variate::Exponential(_)
+        ));
+
+        assert!(fit_csv_column(&path, "velocity", None).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_fit_degenerate_data() {
+        assert!(fit(Family::Normal, &[1.0]).is_err());
+        assert!(fit(Family::Normal, &[1.0, 1.0, 1.0]).is_err());
+        assert!(fit(Family::Lognormal, &[-1.0, 2.0]).is_err());
+        assert!(fit(Family::Weibull, &[0.0, 2.0]).is_err());
+        assert!(fit(Family::Uniform, &[1.0, f64::NAN]).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Goodness-of-fit tests of samples against a hypothesised distribution.
+//!
+//! p-values assume a fully specified distribution. When its parameters were fitted to the same
+//! samples, the Kolmogorov-Smirnov and Anderson-Darling p-values are conservative.
+
+use probability::distribution::{self, Distribution, Inverse};
+
+/// Result of a goodness-of-fit test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TestResult {
+    pub statistic: f64,
+
+    /// Probability of a statistic at least as extreme under the hypothesised distribution
+    pub p_value: f64,
+
+    /// Degrees of freedom, for chi-square tests
+    pub dof: Option<usize>,
+}
+
+/// Bin edges with equal probability under `dist`, from `-inf` to `inf`.
+pub fn equiprobable_edges<D: Inverse<Value = f64>>(dist: &D, bins: usize) -> Vec<f64> {
+    let bins = bins.max(1);
+
+    (0..=bins)
+        .map(|i| match i {
+            0 => f64::NEG_INFINITY,
+            i if i == bins => f64::INFINITY,
+            i => dist.inverse(i as f64 / bins as f64),
+        })
+        .collect()
+}
+
+/// Pearson's chi-square test over bins `[edges[i], edges[i + 1])`.
+///
+/// `num_fitted` parameters estimated from the samples are taken off the degrees of freedom.
+/// Bins without any expected samples are skipped.
+pub fn chi_square<D: Distribution<Value = f64>>(
+    samples: &[f64],
+    dist: &D,
+    edges: &[f64],
+    num_fitted: usize,
+) -> TestResult {
+    let n = samples.len() as f64;
+    let mut observed = vec![0usize; edges.len().saturating_sub(1)];
+
+    for x in samples {
+        let bin = edges.partition_point(|edge| edge <= x);
+        if bin > 0 && bin <= observed.len() {
+            observed[bin - 1] += 1;
+        }
+    }
+
+    let mut statistic = 0.0;
+    let mut used_bins = 0usize;
+    for (i, obs) in observed.iter().enumerate() {
+        let p = cdf(dist, edges[i + 1]) - cdf(dist, edges[i]);
+        let expected = n * p;
+        if expected <= 0.0 {
+            continue;
+        }
+
+        statistic += (*obs as f64 - expected).powi(2) / expected;
+        used_bins += 1;
+    }
+
+    let dof = used_bins.saturating_sub(1 + num_fitted).max(1);
+    let p_value = 1.0 - distribution::Gamma::new(dof as f64 / 2.0, 2.0).distribution(statistic);
+
+    TestResult {
+        statistic,
+        p_value: p_value.clamp(0.0, 1.0),
+        dof: Some(dof),
+    }
+}
+
+/// Kolmogorov-Smirnov test, with Stephens' small-sample correction of the p-value.
+pub fn kolmogorov_smirnov<D: Distribution<Value = f64>>(samples: &[f64], dist: &D) -> TestResult {
+    let sorted = sorted(samples);
+    let n = sorted.len() as f64;
+
+    let statistic = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let f = cdf(dist, *x);
+            (f - i as f64 / n).max((i + 1) as f64 / n - f)
+        })
+        .fold(0.0, f64::max);
+
+    let lambda = (n.sqrt() + 0.12 + 0.11 / n.sqrt()) * statistic;
+
+    TestResult {
+        statistic,
+        p_value: kolmogorov_sf(lambda),
+        dof: None,
+    }
+}
+
+/// Anderson-Darling test, with the asymptotic p-value approximation of Marsaglia & Marsaglia.
+pub fn anderson_darling<D: Distribution<Value = f64>>(samples: &[f64], dist: &D) -> TestResult {
+    let sorted = sorted(samples);
+    let n = sorted.len();
+
+    // keep the logarithms finite for samples outside of the support
+    let clamp = |p: f64| p.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON / 2.0);
+
+    let sum = (0..n)
+        .map(|i| {
+            let low = clamp(cdf(dist, sorted[i]));
+            let high = clamp(cdf(dist, sorted[n - 1 - i]));
+            (2 * i + 1) as f64 * (low.ln() + (1.0 - high).ln())
+        })
+        .sum::<f64>();
+
+    let statistic = -(n as f64) - sum / n as f64;
+
+    TestResult {
+        statistic,
+        p_value: 1.0 - anderson_darling_cdf(statistic),
+        dof: None,
+    }
+}
+
+/// Survival function of the Kolmogorov distribution.
+fn kolmogorov_sf(lambda: f64) -> f64 {
+    if lambda < 0.2 {
+        return 1.0;
+    }
+
+    let sum = (1..=100)
+        .map(|k| {
+            let sign = match k % 2 {
+                1 => 1.0,
+                _ => -1.0,
+            };
+            sign * (-2.0 * (k * k) as f64 * lambda * lambda).exp()
+        })
+        .sum::<f64>();
+
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Asymptotic CDF of the Anderson-Darling statistic.
+fn anderson_darling_cdf(z: f64) -> f64 {
+    if z <= 0.0 {
+        return 0.0;
+    }
+
+    let cdf = match z < 2.0 {
+        true => {
+            (-1.2337141 / z).exp() / z.sqrt()
+                * (2.00012
+                    + (0.247105
+                        - (0.0649821 - (0.0347962 - (0.011672 - 0.00168691 * z) * z) * z) * z)
+                        * z)
+        }
+        false => (-(1.0776
+            - (2.30695 - (0.43424 - (0.082433 - (0.008056 - 0.0003146 * z) * z) * z) * z) * z)
+            .exp())
+        .exp(),
+    };
+
+    cdf.clamp(0.0, 1.0)
+}
+
+/// CDF that also accepts infinite bin edges.
+fn cdf<D: Distribution<Value = f64>>(dist: &D, x: f64) -> f64 {
+    match x {
+        x if x == f64::NEG_INFINITY => 0.0,
+        x if x == f64::INFINITY => 1.0,
+        x => dist.distribution(x),
+    }
+}
+
+fn sorted(samples: &[f64]) -> Vec<f64> {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::distribution::Sample;
+
+    use super::*;
+    use crate::Variate;
+
+    fn samples(spec: &str, n: usize, seed: u64) -> Vec<f64> {
+        let variate = spec.parse::<Variate>().unwrap();
+        let mut source = probability::source::default(seed);
+        (0..n).map(|_| variate.sample(&mut source)).collect()
+    }
+
+    #[test]
+    fn test_accepts_true_distribution() {
+        let dist = "weibull(1.7, 90)".parse::<Variate>().unwrap();
+        let data = samples("weibull(1.7, 90)", 5_000, 3);
+
+        let edges = equiprobable_edges(&dist, 20);
+        let chi = chi_square(&data, &dist, &edges, 0);
+        assert_eq!(chi.dof, Some(19));
+        assert!(chi.p_value > 0.01, "{:?}", chi);
+        assert!(kolmogorov_smirnov(&data, &dist).p_value > 0.01);
+        assert!(anderson_darling(&data, &dist).p_value > 0.01);
+    }
+
+    #[test]
+    fn test_rejects_wrong_distribution() {
+        let dist = "exponential(90)".parse::<Variate>().unwrap();
+        let data = samples("normal(90, 10)", 2_000, 5);
+
+        let edges = equiprobable_edges(&dist, 20);
+        assert!(chi_square(&data, &dist, &edges, 0).p_value < 1e-6);
+        assert!(kolmogorov_smirnov(&data, &dist).p_value < 1e-6);
+        assert!(anderson_darling(&data, &dist).p_value < 1e-6);
+    }
+
+    #[test]
+    fn test_known_values() {
+        // a single sample at the median
+        let dist = distribution::Uniform::new(0.0, 1.0);
+        let ks = kolmogorov_smirnov(&[0.5], &dist);
+        assert!((ks.statistic - 0.5).abs() < 1e-12);
+
+        let ad = anderson_darling(&[0.5], &dist);
+        assert!((ad.statistic - (-1.0 - 2.0 * 0.5f64.ln())).abs() < 1e-12);
+
+        // tabulated critical values
+        assert!((kolmogorov_sf(1.3581) - 0.05).abs() < 1e-3);
+        assert!((1.0 - anderson_darling_cdf(2.492) - 0.05).abs() < 1e-3);
+
+        // the station distribution over integer bins
+        let edges = (0..=4).map(|e| e as f64).collect::<Vec<_>>();
+        let dist = distribution::Uniform::new(0.0, 4.0);
+        let chi = chi_square(&[0.0, 1.0, 2.0, 3.0, 3.0, 3.0], &dist, &edges, 0);
+        assert!((chi.statistic - 2.0).abs() < 1e-12);
+        assert_eq!(chi.dof, Some(3));
+    }
+}
@@ -0,0 +1,176 @@
+//! Importance sampling of exponential variates, with likelihood-ratio weighted estimators.
+//!
+//! Samples drawn from a tilted distribution `g` instead of the nominal `f` are weighted by the
+//! likelihood ratio `f(x) / g(x)`, which makes the weighted output an unbiased estimate under
+//! `f`. The ratio of a run is the product over all of its samples, so its variance grows with
+//! the number of samples, and tilts of long runs have to be mild.
+
+use crate::{stats::Summary, ExponentialLoc};
+
+/// An exponential distribution with its rate tilted, keeping the location.
+#[derive(Clone, Debug)]
+pub struct ExponentialTilt {
+    nominal: ExponentialLoc,
+    tilted: ExponentialLoc,
+}
+
+/// Mean estimated from likelihood-ratio weighted replications.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedEstimate {
+    /// Unbiased estimate, the mean of the weighted outputs
+    pub mean: f64,
+
+    /// Half-width of the confidence interval for the mean
+    pub half_width: f64,
+
+    /// Outputs averaged with normalized weights, biased but often less variable
+    pub self_normalized: f64,
+
+    /// Number of unweighted replications with the same variance as equally weighted ones
+    pub effective_sample_size: f64,
+
+    /// Mean of the weights, which should be close to one
+    pub mean_weight: f64,
+}
+
+impl ExponentialTilt {
+    /// Tilt `nominal` by multiplying its rate by `factor`.
+    pub fn new(nominal: ExponentialLoc, factor: f64) -> Self {
+        assert!(factor > 0.0, "tilting factor must be positive");
+
+        let tilted = ExponentialLoc::new(nominal.lambda() * factor, nominal.loc());
+        Self { nominal, tilted }
+    }
+
+    /// The distribution estimates are made under.
+    pub fn nominal(&self) -> &ExponentialLoc {
+        &self.nominal
+    }
+
+    /// The distribution samples are drawn from.
+    pub fn tilted(&self) -> &ExponentialLoc {
+        &self.tilted
+    }
+
+    /// Logarithm of the likelihood ratio of a sample drawn from the tilted distribution.
+    pub fn log_likelihood_ratio(&self, x: f64) -> f64 {
+        let (nominal, tilted) = (self.nominal.lambda(), self.tilted.lambda());
+        (nominal / tilted).ln() - (nominal - tilted) * (x - self.nominal.loc())
+    }
+}
+
+impl WeightedEstimate {
+    /// Bounds of the confidence interval.
+    pub fn ci(&self) -> (f64, f64) {
+        (self.mean - self.half_width, self.mean + self.half_width)
+    }
+}
+
+/// Estimate the mean of `outputs` under the nominal distributions, from replications with the
+/// given log likelihood ratios.
+pub fn weighted_mean(
+    outputs: &[f64],
+    log_ratios: &[f64],
+    confidence: f64,
+) -> Result<WeightedEstimate, String> {
+    let weighted = outputs
+        .iter()
+        .zip(log_ratios)
+        .map(|(y, l)| y * l.exp())
+        .collect::<Vec<_>>();
+    weighted_estimate(&weighted, outputs, log_ratios, confidence)
+}
+
+/// Estimate a mean under the nominal distributions from `weighted`, replications whose
+/// outputs were weighted as they were simulated, such as by the likelihood ratios of their
+/// parts.
+///
+/// `outputs` and `log_ratios` are the unweighted outputs and log likelihood ratios of the
+/// replications as a whole, for the self-normalized estimate and effective sample size.
+pub fn weighted_estimate(
+    weighted: &[f64],
+    outputs: &[f64],
+    log_ratios: &[f64],
+    confidence: f64,
+) -> Result<WeightedEstimate, String> {
+    if outputs.len() != log_ratios.len() || weighted.len() != log_ratios.len() {
+        return Err("every replication needs a likelihood ratio".to_string());
+    }
+    if outputs.len() < 2 {
+        return Err("weighted estimates need at least two replications".to_string());
+    }
+    if log_ratios
+        .iter()
+        .any(|l| !l.is_finite() || l.abs() > f64::MAX.ln())
+        || weighted.iter().any(|x| !x.is_finite())
+    {
+        return Err("likelihood ratios over- or underflowed, the tilt is too strong".to_string());
+    }
+
+    let summary = Summary::new(weighted, confidence);
+
+    // normalized weights, scaled by the largest for stability
+    let max = log_ratios.iter().fold(f64::NEG_INFINITY, |m, l| m.max(*l));
+    let scaled = log_ratios
+        .iter()
+        .map(|l| (l - max).exp())
+        .collect::<Vec<_>>();
+    let total = scaled.iter().sum::<f64>();
+
+    Ok(WeightedEstimate {
+        mean: summary.mean,
+        half_width: summary.half_width,
+        self_normalized: outputs.iter().zip(&scaled).map(|(y, w)| y * w).sum::<f64>() / total,
+        effective_sample_size: total.powi(2) / scaled.iter().map(|w| w * w).sum::<f64>(),
+        mean_weight: log_ratios.iter().map(|l| l.exp()).sum::<f64>() / log_ratios.len() as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::{
+        distribution::{Distribution, Sample},
+        source,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_tail_probability() {
+        // P(X > 6) of a unit exponential, about 2.5e-3, from samples tilted into the tail
+        let nominal = ExponentialLoc::new(1.0, 0.0);
+        let tilt = ExponentialTilt::new(nominal.clone(), 1.0 / 6.0);
+        let exact = 1.0 - nominal.distribution(6.0);
+
+        let mut source = source::default(5);
+        let (outputs, log_ratios): (Vec<_>, Vec<_>) = (0..2000)
+            .map(|_| {
+                let x = tilt.tilted().sample(&mut source);
+                ((x > 6.0) as u8 as f64, tilt.log_likelihood_ratio(x))
+            })
+            .unzip();
+        let estimate = weighted_mean(&outputs, &log_ratios, 0.99).unwrap();
+
+        let (low, high) = estimate.ci();
+        assert!(low < exact && exact < high, "{:?}", estimate);
+        assert!((estimate.mean_weight - 1.0).abs() < 0.1);
+
+        // naive sampling with the same replications would have a half-width of about 3e-3
+        assert!(estimate.half_width < 1e-3, "{:?}", estimate);
+        assert!(estimate.effective_sample_size < 2000.0);
+    }
+
+    #[test]
+    fn test_untilted_weights() {
+        let tilt = ExponentialTilt::new(ExponentialLoc::new(2.0, 1.0), 1.0);
+        assert_eq!(tilt.log_likelihood_ratio(3.0), 0.0);
+
+        let estimate = weighted_mean(&[1.0, 3.0], &[0.0, 0.0], 0.95).unwrap();
+        assert_eq!(estimate.mean, 2.0);
+        assert_eq!(estimate.self_normalized, 2.0);
+        assert_eq!(estimate.effective_sample_size, 2.0);
+
+        assert!(weighted_mean(&[1.0, 3.0], &[0.0, f64::INFINITY], 0.95).is_err());
+        assert!(weighted_mean(&[1.0], &[0.0], 0.95).is_err());
+    }
+}
@@ -1,17 +1,33 @@
 //! Core functionality for running event-based simulations.
 
 use std::{
-    fs,
-    io::{self, Write},
+    io,
     ops::{Add, Div},
     path::Path,
 };
 
 use probability::{
-    distribution::{self, Distribution, Sample},
-    source::Source,
+    distribution::{self, Distribution, Inverse, Sample},
+    source::{self, Source},
 };
 
+pub mod analytic;
+pub mod bootstrap;
+pub mod control;
+pub mod fit;
+pub mod gof;
+pub mod importance;
+pub mod mmcc;
+pub mod output;
+pub mod qmc;
+pub mod regenerative;
+pub mod selection;
+pub mod stats;
+pub mod stratified;
+mod variate;
+
+pub use variate::{read_csv_column, Empirical, Erlang, Hyperexponential, Variate, Weibull};
+
 /// Event processing logic implements this trait.
 pub trait EventLike {
     /// Shared resources used in the simulation, such as statistical counters, etc.
@@ -28,6 +44,12 @@ pub trait EventLike {
 
     /// From the results of the simulation, calculate a performance measure.
     fn calculate_performance_measure(results: &[Self::EventStats]) -> Self::PerformanceMeasure;
+
+    /// Whether the simulation regenerates in its current state, i.e. its future is independent
+    /// of its past. Models without regeneration points keep the default.
+    fn is_regeneration_point(&self, _shared: &Self::SharedResources) -> bool {
+        false
+    }
 }
 
 /// Runner for event-based simulations
@@ -42,6 +64,9 @@ where
 
     /// The results of the simulation
     results: Vec<P::EventStats>,
+
+    /// Number of results before every regeneration point
+    regenerations: Vec<usize>,
     // perf_measure: Option<P::PerformanceMeasure>,
 }
 
@@ -67,6 +92,15 @@ where
     prepare: usize,
 }
 
+/// A source of randomness that can be split into independent streams.
+///
+/// Every input variate draws from its own stream, so seeded runs are reproducible and no two
+/// variates see the same samples.
+pub trait SplitSource: Source + Clone {
+    /// Derive an independent stream, advancing this one.
+    fn split(&mut self) -> Self;
+}
+
 /// An anththetic sampler that can yield 10 antithetic samples from a reference sampler.
 ///
 /// Any further samples will return the same value as the second sample.
@@ -90,6 +124,31 @@ pub struct ExponentialLoc {
     loc: f64,
 }
 
+/// A distribution truncated to an interval.
+///
+/// Probability mass outside the interval is discarded and the rest is renormalised,
+/// so samples are drawn by inverting the CDF over the interval.
+#[derive(Clone, Debug)]
+pub struct Truncated<D> {
+    inner: D,
+    low: f64,
+    high: f64,
+    /// Inner CDF at the lower bound
+    cdf_low: f64,
+    /// Inner CDF at the upper bound
+    cdf_high: f64,
+}
+
+/// A distribution clamped to an interval.
+///
+/// Probability mass outside the interval is moved onto the nearest bound.
+#[derive(Clone, Debug)]
+pub struct Clamped<D> {
+    inner: D,
+    low: f64,
+    high: f64,
+}
+
 impl Distribution for ExponentialLoc {
     type Value = f64;
 
@@ -114,6 +173,156 @@ impl ExponentialLoc {
             loc,
         }
     }
+
+    /// Rate parameter of the exponential distribution.
+    pub fn lambda(&self) -> f64 {
+        self.inner.lambda()
+    }
+
+    /// Location parameter, the smallest value that can be sampled.
+    pub fn loc(&self) -> f64 {
+        self.loc
+    }
+}
+
+impl Inverse for ExponentialLoc {
+    fn inverse(&self, p: f64) -> Self::Value {
+        self.inner.inverse(p) + self.loc
+    }
+}
+
+impl<D> Truncated<D>
+where
+    D: Distribution<Value = f64>,
+{
+    /// Truncate a distribution to `[low, high]`.
+    ///
+    /// Panics if the interval is empty or holds no probability mass.
+    pub fn new(inner: D, low: f64, high: f64) -> Self {
+        assert!(low < high, "truncation interval must not be empty");
+
+        let cdf_low = inner.distribution(low);
+        let cdf_high = inner.distribution(high);
+        assert!(
+            cdf_high > cdf_low,
+            "truncation interval must hold some probability mass"
+        );
+
+        Self {
+            inner,
+            low,
+            high,
+            cdf_low,
+            cdf_high,
+        }
+    }
+
+    /// The untruncated distribution.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Bounds of the truncation interval.
+    pub fn bounds(&self) -> (f64, f64) {
+        (self.low, self.high)
+    }
+}
+
+impl<D> Distribution for Truncated<D>
+where
+    D: Distribution<Value = f64>,
+{
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        match x {
+            x if x < self.low => 0.0,
+            x if x >= self.high => 1.0,
+            x => (self.inner.distribution(x) - self.cdf_low) / (self.cdf_high - self.cdf_low),
+        }
+    }
+}
+
+impl<D> Inverse for Truncated<D>
+where
+    D: Inverse<Value = f64>,
+{
+    fn inverse(&self, p: f64) -> Self::Value {
+        let p = self.cdf_low + p * (self.cdf_high - self.cdf_low);
+        self.inner.inverse(p).clamp(self.low, self.high)
+    }
+}
+
+impl<D> Sample for Truncated<D>
+where
+    D: Inverse<Value = f64>,
+{
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        self.inverse(source.read::<f64>())
+    }
+}
+
+impl<D> Clamped<D>
+where
+    D: Distribution<Value = f64>,
+{
+    /// Clamp a distribution to `[low, high]`.
+    ///
+    /// Panics if the interval is empty.
+    pub fn new(inner: D, low: f64, high: f64) -> Self {
+        assert!(low < high, "clamping interval must not be empty");
+
+        Self { inner, low, high }
+    }
+
+    /// The unclamped distribution.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Bounds of the clamping interval.
+    pub fn bounds(&self) -> (f64, f64) {
+        (self.low, self.high)
+    }
+}
+
+impl<D> Distribution for Clamped<D>
+where
+    D: Distribution<Value = f64>,
+{
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        match x {
+            x if x < self.low => 0.0,
+            x if x >= self.high => 1.0,
+            x => self.inner.distribution(x),
+        }
+    }
+}
+
+impl<D> Inverse for Clamped<D>
+where
+    D: Inverse<Value = f64>,
+{
+    fn inverse(&self, p: f64) -> Self::Value {
+        self.inner.inverse(p).clamp(self.low, self.high)
+    }
+}
+
+impl<D> Sample for Clamped<D>
+where
+    D: Sample<Value = f64>,
+{
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        self.inner.sample(source).clamp(self.low, self.high)
+    }
 }
 
 impl<'s, S> AntitheticSampler<'s, S>
@@ -183,6 +392,20 @@ where
     }
 }
 
+impl SplitSource for source::Xorshift128Plus {
+    fn split(&mut self) -> Self {
+        source::Xorshift128Plus::new([splitmix64(self.read_u64()), splitmix64(self.read_u64())])
+    }
+}
+
+/// SplitMix64 output function, for deriving well-mixed seeds from related values.
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
 impl<D, S> SingleVariateIterator<D, S>
 where
     D: Sample + Clone,
@@ -195,6 +418,19 @@ where
         }
     }
 
+    /// The distribution samples are drawn from.
+    pub fn distribution(&self) -> &D {
+        &self.distribution
+    }
+
+    /// Draw samples from another distribution, on the same stream.
+    pub fn with_distribution(self, distribution: D) -> Self {
+        Self {
+            source: self.source,
+            distribution,
+        }
+    }
+
     /// Create a new iterator that generates antithetic pairs from the distribution.
     ///
     /// The `prepared` parameter specifies the number of samples to pre-generate.
@@ -257,13 +493,16 @@ where
             inner: logic,
             globals: resources.unwrap_or_default(),
             results: Vec::new(),
+            regenerations: Vec::new(),
             // perf_measure: None,
         }
     }
 
     pub fn run(&mut self) {
+        self.mark_regeneration();
         while let Some(stats) = self.inner.step(&mut self.globals) {
             self.results.extend(stats);
+            self.mark_regeneration();
         }
 
         // self.perf_measure = Some(P::calculate_performance_measure(&self.results));
@@ -276,50 +515,57 @@ where
         P::calculate_performance_measure(&self.results[skip..])
     }
 
+    /// Returns the results of every complete regeneration cycle, from one regeneration point to
+    /// the next.
+    ///
+    /// Cycles are independent and identically distributed, so no warmup is needed, and results
+    /// after the last regeneration point are left out.
+    pub fn regeneration_cycles(&self) -> impl Iterator<Item = &[P::EventStats]> {
+        self.regenerations
+            .windows(2)
+            .map(|w| &self.results[w[0]..w[1]])
+    }
+
+    /// Record a regeneration point if the simulation is in one, unless nothing happened since
+    /// the last.
+    fn mark_regeneration(&mut self) {
+        if self.regenerations.last() != Some(&self.results.len())
+            && self.inner.is_regeneration_point(&self.globals)
+        {
+            self.regenerations.push(self.results.len());
+        }
+    }
+
+    /// Returns the shared resources of the simulation run.
+    pub fn shared_resources(&self) -> &P::SharedResources {
+        &self.globals
+    }
+
+    /// Returns the results of the simulation run.
+    pub fn results(&self) -> &[P::EventStats] {
+        &self.results
+    }
+
     /// Returns the results of the simulation run, comsuming the runner.
     pub fn into_results(self) -> Vec<P::EventStats> {
         self.results
     }
 
-    /// Write the results of the simulation as csv to a file.
+    /// Write the results of the simulation to a file, as csv or JSON Lines compressed by gzip or
+    /// zstd, depending on its extension. See [`output::RecordWriter`].
     ///
-    /// If set to append, headerless data is written to a file.
+    /// If set to append, headerless data is appended to the file as a new compressed stream.
     pub fn write_to_file<T: AsRef<Path>>(&self, path: T, append: bool) -> io::Result<()>
     where
         P::EventStats: serde::Serialize,
     {
-        let mut writer = csv::WriterBuilder::new()
-            .has_headers(!append)
-            .from_writer(vec![]);
+        let mut writer = output::RecordWriter::create(path, append)?;
 
         for record in &self.results {
             writer.serialize(record)?;
         }
 
-        writer.flush()?;
-
-        let mut file = match append {
-            // if appending, write to vec first
-            true => fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)?,
-
-            // if not appending, write to file directly
-            false => fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(path)?,
-        };
-
-        file.write_all(
-            &writer
-                .into_inner()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
-        )?;
-
-        Ok(())
+        writer.finish()
     }
 }
 
@@ -327,4 +573,89 @@ where
 #[allow(unused)]
 mod tests {
     use super::*;
+
+    /// Queue of `len` events that is empty after every `period` events.
+    struct Periodic {
+        len: usize,
+        period: usize,
+        step: usize,
+    }
+
+    impl EventLike for Periodic {
+        type SharedResources = ();
+        type EventStats = usize;
+        type PerformanceMeasure = f64;
+
+        fn step(&mut self, _shared: &mut ()) -> Option<Vec<usize>> {
+            self.step += 1;
+            (self.step <= self.len).then(|| vec![self.step])
+        }
+
+        fn calculate_performance_measure(results: &[usize]) -> f64 {
+            results.len() as f64
+        }
+
+        fn is_regeneration_point(&self, _shared: &()) -> bool {
+            self.step.is_multiple_of(self.period)
+        }
+    }
+
+    #[test]
+    fn test_regeneration_cycles() {
+        let logic = Periodic {
+            len: 11,
+            period: 3,
+            step: 0,
+        };
+        let mut runner = EventRunner::init(logic, None);
+        runner.run();
+
+        // the incomplete cycle after the last regeneration point is left out
+        let cycles = runner.regeneration_cycles().collect::<Vec<_>>();
+        assert_eq!(cycles, vec![&[1, 2, 3][..], &[4, 5, 6][..], &[7, 8, 9][..]]);
+    }
+
+    #[test]
+    fn test_truncated_gaussian() {
+        let dist = Truncated::new(distribution::Gaussian::new(0.0, 1.0), 0.5, 2.0);
+
+        assert_eq!(dist.distribution(0.0), 0.0);
+        assert_eq!(dist.distribution(2.0), 1.0);
+        assert!((dist.inverse(0.0) - 0.5).abs() < 1e-9);
+        assert!((dist.inverse(1.0) - 2.0).abs() < 1e-9);
+
+        let p = dist.distribution(1.0);
+        assert!((dist.inverse(p) - 1.0).abs() < 1e-9);
+
+        let mut source = probability::source::default(42);
+        for _ in 0..10_000 {
+            let x = dist.sample(&mut source);
+            assert!((0.5..=2.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_clamped_gaussian() {
+        let dist = Clamped::new(distribution::Gaussian::new(0.0, 1.0), -1.0, 1.0);
+
+        assert_eq!(dist.distribution(-1.5), 0.0);
+        assert_eq!(dist.distribution(1.0), 1.0);
+        assert!((dist.distribution(0.0) - 0.5).abs() < 1e-9);
+        assert_eq!(dist.inverse(0.01), -1.0);
+        assert_eq!(dist.inverse(0.99), 1.0);
+
+        let mut source = probability::source::default(42);
+        let samples = (0..10_000)
+            .map(|_| dist.sample(&mut source))
+            .collect::<Vec<_>>();
+
+        assert!(samples.iter().all(|x| (-1.0..=1.0).contains(x)));
+        // roughly 16% of the mass sits on each bound
+        let at_low = samples.iter().filter(|x| **x == -1.0).count();
+        assert!(
+            (1300..1900).contains(&at_low),
+            "{} samples at bound",
+            at_low
+        );
+    }
 }
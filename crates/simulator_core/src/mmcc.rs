@@ -0,0 +1,235 @@
+//! M/M/c/c loss system, a reference model for validating the event runner.
+//!
+//! Its blocking probability is known exactly from the Erlang-B formula, so simulated runs check
+//! `EventRunner` semantics independently of any particular application model.
+
+use std::ops::{Add, Div};
+
+use probability::{
+    distribution::{self, Sample},
+    source::Source,
+};
+
+/// Channels of an M/M/c/c system, shared between events.
+#[derive(Clone, Debug, Default)]
+pub struct MmccChannels {
+    /// Total number of channels
+    pub channels: usize,
+
+    /// Channels serving a customer
+    pub busy: usize,
+}
+
+/// Outcome of an arrival.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct MmccArrival {
+    /// Arrival number, starting from 1
+    pub idx: usize,
+
+    pub time: f64,
+
+    /// Whether the arrival found every channel busy
+    pub blocked: bool,
+}
+
+/// Performance measure of an M/M/c/c run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MmccPerf {
+    /// Fraction of arrivals that were blocked
+    pub blocking: f64,
+}
+
+/// M/M/c/c event logic: poisson arrivals, exponential service and no queue.
+#[derive(Clone, Debug)]
+pub struct Mmcc<S>
+where
+    S: Source,
+{
+    source: S,
+    inter_arrival: distribution::Exponential,
+    service: distribution::Exponential,
+
+    /// Arrivals left to generate
+    remaining: usize,
+
+    count: usize,
+    next_arrival: f64,
+
+    /// Departure times of customers in service, latest first
+    departures: Vec<f64>,
+}
+
+impl<S> Mmcc<S>
+where
+    S: Source,
+{
+    /// Create a system with the given arrival and service rates, stopping after `num_arrivals`.
+    pub fn new(arrival_rate: f64, service_rate: f64, num_arrivals: usize, mut source: S) -> Self {
+        let inter_arrival = distribution::Exponential::new(arrival_rate);
+        let next_arrival = inter_arrival.sample(&mut source);
+
+        Self {
+            source,
+            inter_arrival,
+            service: distribution::Exponential::new(service_rate),
+            remaining: num_arrivals,
+            count: 0,
+            next_arrival,
+            departures: Vec::new(),
+        }
+    }
+}
+
+impl<S> crate::EventLike for Mmcc<S>
+where
+    S: Source,
+{
+    type SharedResources = MmccChannels;
+    type EventStats = MmccArrival;
+    type PerformanceMeasure = MmccPerf;
+
+    fn step(&mut self, shared: &mut Self::SharedResources) -> Option<Vec<Self::EventStats>> {
+        let next_departure = self.departures.last().copied();
+
+        match next_departure {
+            // departures before the next arrival, and any left after the last arrival
+            Some(departure) if self.remaining == 0 || departure <= self.next_arrival => {
+                self.departures.pop();
+                shared.busy -= 1;
+                Some(Vec::new())
+            }
+            _ if self.remaining == 0 => None,
+            _ => {
+                let time = self.next_arrival;
+                self.count += 1;
+                self.remaining -= 1;
+                self.next_arrival += self.inter_arrival.sample(&mut self.source);
+
+                let blocked = shared.busy >= shared.channels;
+                if !blocked {
+                    shared.busy += 1;
+                    let departure = time + self.service.sample(&mut self.source);
+                    let pos = self.departures.partition_point(|d| *d > departure);
+                    self.departures.insert(pos, departure);
+                }
+
+                Some(vec![MmccArrival {
+                    idx: self.count,
+                    time,
+                    blocked,
+                }])
+            }
+        }
+    }
+
+    fn calculate_performance_measure(results: &[Self::EventStats]) -> Self::PerformanceMeasure {
+        let blocked = results.iter().filter(|r| r.blocked).count();
+
+        MmccPerf {
+            blocking: blocked as f64 / results.len().max(1) as f64,
+        }
+    }
+}
+
+impl Add for MmccPerf {
+    type Output = MmccPerf;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            blocking: self.blocking + rhs.blocking,
+        }
+    }
+}
+
+impl Div<f64> for MmccPerf {
+    type Output = MmccPerf;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        Self {
+            blocking: self.blocking / rhs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analytic::erlang_b, EventLike, EventRunner};
+
+    fn runner(
+        channels: usize,
+        load: f64,
+        arrivals: usize,
+        seed: u64,
+    ) -> EventRunner<Mmcc<impl Source>> {
+        let model = Mmcc::new(load, 1.0, arrivals, probability::source::default(seed));
+        EventRunner::init(model, Some(MmccChannels { channels, busy: 0 }))
+    }
+
+    #[test]
+    fn test_blocking_matches_erlang_b() {
+        for (channels, load) in [(1, 0.5), (5, 3.0), (10, 8.0)] {
+            let runs = 20;
+            let samples = (0..runs)
+                .map(|seed| {
+                    let mut run = runner(channels, load, 20_000, seed + 1);
+                    run.run();
+                    run.performance_measure(1_000).blocking
+                })
+                .collect::<Vec<_>>();
+
+            let mean = samples.iter().sum::<f64>() / runs as f64;
+            let var = samples.iter().map(|b| (b - mean).powi(2)).sum::<f64>() / (runs - 1) as f64;
+            // t quantile for 99% with 19 degrees of freedom
+            let half_width = 2.861 * (var / runs as f64).sqrt();
+
+            let expected = erlang_b(load, channels);
+            assert!(
+                (mean - expected).abs() < half_width,
+                "c = {}, load = {}: {} ± {} vs {}",
+                channels,
+                load,
+                mean,
+                half_width,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_runner_accumulates_and_skips_warmup() {
+        let mut run = runner(2, 4.0, 5_000, 9);
+        run.run();
+
+        // every channel is released once arrivals stop
+        assert_eq!(run.shared_resources().busy, 0);
+
+        let warm = run.performance_measure(1_000);
+        let all = run.performance_measure(0);
+        let results = run.into_results();
+
+        // one result per arrival, in order, and none for departures
+        assert_eq!(results.len(), 5_000);
+        assert!(results.iter().enumerate().all(|(i, r)| r.idx == i + 1));
+        assert!(results.windows(2).all(|w| w[0].time <= w[1].time));
+
+        assert_eq!(
+            all,
+            Mmcc::<probability::source::Default>::calculate_performance_measure(&results)
+        );
+        assert_eq!(
+            warm,
+            Mmcc::<probability::source::Default>::calculate_performance_measure(&results[1_000..])
+        );
+
+        // an empty system never blocks its first arrivals
+        assert!(!results[0].blocked && !results[1].blocked);
+    }
+
+    #[test]
+    fn test_perf_measure_averaging() {
+        let a = MmccPerf { blocking: 0.2 };
+        let b = MmccPerf { blocking: 0.4 };
+        assert!((((a + b) / 2.0).blocking - 0.3).abs() < 1e-12);
+    }
+}
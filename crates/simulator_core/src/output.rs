@@ -0,0 +1,298 @@
+//! Streaming text output of records as csv or JSON Lines, optionally compressed with gzip or zstd.
+//!
+//! Records are encoded and compressed as they are written, without buffering the whole file in
+//! memory. Appending to a compressed file starts a new gzip member or zstd frame, and decoders
+//! read concatenated members and frames as one stream, so appended files stay valid.
+
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+/// Encoding of every record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Comma separated values with a header
+    Csv,
+
+    /// One json object per line
+    JsonLines,
+}
+
+/// Compression of the encoded records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Compressing writer to a file.
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+enum Encoder {
+    Csv(Box<csv::Writer<Sink>>),
+    JsonLines(Sink),
+}
+
+/// Writer of records to a text file, encoded and compressed as given by its extension.
+pub struct RecordWriter {
+    encoder: Encoder,
+}
+
+impl Encoding {
+    /// Encoding of a file from its extension, after any compression extension: JSON Lines for
+    /// `.jsonl` and `.ndjson`, and csv otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match Compression::strip(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some("jsonl" | "ndjson") => Encoding::JsonLines,
+            _ => Encoding::Csv,
+        }
+    }
+}
+
+impl Compression {
+    /// Compression of a file from its extension, `.gz` or `.zst`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Extension of compressed files, after the extension of their encoding.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// Path without its compression extension, if it has one.
+    pub fn strip(path: &Path) -> PathBuf {
+        match Compression::from_path(path) {
+            Compression::None => path.to_path_buf(),
+            _ => path.with_extension(""),
+        }
+    }
+
+    /// Path with the compression extension appended.
+    pub fn append_to(self, path: &Path) -> PathBuf {
+        match self.extension() {
+            Some(ext) => {
+                let mut path = path.as_os_str().to_owned();
+                path.push(".");
+                path.push(ext);
+                PathBuf::from(path)
+            }
+            None => path.to_path_buf(),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            other => Err(format!("expected none, gzip or zstd, got '{}'", other)),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Sink {
+    fn open(path: &Path, append: bool) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        let file = BufWriter::new(file);
+
+        match Compression::from_path(path) {
+            Compression::None => Ok(Sink::Plain(file)),
+            Compression::Gzip => Ok(Sink::Gzip(GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+            Compression::Zstd => zstd::Encoder::new(file, 0).map(Sink::Zstd),
+        }
+    }
+
+    /// Write the trailer of the gzip member or zstd frame, and flush the file.
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            Sink::Plain(file) => file,
+            Sink::Gzip(encoder) => encoder.finish()?,
+            Sink::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(file) => file.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+            Sink::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+            Sink::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl RecordWriter {
+    /// Create a file of records, or append to it. Csv headers are only written to new files.
+    pub fn create<P: AsRef<Path>>(path: P, append: bool) -> io::Result<Self> {
+        let path = path.as_ref();
+        let sink = Sink::open(path, append)?;
+
+        let encoder = match Encoding::from_path(path) {
+            Encoding::Csv => Encoder::Csv(Box::new(
+                csv::WriterBuilder::new()
+                    .has_headers(!append)
+                    .from_writer(sink),
+            )),
+            Encoding::JsonLines => Encoder::JsonLines(sink),
+        };
+
+        Ok(Self { encoder })
+    }
+
+    pub fn serialize<T: Serialize>(&mut self, record: &T) -> io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Csv(writer) => writer.serialize(record).map_err(io::Error::from),
+            Encoder::JsonLines(sink) => {
+                serde_json::to_writer(&mut *sink, record)?;
+                sink.write_all(b"\n")
+            }
+        }
+    }
+
+    /// Finish the compressed stream. Files that are not finished are truncated.
+    pub fn finish(self) -> io::Result<()> {
+        let sink = match self.encoder {
+            Encoder::Csv(writer) => (*writer).into_inner().map_err(|e| e.into_error())?,
+            Encoder::JsonLines(sink) => sink,
+        };
+        sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        run: u32,
+        time: f64,
+    }
+
+    fn write(path: &Path, append: bool, runs: std::ops::Range<u32>) {
+        let mut writer = RecordWriter::create(path, append).unwrap();
+        for run in runs {
+            writer.serialize(&Row { run, time: 0.5 }).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        let path = Path::new("out/events.jsonl.zst");
+        assert_eq!(Compression::from_path(path), Compression::Zstd);
+        assert_eq!(Encoding::from_path(path), Encoding::JsonLines);
+        assert_eq!(Compression::strip(path), PathBuf::from("out/events.jsonl"));
+
+        let path = Path::new("events.csv");
+        assert_eq!(Compression::from_path(path), Compression::None);
+        assert_eq!(Encoding::from_path(path), Encoding::Csv);
+        assert_eq!(
+            Compression::Gzip.append_to(path),
+            PathBuf::from("events.csv.gz")
+        );
+    }
+
+    #[test]
+    fn test_appended_streams() {
+        // appended members and frames decode as one file, with one csv header
+        let path = std::env::temp_dir().join(format!(
+            "simulator_core_test_appended_{}.csv.gz",
+            std::process::id()
+        ));
+        write(&path, false, 0..2);
+        write(&path, true, 2..3);
+        let mut csv = String::new();
+        MultiGzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut csv)
+            .unwrap();
+        assert_eq!(csv, "run,time\n0,0.5\n1,0.5\n2,0.5\n");
+        std::fs::remove_file(&path).unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "simulator_core_test_appended_{}.jsonl.zst",
+            std::process::id()
+        ));
+        write(&path, false, 0..1);
+        write(&path, true, 1..2);
+        let json = zstd::decode_all(File::open(&path).unwrap()).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"run\":0,\"time\":0.5}\n{\"run\":1,\"time\":0.5}\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        // creating a file replaces it
+        let path = std::env::temp_dir().join(format!(
+            "simulator_core_test_appended_{}.csv",
+            std::process::id()
+        ));
+        write(&path, false, 0..2);
+        write(&path, false, 5..6);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "run,time\n5,0.5\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
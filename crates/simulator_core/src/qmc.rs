@@ -0,0 +1,354 @@
+//! Randomized quasi-Monte Carlo sources, backed by scrambled Sobol and Halton sequences.
+//!
+//! Replication `r` of a design is point `r` of the sequence, and a stream takes the dimension
+//! it was split off its parent with. Every draw of a stream is that coordinate under an Owen
+//! (nested uniform) scramble of its own, so the `n`th draws of the inputs of a model, taken over
+//! the replications, form a scrambled low-discrepancy point set, while within a replication
+//! every draw is independent and uniform. Each replication is therefore distributed as an
+//! unscrambled run, and the variance of an estimator has to be taken across independent
+//! designs.
+//!
+//! Dimensions past the supported ones, and streams split off streams, reuse dimensions with
+//! scrambles of their own. Their draws are still stratified one coordinate at a time, but not
+//! jointly with the other inputs. Variates drawing more than once per sample, such as Gaussian
+//! ones, are not stratified at all.
+
+use std::{fmt::Display, str::FromStr};
+
+use probability::source::Source;
+
+use crate::{splitmix64, SplitSource};
+
+/// Degree, inner coefficients and initial direction numbers of the primitive polynomials of
+/// Sobol dimensions after the first, from Joe and Kuo's `new-joe-kuo-6.21201` table.
+const SOBOL_POLYNOMIALS: [(u32, u32, &[u32]); 20] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+/// Bases of the Halton dimensions.
+const HALTON_BASES: [u64; 21] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73,
+];
+
+/// Low-discrepancy sequence of a quasi-Monte Carlo source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sequence {
+    /// Sobol sequence in base 2, up to 2^32 points
+    Sobol,
+
+    /// Halton sequence, with a prime base per dimension
+    Halton,
+}
+
+/// A randomized quasi-Monte Carlo source.
+#[derive(Clone, Debug)]
+pub struct QmcSource {
+    sequence: Sequence,
+    dimension: usize,
+
+    /// Seed of the scrambles, shared by every replication of the design
+    design: u64,
+
+    /// Index of the point
+    replication: u64,
+
+    /// Unscrambled Sobol coordinate of the point
+    point: u32,
+
+    splits: u32,
+    draws: u64,
+}
+
+impl Sequence {
+    /// Number of dimensions with a low-discrepancy projection.
+    pub fn dimensions(&self) -> usize {
+        match self {
+            Sequence::Sobol => SOBOL_POLYNOMIALS.len() + 1,
+            Sequence::Halton => HALTON_BASES.len(),
+        }
+    }
+}
+
+impl Display for Sequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sequence::Sobol => write!(f, "sobol"),
+            Sequence::Halton => write!(f, "halton"),
+        }
+    }
+}
+
+impl FromStr for Sequence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sobol" => Ok(Sequence::Sobol),
+            "halton" => Ok(Sequence::Halton),
+            _ => Err(format!(
+                "unknown sequence {:?}, expected sobol or halton",
+                s
+            )),
+        }
+    }
+}
+
+impl QmcSource {
+    /// Source of replication `replication` (zero-based) of a design, whose streams take
+    /// dimensions in order of splitting.
+    ///
+    /// Draws of the source itself are the first dimension.
+    pub fn new(sequence: Sequence, design: u64, replication: usize) -> Self {
+        Self::with_dimension(sequence, design, replication as u64, 0)
+    }
+
+    fn with_dimension(sequence: Sequence, design: u64, replication: u64, dimension: usize) -> Self {
+        Self {
+            sequence,
+            dimension,
+            design,
+            replication,
+            point: match sequence {
+                Sequence::Sobol => sobol(dimension, replication as u32),
+                Sequence::Halton => 0,
+            },
+            splits: 0,
+            draws: 0,
+        }
+    }
+
+    /// Dimension of the sequence drawn from.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+}
+
+impl Source for QmcSource {
+    fn read_u64(&mut self) -> u64 {
+        let scramble = splitmix64(self.design ^ splitmix64(self.draws));
+        self.draws += 1;
+
+        match self.sequence {
+            Sequence::Sobol => {
+                let high = owen_scramble(self.point, scramble);
+                // bits past the sequence's precision continue the scramble tree
+                let low = node_hash(scramble, 32, self.point as u64) as u32;
+                (high as u64) << 32 | low as u64
+            }
+            Sequence::Halton => {
+                let x = halton(HALTON_BASES[self.dimension], self.replication, scramble);
+                // casts saturate, should rounding reach one
+                (x * 2f64.powi(64)) as u64
+            }
+        }
+    }
+}
+
+impl SplitSource for QmcSource {
+    fn split(&mut self) -> Self {
+        let index = self.splits;
+        self.splits += 1;
+
+        Self::with_dimension(
+            self.sequence,
+            splitmix64(self.design ^ splitmix64(index as u64 + 1)),
+            self.replication,
+            index as usize % self.sequence.dimensions(),
+        )
+    }
+}
+
+/// Unscrambled point `index` of Sobol dimension `dimension`, as a 32-bit fraction.
+fn sobol(dimension: usize, index: u32) -> u32 {
+    let directions = sobol_directions(dimension);
+    (0..32)
+        .filter(|k| index >> k & 1 == 1)
+        .fold(0, |x, k| x ^ directions[k])
+}
+
+/// Direction numbers of a Sobol dimension.
+fn sobol_directions(dimension: usize) -> [u32; 32] {
+    let mut v = [0u32; 32];
+    if dimension == 0 {
+        // van der Corput sequence
+        for (k, v) in v.iter_mut().enumerate() {
+            *v = 1 << (31 - k);
+        }
+        return v;
+    }
+
+    let (s, a, m) = SOBOL_POLYNOMIALS[dimension - 1];
+    let s = s as usize;
+    for (k, m) in m.iter().enumerate() {
+        v[k] = m << (31 - k);
+    }
+    for k in s..32 {
+        v[k] = v[k - s] ^ (v[k - s] >> s);
+        for j in 1..s {
+            if a >> (s - 1 - j) & 1 == 1 {
+                v[k] ^= v[k - j];
+            }
+        }
+    }
+
+    v
+}
+
+/// Owen scramble of a 32-bit fraction: every bit is flipped by a hash of the bits above it.
+fn owen_scramble(x: u32, seed: u64) -> u32 {
+    (0..32).fold(0, |out, j| {
+        let prefix = (x as u64) >> (32 - j);
+        let flip = node_hash(seed, j, prefix) as u32 & 1;
+        out | ((x >> (31 - j) & 1) ^ flip) << (31 - j)
+    })
+}
+
+/// Hash of a node of the scramble tree, at `depth` below the root with the digits `prefix`.
+fn node_hash(seed: u64, depth: u32, prefix: u64) -> u64 {
+    splitmix64(seed ^ splitmix64((depth as u64) << 58 ^ prefix))
+}
+
+/// Point `index` of the Halton dimension with base `base`, with every digit permuted by a
+/// random permutation drawn from the digits before it.
+fn halton(base: u64, index: u64, seed: u64) -> f64 {
+    // enough digits for the precision of a double
+    let digits = (53.0 / (base as f64).log2()).ceil() as u32;
+
+    let mut strata = (0..base).collect::<Vec<_>>();
+    let (mut rest, mut prefix, mut weight) = (index, 0u64, 1.0);
+    let mut x = 0.0;
+    for j in 0..digits {
+        let digit = rest % base;
+        rest /= base;
+
+        // Fisher-Yates shuffle of the digits, seeded by the node
+        let mut state = node_hash(seed, j, prefix);
+        for (i, s) in strata.iter_mut().enumerate() {
+            *s = i as u64;
+        }
+        for i in (1..base as usize).rev() {
+            state = splitmix64(state);
+            strata.swap(i, (state % (i as u64 + 1)) as usize);
+        }
+
+        weight /= base as f64;
+        x += strata[digit as usize] as f64 * weight;
+        prefix = prefix * base + digit;
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::distribution::{self, Mean};
+
+    use super::*;
+    use crate::SingleVariateIterator;
+
+    /// Streams split off the roots of `k` replications of a design.
+    fn streams(sequence: Sequence, k: usize, design: u64) -> Vec<(QmcSource, QmcSource)> {
+        (0..k)
+            .map(|r| {
+                let mut root = QmcSource::new(sequence, design, r);
+                (root.split(), root.split())
+            })
+            .collect()
+    }
+
+    /// Strata of `k` equal strata of the unit interval hit by the draws.
+    fn strata(draws: impl Iterator<Item = f64>, k: usize) -> Vec<usize> {
+        let mut strata = draws
+            .map(|x| (x * k as f64).floor() as usize)
+            .collect::<Vec<_>>();
+        strata.sort();
+        strata
+    }
+
+    #[test]
+    fn test_unscrambled_sequences() {
+        let points = |d| (0..8).map(move |n| sobol(d, n) as f64 / 2f64.powi(32));
+        assert!(points(0).eq([0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875]));
+        assert!(points(1).eq([0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875]));
+    }
+
+    #[test]
+    fn test_draws_are_stratified_across_replications() {
+        for (sequence, k) in [(Sequence::Sobol, 64), (Sequence::Halton, 81)] {
+            let mut streams = streams(sequence, k, 11);
+            for _ in 0..5 {
+                // the first dimension is in base 2 for both sequences
+                let first = streams.iter_mut().take(64).map(|(a, _)| a.read::<f64>());
+                assert_eq!(strata(first, 64), (0..64).collect::<Vec<_>>());
+                let second = streams.iter_mut().map(|(_, b)| b.read::<f64>());
+                assert_eq!(strata(second, k), (0..k).collect::<Vec<_>>());
+            }
+        }
+
+        // draws of one replication are scrambled independently
+        let mut source = QmcSource::new(Sequence::Sobol, 1, 0).split();
+        assert_ne!(source.read_u64(), source.read_u64());
+    }
+
+    #[test]
+    fn test_streams_form_a_net() {
+        // the first two Sobol dimensions put one of 256 replications in every cell of a 16x16
+        // grid, at every draw
+        let mut streams = streams(Sequence::Sobol, 256, 4);
+        for _ in 0..3 {
+            let cells = streams.iter_mut().map(|(x, y)| {
+                let (x, y) = (x.read::<f64>(), y.read::<f64>());
+                ((x * 16.0).floor() * 16.0 + (y * 16.0).floor()) / 256.0
+            });
+            assert_eq!(strata(cells, 256), (0..256).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_randomized_mean() {
+        // plugged into a variate iterator, the error of the mean over a design is far below
+        // that of a pseudo-random source
+        let k = 256;
+        let exp = distribution::Exponential::new(2.0);
+        let error = |samples: Vec<f64>| (samples.iter().sum::<f64>() / k as f64 - exp.mean()).abs();
+
+        let qmc = (0..20)
+            .map(|design| {
+                let samples = (0..k)
+                    .map(|r| {
+                        let source = QmcSource::new(Sequence::Sobol, design, r).split();
+                        SingleVariateIterator::new(exp, source).next().unwrap()
+                    })
+                    .collect();
+                error(samples)
+            })
+            .sum::<f64>();
+        let naive = (0..20)
+            .map(|seed| {
+                let source = probability::source::default(seed + 1);
+                error(SingleVariateIterator::new(exp, source).take(k).collect())
+            })
+            .sum::<f64>();
+
+        assert!(qmc * 10.0 < naive, "{} vs {}", qmc, naive);
+    }
+}
@@ -0,0 +1,101 @@
+//! Ratio estimators over the cycles of a regenerative simulation.
+//!
+//! Between two regeneration points a cycle accumulates some quantity `y`, such as blocked calls,
+//! over some length `t`, such as initiated calls. Cycles are independent and identically
+//! distributed, so the long-run ratio `E[y] / E[t]` is estimated by `sum(y) / sum(t)` with a
+//! confidence interval from the central limit theorem for ratios, without any warmup.
+
+use crate::stats::student_t_quantile;
+
+/// Long-run ratio estimated from regeneration cycles.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct RatioEstimate {
+    pub cycles: usize,
+    pub ratio: f64,
+
+    /// Half-width of the confidence interval for the ratio
+    pub half_width: f64,
+}
+
+impl RatioEstimate {
+    /// Bounds of the confidence interval.
+    pub fn ci(&self) -> (f64, f64) {
+        (self.ratio - self.half_width, self.ratio + self.half_width)
+    }
+}
+
+/// Estimate `E[y] / E[t]` from the `numerators` `y` and `denominators` `t` of every cycle.
+pub fn ratio_estimate(
+    numerators: &[f64],
+    denominators: &[f64],
+    confidence: f64,
+) -> Result<RatioEstimate, String> {
+    if numerators.len() != denominators.len() {
+        return Err("every cycle needs a numerator and a denominator".to_string());
+    }
+    let n = numerators.len();
+    if n < 2 {
+        return Err("ratio estimates need at least two regeneration cycles".to_string());
+    }
+
+    let mean_y = numerators.iter().sum::<f64>() / n as f64;
+    let mean_t = denominators.iter().sum::<f64>() / n as f64;
+    if mean_t <= 0.0 {
+        return Err("regeneration cycles must have a positive length".to_string());
+    }
+    let ratio = mean_y / mean_t;
+
+    // variance of y - ratio * t, whose mean is zero at the true ratio
+    let variance = numerators
+        .iter()
+        .zip(denominators)
+        .map(|(y, t)| ((y - mean_y) - ratio * (t - mean_t)).powi(2))
+        .sum::<f64>()
+        / (n - 1) as f64;
+
+    Ok(RatioEstimate {
+        cycles: n,
+        ratio,
+        half_width: student_t_quantile(0.5 + confidence / 2.0, (n - 1) as f64) * variance.sqrt()
+            / (mean_t * (n as f64).sqrt()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::{
+        distribution::{Exponential, Sample},
+        source,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_ratio_estimate() {
+        // cycles of exponential length t, accumulating y = t / 4 plus noise
+        let mut source = source::default(3);
+        let exp = Exponential::new(1.0);
+
+        let covered = (0..200)
+            .filter(|_| {
+                let (y, t): (Vec<_>, Vec<_>) = (0..50)
+                    .map(|_| {
+                        let t = exp.sample(&mut source);
+                        (t / 4.0 + exp.sample(&mut source) - 1.0, t)
+                    })
+                    .unzip();
+                let estimate = ratio_estimate(&y, &t, 0.9).unwrap();
+                let (low, high) = estimate.ci();
+                low < 0.25 && 0.25 < high
+            })
+            .count();
+        assert!((165..=195).contains(&covered), "{} of 200 covered", covered);
+
+        // proportional cycles have an exact ratio
+        let estimate = ratio_estimate(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0], 0.95).unwrap();
+        assert_eq!((estimate.ratio, estimate.half_width), (0.5, 0.0));
+
+        assert!(ratio_estimate(&[1.0], &[1.0], 0.95).is_err());
+        assert!(ratio_estimate(&[1.0, 2.0], &[0.0, 0.0], 0.95).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! Sequential ranking-and-selection procedures over independent replications.
+//!
+//! Observations are fed one replication at a time, and every procedure stops as soon as its
+//! decision holds with the requested probability. Replication `r` of every system may share
+//! random numbers, since decisions only depend on the variance of differences.
+
+/// Decision on a stochastic constraint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub enum Feasibility {
+    Feasible,
+    Infeasible,
+
+    /// Not enough observations yet
+    Undecided,
+}
+
+/// Sequential check of the constraint `E[Y] < limit`, after Andradóttir and Kim (2010).
+///
+/// Systems with a mean below `limit - tolerance` are declared feasible and systems with a mean
+/// above `limit + tolerance` infeasible, each with probability at least `1 - error`.
+/// Either decision may be made in between.
+#[derive(Clone, Debug)]
+pub struct FeasibilityCheck {
+    limit: f64,
+    tolerance: f64,
+    initial: usize,
+    h2: f64,
+
+    observations: Vec<f64>,
+    variance: Option<f64>,
+    decision: Feasibility,
+}
+
+/// Kim and Nelson's fully sequential procedure for the system with the smallest mean.
+///
+/// The selected system is within `indifference` of the best with probability at least
+/// `1 - error`.
+#[derive(Clone, Debug)]
+pub struct KnSelection {
+    indifference: f64,
+    initial: usize,
+    h2: f64,
+
+    /// Observations of every system
+    observations: Vec<Vec<f64>>,
+
+    /// Variances of pairwise differences after the first stage
+    variances: Option<Vec<Vec<f64>>>,
+
+    alive: Vec<bool>,
+}
+
+impl FeasibilityCheck {
+    /// Check a constraint with `initial` observations in the first stage, at least 2.
+    pub fn new(limit: f64, tolerance: f64, initial: usize, error: f64) -> Self {
+        Self {
+            limit,
+            tolerance,
+            initial,
+            h2: 2.0 * eta(error, initial) * (initial - 1) as f64,
+            observations: Vec::new(),
+            variance: None,
+            decision: Feasibility::Undecided,
+        }
+    }
+
+    /// Add the next observation, returning the decision so far.
+    pub fn add(&mut self, y: f64) -> Feasibility {
+        if self.decision != Feasibility::Undecided {
+            return self.decision;
+        }
+
+        self.observations.push(y);
+        let r = self.observations.len();
+        if r < self.initial {
+            return self.decision;
+        }
+
+        let variance = *self
+            .variance
+            .get_or_insert_with(|| sample_variance(&self.observations));
+        let sum = self
+            .observations
+            .iter()
+            .map(|y| y - self.limit)
+            .sum::<f64>();
+        let bound = continuation(r, self.tolerance, self.h2, variance);
+
+        self.decision = match sum {
+            s if s <= -bound => Feasibility::Feasible,
+            s if s >= bound => Feasibility::Infeasible,
+            _ => Feasibility::Undecided,
+        };
+        self.decision
+    }
+
+    pub fn decision(&self) -> Feasibility {
+        self.decision
+    }
+
+    /// Observations added until the decision.
+    pub fn observations(&self) -> usize {
+        self.observations.len()
+    }
+}
+
+impl KnSelection {
+    /// Select among `systems` with `initial` observations in the first stage, at least 2.
+    pub fn new(systems: usize, indifference: f64, initial: usize, error: f64) -> Self {
+        let pairwise_error = error / (systems.max(2) - 1) as f64;
+
+        Self {
+            indifference,
+            initial,
+            h2: 2.0 * eta(pairwise_error, initial) * (initial - 1) as f64,
+            observations: vec![Vec::new(); systems],
+            variances: None,
+            alive: vec![true; systems],
+        }
+    }
+
+    /// Systems not yet eliminated.
+    pub fn surviving(&self) -> Vec<usize> {
+        (0..self.alive.len()).filter(|i| self.alive[*i]).collect()
+    }
+
+    /// The selected system, once every other one is eliminated.
+    pub fn selected(&self) -> Option<usize> {
+        match self.surviving().as_slice() {
+            [best] => Some(*best),
+            _ => None,
+        }
+    }
+
+    /// Add the next observation of every surviving system, in the order of `surviving`.
+    pub fn add(&mut self, observations: &[f64]) {
+        let surviving = self.surviving();
+        assert_eq!(observations.len(), surviving.len());
+        for (i, y) in surviving.iter().zip(observations) {
+            self.observations[*i].push(*y);
+        }
+
+        let r = self.observations[surviving[0]].len();
+        if r < self.initial || surviving.len() < 2 {
+            return;
+        }
+
+        let observations = &self.observations;
+        let variances = self.variances.get_or_insert_with(|| {
+            (0..observations.len())
+                .map(|i| {
+                    (0..observations.len())
+                        .map(|l| {
+                            let diff = observations[i]
+                                .iter()
+                                .zip(&observations[l])
+                                .map(|(a, b)| a - b)
+                                .collect::<Vec<_>>();
+                            sample_variance(&diff)
+                        })
+                        .collect()
+                })
+                .collect()
+        });
+
+        let sums = surviving
+            .iter()
+            .map(|i| self.observations[*i].iter().sum::<f64>())
+            .collect::<Vec<_>>();
+        let eliminated = surviving
+            .iter()
+            .enumerate()
+            .filter(|&(a, &i)| {
+                surviving.iter().enumerate().any(|(b, &l)| {
+                    i != l
+                        && sums[a] - sums[b]
+                            > continuation(r, self.indifference, self.h2, variances[i][l])
+                })
+            })
+            .map(|(_, i)| *i)
+            .collect::<Vec<_>>();
+
+        for i in eliminated {
+            self.alive[i] = false;
+        }
+    }
+
+    /// Sample mean of every system's observations.
+    pub fn means(&self) -> Vec<f64> {
+        self.observations
+            .iter()
+            .map(|obs| obs.iter().sum::<f64>() / obs.len().max(1) as f64)
+            .collect()
+    }
+}
+
+/// Constant of the continuation region for a per-comparison error with `initial` observations.
+fn eta(error: f64, initial: usize) -> f64 {
+    0.5 * ((2.0 * error).powf(-2.0 / (initial - 1) as f64) - 1.0)
+}
+
+/// Half-width of the triangular continuation region of a sum after `r` observations.
+fn continuation(r: usize, tolerance: f64, h2: f64, variance: f64) -> f64 {
+    (h2 * variance / (2.0 * tolerance) - tolerance * r as f64 / 2.0).max(0.0)
+}
+
+fn sample_variance(samples: &[f64]) -> f64 {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::{
+        distribution::{Gaussian, Sample},
+        source,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_feasibility_check() {
+        let mut source = source::default(7);
+        let noise = Gaussian::new(0.0, 1.0);
+
+        let mut decide = |mean: f64| {
+            let mut check = FeasibilityCheck::new(0.0, 0.2, 10, 0.05);
+            while check.decision() == Feasibility::Undecided {
+                check.add(mean + noise.sample(&mut source));
+            }
+            check.decision()
+        };
+
+        let trials = 200;
+        let mut correct = |mean, expected| (0..trials).filter(|_| decide(mean) == expected).count();
+
+        // at the tolerance, decisions are correct with probability at least 0.95
+        assert!(correct(-0.2, Feasibility::Feasible) as f64 >= 0.93 * trials as f64);
+        assert!(correct(0.2, Feasibility::Infeasible) as f64 >= 0.93 * trials as f64);
+        assert_eq!(correct(-2.0, Feasibility::Feasible), trials);
+    }
+
+    #[test]
+    fn test_kn_selection() {
+        let mut source = source::default(11);
+        let noise = Gaussian::new(0.0, 1.0);
+        let means = [0.0, 0.25, 0.5, 1.0];
+
+        let trials = 100;
+        let mut correct = 0;
+        for _ in 0..trials {
+            let mut kn = KnSelection::new(means.len(), 0.25, 10, 0.05);
+            while kn.selected().is_none() {
+                // common noise, as under common random numbers
+                let common = noise.sample(&mut source);
+                let obs = kn
+                    .surviving()
+                    .iter()
+                    .map(|i| means[*i] + common + 0.5 * noise.sample(&mut source))
+                    .collect::<Vec<_>>();
+                kn.add(&obs);
+            }
+
+            correct += (kn.selected() == Some(0)) as usize;
+        }
+
+        assert!(correct as f64 >= 0.93 * trials as f64, "{}", correct);
+    }
+}
@@ -0,0 +1,293 @@
+//! Summary statistics of independent replications.
+
+use probability::distribution::{self, Distribution, Inverse};
+
+/// Confidence level used when none is given.
+pub const DEFAULT_CONFIDENCE: f64 = 0.95;
+
+/// Sample statistics with a Student-t confidence interval for the mean.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct Summary {
+    pub n: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+
+    /// Half-width of the confidence interval for the mean
+    pub half_width: f64,
+
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// Summarise samples, with a confidence interval at `confidence`, e.g. 0.95.
+    ///
+    /// The interval is unbounded with fewer than 2 samples.
+    pub fn new(samples: &[f64], confidence: f64) -> Self {
+        let n = samples.len();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let std_dev = match n > 1 {
+            true => {
+                (samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+            }
+            false => f64::NAN,
+        };
+
+        let half_width = match n > 1 {
+            true => {
+                student_t_quantile(0.5 + confidence / 2.0, (n - 1) as f64) * std_dev
+                    / (n as f64).sqrt()
+            }
+            false => f64::INFINITY,
+        };
+
+        Self {
+            n,
+            mean,
+            std_dev,
+            half_width,
+            min: samples.iter().copied().fold(f64::INFINITY, f64::min),
+            max: samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    /// Bounds of the confidence interval.
+    pub fn ci(&self) -> (f64, f64) {
+        (self.mean - self.half_width, self.mean + self.half_width)
+    }
+}
+
+/// Two-sample t test of a difference in means.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct TestResult {
+    /// Estimated difference, `mean(a) - mean(b)`
+    pub difference: f64,
+
+    pub t: f64,
+    pub dof: f64,
+
+    /// Two-sided p-value of no difference
+    pub p_value: f64,
+
+    /// Cohen's d, the difference in units of standard deviation
+    pub effect_size: f64,
+}
+
+/// Paired t test of `a[i] - b[i]`, with the effect size in units of the differences' standard
+/// deviation.
+pub fn paired_t_test(a: &[f64], b: &[f64]) -> Result<TestResult, String> {
+    if a.len() != b.len() {
+        return Err("paired samples must have the same length".to_string());
+    }
+    if a.len() < 2 {
+        return Err("t tests need at least two samples".to_string());
+    }
+
+    let diff = a.iter().zip(b).map(|(a, b)| a - b).collect::<Vec<_>>();
+    let summary = Summary::new(&diff, DEFAULT_CONFIDENCE);
+    let se = summary.std_dev / (summary.n as f64).sqrt();
+
+    Ok(t_test(
+        summary.mean,
+        se,
+        (summary.n - 1) as f64,
+        summary.std_dev,
+    ))
+}
+
+/// Welch's t test of independent samples, with the effect size in units of their pooled
+/// standard deviation.
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> Result<TestResult, String> {
+    if a.len() < 2 || b.len() < 2 {
+        return Err("t tests need at least two samples".to_string());
+    }
+
+    let (a, b) = (
+        Summary::new(a, DEFAULT_CONFIDENCE),
+        Summary::new(b, DEFAULT_CONFIDENCE),
+    );
+    let (va, vb) = (
+        a.std_dev.powi(2) / a.n as f64,
+        b.std_dev.powi(2) / b.n as f64,
+    );
+    let pooled = (((a.n - 1) as f64 * a.std_dev.powi(2) + (b.n - 1) as f64 * b.std_dev.powi(2))
+        / (a.n + b.n - 2) as f64)
+        .sqrt();
+
+    Ok(t_test(
+        a.mean - b.mean,
+        (va + vb).sqrt(),
+        welch_dof(&a, &b),
+        pooled,
+    ))
+}
+
+/// Half-width of the Welch interval for the difference of two independent means.
+pub fn welch_half_width(a: &Summary, b: &Summary, confidence: f64) -> f64 {
+    let (va, vb) = (
+        a.std_dev.powi(2) / a.n as f64,
+        b.std_dev.powi(2) / b.n as f64,
+    );
+
+    match va + vb > 0.0 {
+        true => student_t_quantile(0.5 + confidence / 2.0, welch_dof(a, b)) * (va + vb).sqrt(),
+        false => 0.0,
+    }
+}
+
+/// Welch-Satterthwaite degrees of freedom of the difference of two independent means.
+fn welch_dof(a: &Summary, b: &Summary) -> f64 {
+    let (va, vb) = (
+        a.std_dev.powi(2) / a.n as f64,
+        b.std_dev.powi(2) / b.n as f64,
+    );
+    (va + vb).powi(2) / (va.powi(2) / (a.n - 1) as f64 + vb.powi(2) / (b.n - 1) as f64)
+}
+
+/// Test of `difference` with standard error `se`, where samples without variance differ
+/// certainly or not at all.
+fn t_test(difference: f64, se: f64, dof: f64, std_dev: f64) -> TestResult {
+    let (t, p_value, effect_size) = match se > 0.0 {
+        true => (
+            difference / se,
+            student_t_two_sided_p(difference / se, dof),
+            difference / std_dev,
+        ),
+        false => match difference == 0.0 {
+            true => (0.0, 1.0, 0.0),
+            false => (
+                difference.signum() * f64::INFINITY,
+                0.0,
+                difference.signum() * f64::INFINITY,
+            ),
+        },
+    };
+
+    TestResult {
+        difference,
+        t,
+        // without variance the degrees of freedom are 0 / 0
+        dof: match dof.is_finite() {
+            true => dof,
+            false => f64::INFINITY,
+        },
+        p_value,
+        effect_size,
+    }
+}
+
+/// Sample correlation of paired samples, NaN if either has no variance.
+pub fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len().min(b.len()) as f64;
+    let mean_a = a.iter().sum::<f64>() / n;
+    let mean_b = b.iter().sum::<f64>() / n;
+
+    let (cov, var_a, var_b) = a
+        .iter()
+        .zip(b)
+        .fold((0.0, 0.0, 0.0), |(c, va, vb), (x, y)| {
+            let (dx, dy) = (x - mean_a, y - mean_b);
+            (c + dx * dy, va + dx * dx, vb + dy * dy)
+        });
+
+    cov / (var_a * var_b).sqrt()
+}
+
+/// Quantile of Student's t distribution with `dof` degrees of freedom.
+pub fn student_t_quantile(p: f64, dof: f64) -> f64 {
+    match p {
+        p if p <= 0.0 => f64::NEG_INFINITY,
+        p if p >= 1.0 => f64::INFINITY,
+        p if p < 0.5 => -student_t_quantile(1.0 - p, dof),
+        0.5 => 0.0,
+        p => {
+            // P(|T| > t) = I_x(dof / 2, 1 / 2) with x = dof / (dof + t^2)
+            let x = distribution::Beta::new(dof / 2.0, 0.5, 0.0, 1.0).inverse(2.0 * (1.0 - p));
+            (dof * (1.0 - x) / x).sqrt()
+        }
+    }
+}
+
+/// Probability that Student's t with `dof` degrees of freedom exceeds `|t|` in either direction.
+pub fn student_t_two_sided_p(t: f64, dof: f64) -> f64 {
+    match t.is_finite() {
+        true => distribution::Beta::new(dof / 2.0, 0.5, 0.0, 1.0).distribution(dof / (dof + t * t)),
+        false => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_student_t() {
+        // tabulated quantiles
+        for (p, dof, t) in [
+            (0.975, 1.0, 12.7062),
+            (0.975, 9.0, 2.2622),
+            (0.995, 19.0, 2.8609),
+            (0.95, 30.0, 1.6973),
+            (0.975, 1e6, 1.95996),
+        ] {
+            let q = student_t_quantile(p, dof);
+            assert!((q - t).abs() < 1e-3, "t({}, {}) = {}", p, dof, q);
+            assert!((student_t_quantile(1.0 - p, dof) + t).abs() < 1e-3);
+            assert!((student_t_two_sided_p(t, dof) - 2.0 * (1.0 - p)).abs() < 1e-4);
+        }
+
+        assert_eq!(student_t_quantile(0.5, 4.0), 0.0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(&[1.0, 2.0, 3.0, 4.0, 5.0], 0.95);
+        assert_eq!(summary.n, 5);
+        assert_eq!(summary.mean, 3.0);
+        assert!((summary.std_dev - 2.5f64.sqrt()).abs() < 1e-12);
+        assert!((summary.half_width - 2.7764 * (0.5f64).sqrt()).abs() < 1e-3);
+        assert_eq!((summary.min, summary.max), (1.0, 5.0));
+
+        let (low, high) = summary.ci();
+        assert!(low < 3.0 && high > 3.0);
+
+        assert_eq!(Summary::new(&[1.0], 0.95).half_width, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_correlation() {
+        let a = [1.0, 2.0, 3.0, 4.0];
+        assert!((correlation(&a, &[2.0, 4.0, 6.0, 8.0]) - 1.0).abs() < 1e-12);
+        assert!((correlation(&a, &[4.0, 3.0, 2.0, 1.0]) + 1.0).abs() < 1e-12);
+        assert!(correlation(&a, &[1.0; 4]).is_nan());
+    }
+
+    #[test]
+    fn test_t_tests() {
+        let a = [5.1, 4.9, 5.6, 5.8, 6.0, 5.2];
+        let b = [4.8, 4.7, 5.1, 5.4, 5.9, 4.9];
+
+        // differences 0.3, 0.2, 0.5, 0.4, 0.1, 0.3 have mean 0.3 and sd sqrt(0.02)
+        let paired = paired_t_test(&a, &b).unwrap();
+        assert!((paired.difference - 0.3).abs() < 1e-12);
+        assert!((paired.t - 0.3 / (0.02f64 / 6.0).sqrt()).abs() < 1e-9);
+        assert_eq!(paired.dof, 5.0);
+        assert!((paired.p_value - student_t_two_sided_p(paired.t, 5.0)).abs() < 1e-12);
+        assert!(paired.p_value < 0.01);
+        assert!((paired.effect_size - 0.3 / 0.02f64.sqrt()).abs() < 1e-9);
+
+        // ignoring the pairing, the same samples barely differ
+        let welch = welch_t_test(&a, &b).unwrap();
+        assert!((welch.difference - 0.3).abs() < 1e-12);
+        assert!(welch.p_value > 0.1 && welch.dof > 5.0 && welch.dof < 10.0);
+        assert!(welch.effect_size > 0.0 && welch.effect_size < 1.0);
+
+        // samples without variance
+        let same = paired_t_test(&[1.0; 3], &[1.0; 3]).unwrap();
+        assert_eq!((same.p_value, same.effect_size), (1.0, 0.0));
+        assert_eq!(welch_t_test(&[2.0; 3], &[1.0; 4]).unwrap().p_value, 0.0);
+
+        assert!(paired_t_test(&a, &b[1..]).is_err());
+        assert!(welch_t_test(&a, &[1.0]).is_err());
+    }
+}
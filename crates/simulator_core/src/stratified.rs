@@ -0,0 +1,215 @@
+//! Sources stratified across replications, for stratified sampling and Latin hypercube designs.
+//!
+//! Replication `r` of `k` takes its `i`th draw from stratum `π_i(r)` of `k` equal strata of the
+//! unit interval, where `π_i` is a random permutation shared by every replication of a design.
+//! Over the `k` replications, every draw therefore covers each stratum exactly once, while each
+//! replication on its own still sees independent uniform draws.
+//!
+//! Streams split off a stratified source get permutations of their own, so stratifying every
+//! input variate of a model gives a Latin hypercube design over the inputs. Variates sampled by
+//! inversion, with one draw per sample, are stratified exactly; other samplers still get
+//! uniform draws, but their samples are not stratified.
+//!
+//! Replications of one design are dependent, so the variance of an estimator has to be taken
+//! across independent designs.
+
+use probability::source::Source;
+
+use crate::{splitmix64, SplitSource};
+
+/// A source whose draws are stratified across the replications of a design.
+#[derive(Clone, Debug)]
+pub struct StratifiedSource<S> {
+    /// Independent offsets within strata
+    source: S,
+
+    /// Seed of the permutations, shared by every replication of the design
+    design: u64,
+
+    replication: usize,
+    replications: usize,
+
+    /// Whether draws of this stream are stratified
+    stratified: bool,
+
+    /// Streams split off with these indices are stratified, as a bit mask
+    selected: u64,
+
+    splits: u32,
+    draws: u64,
+}
+
+impl<S> StratifiedSource<S>
+where
+    S: Source,
+{
+    /// Source of replication `replication` (zero-based) of `replications`, with every stream
+    /// split off it stratified.
+    ///
+    /// Draws of the source itself are not stratified, only those of its streams.
+    pub fn new(source: S, design: u64, replication: usize, replications: usize) -> Self {
+        assert!(replication < replications);
+
+        Self {
+            source,
+            design,
+            replication,
+            replications,
+            stratified: false,
+            selected: u64::MAX,
+            splits: 0,
+            draws: 0,
+        }
+    }
+
+    /// Only stratify the streams split off with the given indices, in order of splitting.
+    pub fn with_selected(mut self, indices: &[usize]) -> Self {
+        self.selected = indices
+            .iter()
+            .filter(|i| **i < 64)
+            .fold(0, |mask, i| mask | 1 << i);
+        self
+    }
+
+    /// Stratum of the next draw.
+    fn stratum(&self) -> u64 {
+        // Fisher-Yates shuffle of the strata, seeded by the design and the draw
+        let mut state = splitmix64(self.design ^ splitmix64(self.draws));
+        let mut strata = (0..self.replications as u64).collect::<Vec<_>>();
+        for i in (1..strata.len()).rev() {
+            state = splitmix64(state);
+            strata.swap(i, (state % (i as u64 + 1)) as usize);
+        }
+
+        strata[self.replication]
+    }
+}
+
+impl<S> Source for StratifiedSource<S>
+where
+    S: Source,
+{
+    fn read_u64(&mut self) -> u64 {
+        let offset = self.source.read_u64();
+        if !self.stratified {
+            return offset;
+        }
+
+        let stratum = self.stratum();
+        self.draws += 1;
+
+        (((stratum as u128) << 64 | offset as u128) / self.replications as u128) as u64
+    }
+}
+
+impl<S> SplitSource for StratifiedSource<S>
+where
+    S: SplitSource,
+{
+    fn split(&mut self) -> Self {
+        let index = self.splits;
+        self.splits += 1;
+
+        Self {
+            source: self.source.split(),
+            design: splitmix64(self.design ^ splitmix64(index as u64 + 1)),
+            replication: self.replication,
+            replications: self.replications,
+            stratified: index < 64 && self.selected & 1 << index != 0,
+            // streams of streams are not stratified
+            selected: 0,
+            splits: 0,
+            draws: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use probability::{
+        distribution::{Exponential, Sample},
+        source,
+    };
+
+    use super::*;
+
+    fn replications(k: usize, design: u64) -> Vec<StratifiedSource<source::Default>> {
+        (0..k)
+            .map(|r| {
+                let mut root = StratifiedSource::new(source::default(r as u64 + 1), design, r, k);
+                root.split()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_draws_cover_every_stratum() {
+        let k = 16;
+        let mut streams = replications(k, 5);
+
+        for _ in 0..50 {
+            let mut strata = streams
+                .iter_mut()
+                .map(|s| (s.read::<f64>() * k as f64).floor() as usize)
+                .collect::<Vec<_>>();
+            strata.sort();
+            assert_eq!(strata, (0..k).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_latin_hypercube_streams() {
+        let k = 8;
+        let mut roots = (0..k)
+            .map(|r| StratifiedSource::new(source::default(r as u64 + 1), 9, r, k))
+            .collect::<Vec<_>>();
+        let mut first = roots.iter_mut().map(|s| s.split()).collect::<Vec<_>>();
+        let mut second = roots.iter_mut().map(|s| s.split()).collect::<Vec<_>>();
+
+        let strata = |streams: &mut [StratifiedSource<source::Default>]| {
+            streams
+                .iter_mut()
+                .map(|s| (s.read::<f64>() * k as f64).floor() as usize)
+                .collect::<Vec<_>>()
+        };
+
+        // both inputs cover every stratum, in different orders
+        let (a, b) = (strata(&mut first), strata(&mut second));
+        let mut sorted = a.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..k).collect::<Vec<_>>());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_selected_streams() {
+        let mut root = StratifiedSource::new(source::default(1), 3, 0, 4).with_selected(&[1]);
+        assert!(!root.split().stratified);
+        assert!(root.split().stratified);
+        assert!(!root.split().stratified);
+    }
+
+    #[test]
+    fn test_stratified_mean() {
+        // the mean over a design is much closer to the true mean than independent samples
+        let k = 100;
+        let exp = Exponential::new(2.0);
+        let design_error = |design: u64| {
+            let mean = replications(k, design)
+                .iter_mut()
+                .map(|s| exp.sample(s))
+                .sum::<f64>()
+                / k as f64;
+            (mean - 0.5).abs()
+        };
+
+        let mut source = source::default(2);
+        let naive_error = |source: &mut source::Default| {
+            ((0..k).map(|_| exp.sample(source)).sum::<f64>() / k as f64 - 0.5).abs()
+        };
+
+        let stratified = (0..20).map(design_error).sum::<f64>();
+        let naive = (0..20).map(|_| naive_error(&mut source)).sum::<f64>();
+        assert!(stratified * 5.0 < naive, "{} vs {}", stratified, naive);
+    }
+}
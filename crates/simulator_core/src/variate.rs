@@ -0,0 +1,1073 @@
+//! Input distributions that can be selected at runtime.
+//!
+//! Every distribution is specified as a string such as `lognormal(4.2, 0.8)`,
+//! which is parsed into a [`Variate`].
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use probability::{
+    distribution::{self, Distribution, Inverse, Sample},
+    source::Source,
+};
+
+use crate::{fit, Clamped, ExponentialLoc, Truncated};
+
+/// A distribution selected at runtime.
+#[derive(Clone, Debug)]
+pub enum Variate {
+    /// `exponential(mean[, loc])`
+    Exponential(ExponentialLoc),
+    /// `uniform(min, max)`
+    Uniform(distribution::Uniform),
+    /// `normal(mean, stddev)`
+    Normal(distribution::Gaussian),
+    /// `lognormal(mu, sigma)`, parameters of the underlying normal distribution
+    Lognormal(distribution::Lognormal),
+    /// `weibull(shape, scale)`
+    Weibull(Weibull),
+    /// `gamma(shape, scale)`
+    Gamma(distribution::Gamma),
+    /// `erlang(k, mean)`
+    Erlang(Erlang),
+    /// `hyperexponential(p1, mean1, p2, mean2, ...)`
+    Hyperexponential(Hyperexponential),
+    /// `triangular(min, mode, max)`
+    Triangular(distribution::Triangular),
+    /// `empirical(x1, x2, ...)` or `empirical("file.csv", "column")`
+    Empirical(Empirical),
+    /// `truncated(<variate>, low, high)`
+    Truncated(Box<Truncated<Variate>>),
+    /// `clamped(<variate>, low, high)`
+    Clamped(Box<Clamped<Variate>>),
+    /// `scaled(<variate>, factor)`, the variate multiplied by a positive factor
+    Scaled(Box<Variate>, f64),
+}
+
+/// Weibull distribution.
+#[derive(Clone, Debug)]
+pub struct Weibull {
+    shape: f64,
+    scale: f64,
+}
+
+/// Erlang distribution, the sum of `k` exponential phases.
+#[derive(Clone, Debug)]
+pub struct Erlang {
+    k: u32,
+    /// Rate of each phase
+    rate: f64,
+}
+
+/// Hyperexponential distribution, a probabilistic mixture of exponential distributions.
+#[derive(Clone, Debug)]
+pub struct Hyperexponential {
+    /// Branch probabilities and means
+    branches: Vec<(f64, f64)>,
+}
+
+/// Empirical distribution with a piecewise-linear CDF through the sorted samples.
+#[derive(Clone, Debug)]
+pub struct Empirical {
+    /// Sorted samples
+    samples: Vec<f64>,
+    /// File and column the samples were read from
+    origin: Option<(String, String)>,
+}
+
+/// Argument of a distribution specification.
+#[derive(Clone, Debug)]
+enum SpecArg {
+    Number(f64),
+    Text(String),
+    Spec(Variate),
+}
+
+/// Recursive descent parser for distribution specifications.
+struct SpecParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Weibull {
+    pub fn new(shape: f64, scale: f64) -> Self {
+        Self { shape, scale }
+    }
+
+    pub fn shape(&self) -> f64 {
+        self.shape
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+}
+
+impl Distribution for Weibull {
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        match x <= 0.0 {
+            true => 0.0,
+            false => 1.0 - (-(x / self.scale).powf(self.shape)).exp(),
+        }
+    }
+}
+
+impl Inverse for Weibull {
+    fn inverse(&self, p: f64) -> Self::Value {
+        self.scale * (-(1.0 - p).ln()).powf(1.0 / self.shape)
+    }
+}
+
+impl Sample for Weibull {
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        self.inverse(source.read::<f64>())
+    }
+}
+
+impl Erlang {
+    /// Create an Erlang distribution with `k` phases and the given overall mean.
+    pub fn new(k: u32, mean: f64) -> Self {
+        Self {
+            k,
+            rate: k as f64 / mean,
+        }
+    }
+
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.k as f64 / self.rate
+    }
+}
+
+impl Distribution for Erlang {
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        distribution::Gamma::new(self.k as f64, 1.0 / self.rate).distribution(x)
+    }
+}
+
+impl Inverse for Erlang {
+    fn inverse(&self, p: f64) -> Self::Value {
+        invert_cdf(self, p, self.mean())
+    }
+}
+
+impl Sample for Erlang {
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        // uniforms are in [0, 1), so their complements are never 0
+        let log_sum = (0..self.k)
+            .map(|_| (1.0 - source.read::<f64>()).ln())
+            .sum::<f64>();
+        -log_sum / self.rate
+    }
+}
+
+impl Hyperexponential {
+    /// Create a hyperexponential distribution from `(probability, mean)` branches.
+    pub fn new(branches: Vec<(f64, f64)>) -> Self {
+        Self { branches }
+    }
+
+    pub fn branches(&self) -> &[(f64, f64)] {
+        &self.branches
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.branches.iter().map(|(p, mean)| p * mean).sum()
+    }
+}
+
+impl Distribution for Hyperexponential {
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        match x <= 0.0 {
+            true => 0.0,
+            false => self
+                .branches
+                .iter()
+                .map(|(p, mean)| p * (1.0 - (-x / mean).exp()))
+                .sum(),
+        }
+    }
+}
+
+impl Inverse for Hyperexponential {
+    fn inverse(&self, p: f64) -> Self::Value {
+        invert_cdf(self, p, self.mean())
+    }
+}
+
+impl Sample for Hyperexponential {
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        let branch = source.read::<f64>();
+        let u = source.read::<f64>();
+
+        let mut acc = 0.0;
+        let mean = self
+            .branches
+            .iter()
+            .find(|(p, _)| {
+                acc += p;
+                branch < acc
+            })
+            .or(self.branches.last())
+            .map(|(_, mean)| *mean)
+            .unwrap_or_default();
+
+        // u is in [0, 1), so its complement is never 0
+        -(1.0 - u).ln() * mean
+    }
+}
+
+impl Empirical {
+    /// Create an empirical distribution from samples.
+    ///
+    /// Panics if fewer than two samples are given.
+    pub fn new(samples: Vec<f64>) -> Self {
+        assert!(
+            samples.len() >= 2,
+            "empirical distribution requires at least 2 samples"
+        );
+
+        let mut samples = samples;
+        samples.sort_by(f64::total_cmp);
+
+        Self {
+            samples,
+            origin: None,
+        }
+    }
+
+    /// Create an empirical distribution from a column of a csv file.
+    pub fn from_csv<P: AsRef<Path>>(path: P, column: &str) -> Result<Self, String> {
+        let samples = read_csv_column(&path, column)?;
+
+        if samples.len() < 2 {
+            return Err(format!(
+                "column '{}' of {} has fewer than 2 samples",
+                column,
+                path.as_ref().display()
+            ));
+        }
+
+        Ok(Self {
+            origin: Some((path.as_ref().display().to_string(), column.to_string())),
+            ..Self::new(samples)
+        })
+    }
+
+    /// Sorted samples of the distribution.
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+
+    pub fn mean(&self) -> f64 {
+        // every segment between adjacent samples holds the same probability mass
+        let segments = self.samples.windows(2).map(|w| (w[0] + w[1]) / 2.0);
+        segments.sum::<f64>() / (self.samples.len() - 1) as f64
+    }
+}
+
+impl Distribution for Empirical {
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        let n = self.samples.len();
+
+        match self.samples.partition_point(|s| *s <= x) {
+            0 => 0.0,
+            idx if idx == n => 1.0,
+            idx => {
+                let (lo, hi) = (self.samples[idx - 1], self.samples[idx]);
+                ((idx - 1) as f64 + (x - lo) / (hi - lo)) / (n - 1) as f64
+            }
+        }
+    }
+}
+
+impl Inverse for Empirical {
+    fn inverse(&self, p: f64) -> Self::Value {
+        let pos = p.clamp(0.0, 1.0) * (self.samples.len() - 1) as f64;
+        let idx = (pos.floor() as usize).min(self.samples.len() - 2);
+        let frac = pos - idx as f64;
+
+        self.samples[idx] + frac * (self.samples[idx + 1] - self.samples[idx])
+    }
+}
+
+impl Sample for Empirical {
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        self.inverse(source.read::<f64>())
+    }
+}
+
+impl Variate {
+    /// Smallest and largest values the variate can take, which may be infinite.
+    pub fn support(&self) -> (f64, f64) {
+        match self {
+            Variate::Exponential(d) => (d.loc(), f64::INFINITY),
+            Variate::Uniform(d) => (d.a(), d.b()),
+            Variate::Normal(_) => (f64::NEG_INFINITY, f64::INFINITY),
+            Variate::Lognormal(_)
+            | Variate::Weibull(_)
+            | Variate::Gamma(_)
+            | Variate::Erlang(_)
+            | Variate::Hyperexponential(_) => (0.0, f64::INFINITY),
+            Variate::Triangular(d) => (d.a(), d.b()),
+            Variate::Empirical(d) => (d.samples[0], d.samples[d.samples.len() - 1]),
+            Variate::Truncated(d) => {
+                let ((low, high), (inner_low, inner_high)) = (d.bounds(), d.inner().support());
+                (low.max(inner_low), high.min(inner_high))
+            }
+            Variate::Clamped(d) => {
+                let ((low, high), (inner_low, inner_high)) = (d.bounds(), d.inner().support());
+                (inner_low.clamp(low, high), inner_high.clamp(low, high))
+            }
+            Variate::Scaled(d, factor) => {
+                let (low, high) = d.support();
+                (low * factor, high * factor)
+            }
+        }
+    }
+}
+
+impl Distribution for Variate {
+    type Value = f64;
+
+    fn distribution(&self, x: f64) -> f64 {
+        match self {
+            Variate::Exponential(d) => d.distribution(x),
+            Variate::Uniform(d) => d.distribution(x),
+            Variate::Normal(d) => d.distribution(x),
+            Variate::Lognormal(d) => d.distribution(x),
+            Variate::Weibull(d) => d.distribution(x),
+            Variate::Gamma(d) => d.distribution(x),
+            Variate::Erlang(d) => d.distribution(x),
+            Variate::Hyperexponential(d) => d.distribution(x),
+            Variate::Triangular(d) => d.distribution(x),
+            Variate::Empirical(d) => d.distribution(x),
+            Variate::Truncated(d) => d.distribution(x),
+            Variate::Clamped(d) => d.distribution(x),
+            Variate::Scaled(d, factor) => d.distribution(x / factor),
+        }
+    }
+}
+
+impl Inverse for Variate {
+    fn inverse(&self, p: f64) -> Self::Value {
+        match self {
+            Variate::Exponential(d) => d.inverse(p),
+            Variate::Uniform(d) => d.inverse(p),
+            Variate::Normal(d) => d.inverse(p),
+            Variate::Lognormal(d) => d.inverse(p),
+            Variate::Weibull(d) => d.inverse(p),
+            Variate::Gamma(d) => invert_cdf(d, p, d.k() * d.theta()),
+            Variate::Erlang(d) => d.inverse(p),
+            Variate::Hyperexponential(d) => d.inverse(p),
+            Variate::Triangular(d) => d.inverse(p),
+            Variate::Empirical(d) => d.inverse(p),
+            Variate::Truncated(d) => d.inverse(p),
+            Variate::Clamped(d) => d.inverse(p),
+            Variate::Scaled(d, factor) => d.inverse(p) * factor,
+        }
+    }
+}
+
+impl Sample for Variate {
+    fn sample<S>(&self, source: &mut S) -> Self::Value
+    where
+        S: Source,
+    {
+        match self {
+            Variate::Exponential(d) => d.sample(source),
+            Variate::Uniform(d) => d.sample(source),
+            Variate::Normal(d) => d.sample(source),
+            Variate::Lognormal(d) => d.sample(source),
+            Variate::Weibull(d) => d.sample(source),
+            Variate::Gamma(d) => d.sample(source),
+            Variate::Erlang(d) => d.sample(source),
+            Variate::Hyperexponential(d) => d.sample(source),
+            Variate::Triangular(d) => d.sample(source),
+            Variate::Empirical(d) => d.sample(source),
+            Variate::Truncated(d) => d.sample(source),
+            Variate::Clamped(d) => d.sample(source),
+            Variate::Scaled(d, factor) => d.sample(source) * factor,
+        }
+    }
+}
+
+impl distribution::Mean for Variate {
+    fn mean(&self) -> f64 {
+        match self {
+            Variate::Exponential(d) => d.loc() + 1.0 / d.lambda(),
+            Variate::Uniform(d) => d.mean(),
+            Variate::Normal(d) => d.mean(),
+            Variate::Lognormal(d) => d.mean(),
+            Variate::Gamma(d) => d.mean(),
+            Variate::Erlang(d) => d.mean(),
+            Variate::Hyperexponential(d) => d.mean(),
+            Variate::Triangular(d) => (d.a() + d.b() + d.c()) / 3.0,
+            Variate::Empirical(d) => d.mean(),
+            Variate::Scaled(d, factor) => d.mean() * factor,
+            Variate::Weibull(_) | Variate::Truncated(_) | Variate::Clamped(_) => {
+                mean_by_quadrature(self)
+            }
+        }
+    }
+}
+
+impl From<ExponentialLoc> for Variate {
+    fn from(value: ExponentialLoc) -> Self {
+        Variate::Exponential(value)
+    }
+}
+
+impl From<distribution::Uniform> for Variate {
+    fn from(value: distribution::Uniform) -> Self {
+        Variate::Uniform(value)
+    }
+}
+
+impl From<distribution::Gaussian> for Variate {
+    fn from(value: distribution::Gaussian) -> Self {
+        Variate::Normal(value)
+    }
+}
+
+impl From<Truncated<Variate>> for Variate {
+    fn from(value: Truncated<Variate>) -> Self {
+        Variate::Truncated(Box::new(value))
+    }
+}
+
+impl From<Clamped<Variate>> for Variate {
+    fn from(value: Clamped<Variate>) -> Self {
+        Variate::Clamped(Box::new(value))
+    }
+}
+
+impl Display for Variate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Variate::Exponential(d) => match d.loc() == 0.0 {
+                true => write!(f, "exponential({})", 1.0 / d.lambda()),
+                false => write!(f, "exponential({}, {})", 1.0 / d.lambda(), d.loc()),
+            },
+            Variate::Uniform(d) => write!(f, "uniform({}, {})", d.a(), d.b()),
+            Variate::Normal(d) => write!(f, "normal({}, {})", d.mu(), d.sigma()),
+            Variate::Lognormal(d) => write!(f, "lognormal({}, {})", d.mu(), d.sigma()),
+            Variate::Weibull(d) => write!(f, "weibull({}, {})", d.shape, d.scale),
+            Variate::Gamma(d) => write!(f, "gamma({}, {})", d.k(), d.theta()),
+            Variate::Erlang(d) => write!(f, "erlang({}, {})", d.k, d.mean()),
+            Variate::Hyperexponential(d) => {
+                let branches = d
+                    .branches
+                    .iter()
+                    .map(|(p, mean)| format!("{}, {}", p, mean))
+                    .collect::<Vec<_>>();
+                write!(f, "hyperexponential({})", branches.join(", "))
+            }
+            Variate::Triangular(d) => write!(f, "triangular({}, {}, {})", d.a(), d.c(), d.b()),
+            Variate::Empirical(d) => match &d.origin {
+                Some((file, column)) => write!(f, "empirical({:?}, {:?})", file, column),
+                None => {
+                    let samples = d.samples.iter().map(f64::to_string).collect::<Vec<_>>();
+                    write!(f, "empirical({})", samples.join(", "))
+                }
+            },
+            Variate::Truncated(d) => {
+                let (low, high) = d.bounds();
+                write!(f, "truncated({}, {}, {})", d.inner(), low, high)
+            }
+            Variate::Clamped(d) => {
+                let (low, high) = d.bounds();
+                write!(f, "clamped({}, {}, {})", d.inner(), low, high)
+            }
+            Variate::Scaled(d, factor) => write!(f, "scaled({}, {})", d, factor),
+        }
+    }
+}
+
+impl FromStr for Variate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = SpecParser { input: s, pos: 0 };
+        let variate = parser.spec()?;
+
+        parser.skip_whitespace();
+        match parser.pos == s.len() {
+            true => Ok(variate),
+            false => Err(parser.error("unexpected trailing input")),
+        }
+    }
+}
+
+impl serde::Serialize for Variate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Variate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let spec = String::deserialize(deserializer)?;
+        spec.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl<'a> SpecParser<'a> {
+    fn error(&self, msg: &str) -> String {
+        format!("{} at position {} of '{}'", msg, self.pos, self.input)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.rest().starts_with(c) {
+            true => {
+                self.pos += c.len_utf8();
+                Ok(())
+            }
+            false => Err(self.error(&format!("expected '{}'", c))),
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        self.skip_whitespace();
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+
+        let ident = &self.rest()[..len];
+        self.pos += len;
+        ident.to_ascii_lowercase()
+    }
+
+    /// Parse `name(args...)`
+    fn spec(&mut self) -> Result<Variate, String> {
+        let name = self.ident();
+        if name.is_empty() {
+            return Err(self.error("expected a distribution name"));
+        }
+
+        self.expect('(')?;
+
+        let mut args = Vec::new();
+        self.skip_whitespace();
+        if !self.rest().starts_with(')') {
+            loop {
+                args.push(self.arg()?);
+                self.skip_whitespace();
+                match self.rest().starts_with(',') {
+                    true => self.pos += 1,
+                    false => break,
+                }
+            }
+        }
+
+        self.expect(')')?;
+
+        build_variate(&name, args)
+    }
+
+    fn arg(&mut self) -> Result<SpecArg, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+
+        match rest.chars().next() {
+            Some('"') => {
+                let end = rest[1..]
+                    .find('"')
+                    .ok_or_else(|| self.error("unterminated string"))?;
+                let text = rest[1..end + 1].to_string();
+                self.pos += end + 2;
+                Ok(SpecArg::Text(text))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                let ident = self.ident();
+                self.skip_whitespace();
+
+                match (self.rest().starts_with('('), ident.as_str()) {
+                    (true, _) => {
+                        self.pos = start;
+                        Ok(SpecArg::Spec(self.spec()?))
+                    }
+                    (false, "inf") => Ok(SpecArg::Number(f64::INFINITY)),
+                    (false, _) => Err(self.error(&format!("unexpected '{}'", ident))),
+                }
+            }
+            Some(_) => {
+                let len = rest
+                    .find(|c: char| c == ',' || c == ')' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                let number = rest[..len].parse::<f64>().map_err(|e| {
+                    self.error(&format!("invalid number '{}': {}", &rest[..len], e))
+                })?;
+                self.pos += len;
+                Ok(SpecArg::Number(number))
+            }
+            None => Err(self.error("expected an argument")),
+        }
+    }
+}
+
+/// Build a variate from its parsed name and arguments, validating parameters.
+fn build_variate(name: &str, args: Vec<SpecArg>) -> Result<Variate, String> {
+    let numbers = || -> Result<Vec<f64>, String> {
+        args.iter()
+            .map(|arg| match arg {
+                SpecArg::Number(n) => Ok(*n),
+                _ => Err(format!("{}() takes only numeric arguments", name)),
+            })
+            .collect()
+    };
+
+    let arity = |numbers: &[f64], expected: &[usize]| -> Result<(), String> {
+        match expected.contains(&numbers.len()) {
+            true => Ok(()),
+            false => Err(format!(
+                "{}() takes {:?} arguments, got {}",
+                name,
+                expected,
+                numbers.len()
+            )),
+        }
+    };
+
+    let positive = |what: &str, x: f64| -> Result<f64, String> {
+        match x > 0.0 && x.is_finite() {
+            true => Ok(x),
+            false => Err(format!("{}() {} must be positive, got {}", name, what, x)),
+        }
+    };
+
+    let variate = match name {
+        "exponential" | "exp" => {
+            let n = numbers()?;
+            arity(&n, &[1, 2])?;
+            let mean = positive("mean", n[0])?;
+            Variate::Exponential(ExponentialLoc::new(
+                1.0 / mean,
+                n.get(1).copied().unwrap_or(0.0),
+            ))
+        }
+        "uniform" => {
+            let n = numbers()?;
+            arity(&n, &[2])?;
+            if n[0] >= n[1] {
+                return Err(format!("uniform() requires min < max, got {:?}", n));
+            }
+            Variate::Uniform(distribution::Uniform::new(n[0], n[1]))
+        }
+        "normal" | "gaussian" => {
+            let n = numbers()?;
+            arity(&n, &[2])?;
+            Variate::Normal(distribution::Gaussian::new(n[0], positive("stddev", n[1])?))
+        }
+        "lognormal" => {
+            let n = numbers()?;
+            arity(&n, &[2])?;
+            Variate::Lognormal(distribution::Lognormal::new(n[0], positive("sigma", n[1])?))
+        }
+        "weibull" => {
+            let n = numbers()?;
+            arity(&n, &[2])?;
+            Variate::Weibull(Weibull::new(
+                positive("shape", n[0])?,
+                positive("scale", n[1])?,
+            ))
+        }
+        "gamma" => {
+            let n = numbers()?;
+            arity(&n, &[2])?;
+            Variate::Gamma(distribution::Gamma::new(
+                positive("shape", n[0])?,
+                positive("scale", n[1])?,
+            ))
+        }
+        "erlang" => {
+            let n = numbers()?;
+            arity(&n, &[2])?;
+            if !(n[0] >= 1.0 && n[0].fract() == 0.0) {
+                return Err(format!(
+                    "erlang() k must be a positive integer, got {}",
+                    n[0]
+                ));
+            }
+            Variate::Erlang(Erlang::new(n[0] as u32, positive("mean", n[1])?))
+        }
+        "hyperexponential" | "hyperexp" => {
+            let n = numbers()?;
+            if n.is_empty() || n.len() % 2 != 0 {
+                return Err(format!(
+                    "{}() takes (probability, mean) pairs, got {} arguments",
+                    name,
+                    n.len()
+                ));
+            }
+
+            let branches = n
+                .chunks(2)
+                .map(|pair| Ok((pair[0], positive("mean", pair[1])?)))
+                .collect::<Result<Vec<_>, String>>()?;
+
+            let total = branches.iter().map(|(p, _)| p).sum::<f64>();
+            if branches.iter().any(|(p, _)| *p < 0.0) || (total - 1.0).abs() > 1e-9 {
+                return Err(format!(
+                    "{}() probabilities must be non-negative and sum to 1, got {}",
+                    name, total
+                ));
+            }
+
+            Variate::Hyperexponential(Hyperexponential::new(branches))
+        }
+        "triangular" => {
+            let n = numbers()?;
+            arity(&n, &[3])?;
+            let (min, mode, max) = (n[0], n[1], n[2]);
+            if min >= max || mode < min || mode > max {
+                return Err(format!(
+                    "triangular() requires min <= mode <= max and min < max, got {:?}",
+                    n
+                ));
+            }
+            Variate::Triangular(distribution::Triangular::new(min, max, mode))
+        }
+        "empirical" => match args.as_slice() {
+            [SpecArg::Text(file), SpecArg::Text(column)] => {
+                Variate::Empirical(Empirical::from_csv(file, column)?)
+            }
+            _ => {
+                let n = numbers()
+                    .map_err(|_| "empirical() takes samples, or a file and column".to_string())?;
+                if n.len() < 2 {
+                    return Err("empirical() requires at least 2 samples".to_string());
+                }
+                Variate::Empirical(Empirical::new(n))
+            }
+        },
+        "fit" => match args.as_slice() {
+            [SpecArg::Text(file), SpecArg::Text(column)] => {
+                fit::fit_csv_column(file, column, None)?.into()
+            }
+            [SpecArg::Text(file), SpecArg::Text(column), SpecArg::Text(family)] => {
+                fit::fit_csv_column(file, column, Some(family.parse()?))?.into()
+            }
+            _ => {
+                return Err(
+                    "fit() takes a file, a column and optionally a family, e.g. fit(\"data.csv\", \"x\", \"weibull\")"
+                        .to_string(),
+                )
+            }
+        },
+        "scaled" => match args.as_slice() {
+            [SpecArg::Spec(inner), SpecArg::Number(factor)] => {
+                Variate::Scaled(Box::new(inner.clone()), positive("factor", *factor)?)
+            }
+            _ => {
+                return Err(
+                    "scaled() takes a distribution and a factor, e.g. scaled(exponential(1), 2)"
+                        .to_string(),
+                )
+            }
+        },
+        "truncated" | "clamped" => match args.as_slice() {
+            [SpecArg::Spec(inner), SpecArg::Number(low), SpecArg::Number(high)] => {
+                if low >= high {
+                    return Err(format!("{}() requires low < high", name));
+                }
+
+                let inner = inner.clone();
+                match name {
+                    "truncated" => {
+                        if inner.distribution(*high) <= inner.distribution(*low) {
+                            return Err(format!(
+                                "truncated() interval [{}, {}] holds no probability mass",
+                                low, high
+                            ));
+                        }
+                        Truncated::new(inner, *low, *high).into()
+                    }
+                    _ => Clamped::new(inner, *low, *high).into(),
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "{}() takes a distribution and two bounds, e.g. {}(normal(0, 1), -1, 1)",
+                    name, name
+                ))
+            }
+        },
+        other => return Err(format!("unknown distribution '{}'", other)),
+    };
+
+    Ok(variate)
+}
+
+/// Mean as the integral of the inverse CDF over `(0, 1)`, by the midpoint rule.
+fn mean_by_quadrature<D: Inverse<Value = f64>>(dist: &D) -> f64 {
+    const NODES: usize = 20_000;
+
+    (0..NODES)
+        .map(|i| dist.inverse((i as f64 + 0.5) / NODES as f64))
+        .sum::<f64>()
+        / NODES as f64
+}
+
+/// Invert a continuous CDF on `[0, inf)` by bisection.
+///
+/// `scale` is a rough size of the distribution, used to bracket the inverse.
+fn invert_cdf<D: Distribution<Value = f64>>(dist: &D, p: f64, scale: f64) -> f64 {
+    if p <= 0.0 {
+        return 0.0;
+    }
+
+    let mut low = 0.0;
+    let mut high = scale.max(f64::MIN_POSITIVE);
+    while dist.distribution(high) < p {
+        if high > f64::MAX / 2.0 {
+            return f64::INFINITY;
+        }
+        low = high;
+        high *= 2.0;
+    }
+
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        match dist.distribution(mid) < p {
+            true => low = mid,
+            false => high = mid,
+        }
+    }
+
+    0.5 * (low + high)
+}
+
+/// Read a numeric column from a csv file with headers.
+///
+/// Column names are compared with surrounding whitespace trimmed.
+pub fn read_csv_column<P: AsRef<Path>>(path: P, column: &str) -> Result<Vec<f64>, String> {
+    let path = path.as_ref();
+    let mut reader =
+        csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .clone();
+
+    let col_idx = headers
+        .iter()
+        .position(|h| h.trim() == column.trim())
+        .ok_or_else(|| {
+            format!(
+                "{}: no column '{}', available columns are {:?}",
+                path.display(),
+                column,
+                headers.iter().map(str::trim).collect::<Vec<_>>()
+            )
+        })?;
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(row, record)| {
+            let record = record.map_err(|e| format!("{}: {}", path.display(), e))?;
+            let field = record.get(col_idx).unwrap_or_default().trim();
+
+            field.parse::<f64>().map_err(|e| {
+                format!(
+                    "{}: row {} of column '{}': invalid number '{}': {}",
+                    path.display(),
+                    row + 1,
+                    column,
+                    field,
+                    e
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trip() {
+        let specs = [
+            "exponential(99.83189, 10.004)",
+            "exponential(1.36982)",
+            "uniform(0, 20)",
+            "normal(120.072, 9.0186)",
+            "lognormal(4.2, 0.8)",
+            "weibull(1.5, 100)",
+            "gamma(2, 50)",
+            "erlang(3, 90)",
+            "hyperexponential(0.3, 10, 0.7, 120)",
+            "triangular(10, 60, 300)",
+            "empirical(1, 2, 4, 8)",
+            "truncated(normal(120, 9), 1, 250)",
+            "clamped(normal(120, 9), 60, inf)",
+            "scaled(exponential(1.36982), 0.5)",
+        ];
+
+        for spec in specs {
+            let variate = spec.parse::<Variate>().unwrap();
+            let reparsed = variate.to_string().parse::<Variate>().unwrap();
+
+            for x in [0.5, 1.0, 5.0, 50.0, 120.0, 200.0] {
+                assert!(
+                    (variate.distribution(x) - reparsed.distribution(x)).abs() < 1e-12,
+                    "{} does not round trip through {}",
+                    spec,
+                    variate
+                );
+            }
+        }
+
+        assert_eq!(
+            "truncated(normal(120, 9), 1, 250)"
+                .parse::<Variate>()
+                .unwrap()
+                .to_string(),
+            "truncated(normal(120, 9), 1, 250)"
+        );
+    }
+
+    #[test]
+    fn test_support() {
+        let support = |spec: &str| spec.parse::<Variate>().unwrap().support();
+
+        assert_eq!(support("exponential(2, 5)"), (5.0, f64::INFINITY));
+        assert_eq!(
+            support("normal(50, 100)"),
+            (f64::NEG_INFINITY, f64::INFINITY)
+        );
+        assert_eq!(support("empirical(3, 1, 2)"), (1.0, 3.0));
+        assert_eq!(support("truncated(normal(50, 100), 1, 500)"), (1.0, 500.0));
+        assert_eq!(support("truncated(uniform(0, 10), -5, 5)"), (0.0, 5.0));
+        assert_eq!(support("clamped(uniform(2, 3), 0, 10)"), (2.0, 3.0));
+        assert_eq!(support("clamped(normal(0, 1), -1, 1)"), (-1.0, 1.0));
+        assert_eq!(support("scaled(uniform(1, 2), 3)"), (3.0, 6.0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("lognormal(4.2)".parse::<Variate>().is_err());
+        assert!("lognormal(4.2, -1)".parse::<Variate>().is_err());
+        assert!("cauchy(0, 1)".parse::<Variate>().is_err());
+        assert!("uniform(2, 1)".parse::<Variate>().is_err());
+        assert!("erlang(1.5, 10)".parse::<Variate>().is_err());
+        assert!("hyperexp(0.5, 1, 0.4, 2)".parse::<Variate>().is_err());
+        assert!("triangular(0, 5, 2)".parse::<Variate>().is_err());
+        assert!("truncated(normal(0, 1), 50, 60)"
+            .parse::<Variate>()
+            .is_err());
+        assert!("normal(0, 1) extra".parse::<Variate>().is_err());
+        assert!("normal(0, 1".parse::<Variate>().is_err());
+        assert!("scaled(normal(0, 1), 0)".parse::<Variate>().is_err());
+    }
+
+    #[test]
+    fn test_inverse_matches_distribution() {
+        let specs = [
+            "exponential(100, 10)",
+            "weibull(1.5, 100)",
+            "gamma(2.5, 40)",
+            "erlang(3, 90)",
+            "hyperexponential(0.3, 10, 0.7, 120)",
+            "triangular(10, 60, 300)",
+            "empirical(1, 2, 4, 8)",
+            "truncated(normal(120, 9), 100, 250)",
+            "scaled(weibull(1.5, 100), 2)",
+        ];
+
+        for spec in specs {
+            let variate = spec.parse::<Variate>().unwrap();
+            for p in [0.1, 0.25, 0.5, 0.75, 0.9] {
+                let x = variate.inverse(p);
+                assert!(
+                    (variate.distribution(x) - p).abs() < 1e-6,
+                    "{}: F(F^-1({})) = {}",
+                    spec,
+                    p,
+                    variate.distribution(x)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_means() {
+        let cases = [
+            ("weibull(2, 100)", 88.6227),
+            ("erlang(3, 90)", 90.0),
+            ("hyperexponential(0.3, 10, 0.7, 120)", 87.0),
+            ("gamma(2, 50)", 100.0),
+            ("empirical(1, 2, 4, 8)", 3.5),
+            ("scaled(gamma(2, 50), 0.5)", 50.0),
+        ];
+
+        let mut source = probability::source::default(7);
+        for (spec, mean) in cases {
+            let variate = spec.parse::<Variate>().unwrap();
+            let n = 200_000;
+            let avg = (0..n).map(|_| variate.sample(&mut source)).sum::<f64>() / n as f64;
+            assert!(
+                (avg - mean).abs() / mean < 0.02,
+                "{}: sample mean {} vs {}",
+                spec,
+                avg,
+                mean
+            );
+            assert!(
+                (distribution::Mean::mean(&variate) - mean).abs() / mean < 0.002,
+                "{}: mean {}",
+                spec,
+                distribution::Mean::mean(&variate)
+            );
+        }
+
+        let truncated = "truncated(normal(0, 1), 0, inf)"
+            .parse::<Variate>()
+            .unwrap();
+        let half_normal_mean = (2.0 / std::f64::consts::PI).sqrt();
+        assert!((distribution::Mean::mean(&truncated) - half_normal_mean).abs() < 1e-3);
+    }
+}